@@ -1,935 +1,8526 @@
-use sqlx::{migrate::MigrateDatabase, Sqlite, SqlitePool, Row};
+use sqlx::query::Query;
+use sqlx::sqlite::{SqliteArguments, SqliteConnectOptions, SqliteQueryResult};
+use sqlx::{migrate::MigrateDatabase, Column, Connection, Execute, QueryBuilder, Sqlite, SqliteConnection, SqlitePool, Row, TypeInfo, ValueRef};
+use crate::error::{conflict_error, database_busy_error, map_constraint_error, not_found_error, validation_error, CorruptDatabaseError};
+use crate::export::{event_to_google_csv_row, note_to_markdown, parse_note_markdown, report_to_markdown, slugify, to_csv, GOOGLE_CSV_HEADERS};
+use crate::search;
+use crate::quickadd;
+use crate::validation;
 use crate::models::*;
-use chrono::Utc;
+use serde::{de::DeserializeOwned, Deserialize};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc, Weekday};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 
-pub struct DatabaseService {
-    pool: SqlitePool,
+// 每次调整表结构时加一，并在 run_migrations 中加上升级到该版本的步骤
+const SCHEMA_VERSION: i64 = 4;
+
+const TABLES: [&str; 14] = [
+    "calendar_events",
+    "event_exceptions",
+    "habits",
+    "habit_records",
+    "habit_skips",
+    "habit_pauses",
+    "todos",
+    "subtasks",
+    "pomodoro_sessions",
+    "pomodoro_settings",
+    "notes",
+    "note_templates",
+    "entity_links",
+    "app_settings",
+];
+const PRIORITIES: [&str; 3] = ["low", "medium", "high"];
+const POMODORO_SESSION_TYPES: [&str; 3] = ["work", "short_break", "long_break"];
+// 可以配置默认分类的实体，未配置时使用 schema 中的 'general'
+const DEFAULT_CATEGORY_ENTITIES: [&str; 2] = ["todo", "note"];
+const ATTENDEE_STATUSES: [&str; 4] = ["unknown", "accepted", "declined", "tentative"];
+const DEFAULT_EVENT_TYPES: [&str; 6] = ["work", "personal", "health", "study", "meeting", "other"];
+// 置顶时排到最后，取消置顶时清空排序值；绑定一个表示是否置顶的参数
+const PIN_ORDER_ASSIGNMENT: &str = "pin_order = CASE WHEN ? THEN COALESCE(pin_order, (SELECT COALESCE(MAX(pin_order), 0) + 1 FROM notes WHERE is_pinned = TRUE)) ELSE NULL END";
+
+const JOURNAL_TAG: &str = "journal";
+
+const DEFAULT_NOTE_COLORS: [(&str, &str); 6] = [
+    ("黄色", "#fef3c7"),
+    ("绿色", "#d1fae5"),
+    ("蓝色", "#dbeafe"),
+    ("紫色", "#e9d5ff"),
+    ("粉色", "#fce7f3"),
+    ("橙色", "#fed7aa"),
+];
+
+// 新习惯未指定颜色时使用的调色板，均能与浅色和深色背景区分
+const HABIT_COLOR_PALETTE: [&str; 8] = [
+    "#2563eb", "#16a34a", "#dc2626", "#d97706", "#9333ea", "#db2777", "#0891b2", "#4f46e5",
+];
+
+// 选择现有习惯中用得最少的调色板颜色，数量相同时取调色板中靠前的
+fn next_habit_color(existing: &[Habit]) -> &'static str {
+    HABIT_COLOR_PALETTE
+        .iter()
+        .copied()
+        .min_by_key(|color| {
+            existing
+                .iter()
+                .filter(|habit| habit.color.eq_ignore_ascii_case(color))
+                .count()
+        })
+        .unwrap_or(HABIT_COLOR_PALETTE[0])
 }
 
-impl DatabaseService {
-    pub async fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let database_url = "sqlite://toolbox.db";
-        
-        // 创建数据库（如果不存在）
-        if !Sqlite::database_exists(database_url).await.unwrap_or(false) {
-            Sqlite::create_database(database_url).await?;
+// 校验 #rgb / #rrggbb 颜色，统一为小写的 #rrggbb
+fn normalize_hex_color(color: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let digits = color
+        .trim()
+        .strip_prefix('#')
+        .filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| validation_error(format!("无效的颜色: {}", color)))?;
+
+    match digits.len() {
+        3 => Ok(digits
+            .chars()
+            .fold(String::from("#"), |mut hex, c| {
+                hex.push(c);
+                hex.push(c);
+                hex
+            })
+            .to_ascii_lowercase()),
+        6 => Ok(format!("#{}", digits.to_ascii_lowercase())),
+        _ => Err(validation_error(format!("无效的颜色: {}", color))),
+    }
+}
+
+// 标题/名称去掉首尾空白，内部连续空白合并为一个空格，结果为空时报错
+fn sanitize_title(title: &str, field: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    if title.is_empty() {
+        return Err(validation_error(format!("{}不能为空", field)));
+    }
+    Ok(title)
+}
+
+// 关联不分方向，统一把便笺放在 source 一侧，避免同一对实体存两条
+fn normalize_link<'a>(a: (&'a str, &'a str), b: (&'a str, &'a str)) -> Result<((&'a str, &'a str), (&'a str, &'a str)), Box<dyn std::error::Error>> {
+    match (a.0, b.0) {
+        ("note", "todo" | "event") => Ok((a, b)),
+        ("todo" | "event", "note") => Ok((b, a)),
+        (source, target) => Err(validation_error(format!("不支持的关联类型: {} 与 {}", source, target))),
+    }
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, Box<dyn std::error::Error>> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| validation_error(format!("无效的日期: {}", date)))
+}
+
+// 替换模板中的 {{date}} 和 {{title}} 占位符，其他花括号内容原样保留
+fn render_note_template(text: &str, title: &str, date: &str) -> String {
+    text.replace("{{date}}", date).replace("{{title}}", title)
+}
+
+// 日期都是 YYYY-MM-DD，按字符串比较即可；起始晚于结束的范围视为空范围而不是错误
+fn is_reversed_range(start_date: &str, end_date: &str) -> bool {
+    start_date > end_date
+}
+
+// 排序方式只能取固定值，拼接进 SQL 是安全的
+fn todo_order_by(sort_by: Option<&str>) -> Result<&'static str, Box<dyn std::error::Error>> {
+    match sort_by.unwrap_or("created") {
+        "created" => Ok("created_at DESC"),
+        "due_date" => Ok("due_date IS NULL, due_date ASC, created_at DESC"),
+        "priority" => Ok("CASE priority WHEN 'high' THEN 0 WHEN 'medium' THEN 1 WHEN 'low' THEN 2 ELSE 3 END, created_at DESC"),
+        other => Err(validation_error(format!("不支持的排序方式: {}", other))),
+    }
+}
+
+// 损坏的 JSON 列表降级为空列表并记录警告，避免一行坏数据导致整个列表查询失败
+fn sanitize_json_list(value: &mut Option<String>, table: &str, id: &str) {
+    if let Some(raw) = value {
+        if serde_json::from_str::<Vec<serde_json::Value>>(raw).is_err() {
+            tracing::warn!(table, id, raw = %raw, "JSON 列表已损坏，按空列表处理");
+            *value = Some("[]".to_string());
         }
+    }
+}
 
-        let pool = SqlitePool::connect(database_url).await?;
+// 字符串升级为参与者对象；无法识别的条目原样保留并记录警告，不丢弃数据
+fn upgrade_attendee_entries(entries: Vec<serde_json::Value>, event_id: &str) -> Vec<serde_json::Value> {
+    entries
+        .into_iter()
+        .map(|entry| match entry {
+            serde_json::Value::String(name) => serde_json::json!({ "name": name, "email": null, "status": "unknown" }),
+            other => {
+                if serde_json::from_value::<Attendee>(other.clone()).is_err() {
+                    tracing::warn!(event_id, entry = %other, "无法识别的参与者条目，保留原值");
+                }
+                other
+            }
+        })
+        .collect()
+}
+
+fn sanitize_event(mut event: CalendarEvent) -> CalendarEvent {
+    sanitize_json_list(&mut event.attendees, "calendar_events", &event.id);
+    event.duration_minutes = event_duration_minutes(&event);
+    event
+}
 
-        // 创建所有表
-        Self::create_tables(&pool).await?;
+// 日程时间为 HH:MM，旧数据中可能带秒
+fn parse_event_time(time: &str) -> Result<NaiveTime, Box<dyn std::error::Error>> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M:%S"))
+        .map_err(|_| validation_error(format!("无效的时间: {}", time)))
+}
 
-        Ok(DatabaseService { pool })
+fn validate_event_times(start_time: Option<&str>, end_time: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    for time in [start_time, end_time].into_iter().flatten() {
+        if !time.is_empty() {
+            parse_event_time(time)?;
+        }
     }
+    Ok(())
+}
 
-    async fn create_tables(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Error>> {
-        // 日程事件表
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS calendar_events (
-                id TEXT PRIMARY KEY,
-                title TEXT NOT NULL,
-                description TEXT,
-                date TEXT NOT NULL,
-                start_time TEXT,
-                end_time TEXT,
-                event_type TEXT NOT NULL,
-                priority TEXT NOT NULL,
-                is_all_day BOOLEAN NOT NULL,
-                reminder INTEGER,
-                repeat_type TEXT,
-                location TEXT,
-                attendees TEXT,
-                created_at DATETIME NOT NULL,
-                updated_at DATETIME NOT NULL
-            )
-            "#,
-        )
-        .execute(pool)
-        .await?;
+// 同一天内的分钟数，结束时间早于开始时间视为次日结束，加 24 小时
+fn time_span_minutes(start_time: &str, end_time: &str) -> Result<i64, Box<dyn std::error::Error>> {
+    let minutes = (parse_event_time(end_time)? - parse_event_time(start_time)?).num_minutes();
+    Ok(if minutes < 0 { minutes + 24 * 60 } else { minutes })
+}
 
-        // 习惯表
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS habits (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                description TEXT,
-                category TEXT NOT NULL,
-                color TEXT NOT NULL,
-                target INTEGER NOT NULL,
-                unit TEXT NOT NULL,
-                frequency TEXT NOT NULL,
-                is_active BOOLEAN NOT NULL,
-                created_at DATETIME NOT NULL,
-                updated_at DATETIME NOT NULL
-            )
-            "#,
-        )
-        .execute(pool)
-        .await?;
+// 全天日程或缺少/无法解析时间时为 None；多日日程按结束日期计算跨天的部分
+fn event_duration_minutes(event: &CalendarEvent) -> Option<i64> {
+    if event.is_all_day {
+        return None;
+    }
+    let (start_time, end_time) = (event.start_time.as_deref()?, event.end_time.as_deref()?);
 
-        // 习惯记录表
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS habit_records (
-                id TEXT PRIMARY KEY,
-                habit_id TEXT NOT NULL,
-                date TEXT NOT NULL,
-                completed BOOLEAN NOT NULL,
-                value INTEGER,
-                note TEXT,
-                created_at DATETIME NOT NULL,
-                FOREIGN KEY (habit_id) REFERENCES habits (id) ON DELETE CASCADE
-            )
-            "#,
-        )
-        .execute(pool)
-        .await?;
+    let extra_days = match event.end_date.as_deref() {
+        Some(end_date) => (parse_date(end_date).ok()? - parse_date(&event.date).ok()?).num_days(),
+        None => 0,
+    };
+    if extra_days > 0 {
+        let minutes = (parse_event_time(end_time).ok()? - parse_event_time(start_time).ok()?).num_minutes();
+        Some(extra_days * 24 * 60 + minutes)
+    } else {
+        time_span_minutes(start_time, end_time).ok()
+    }
+}
 
-        // 待办事项表
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS todos (
-                id TEXT PRIMARY KEY,
-                title TEXT NOT NULL,
-                description TEXT,
-                completed BOOLEAN NOT NULL DEFAULT FALSE,
-                priority TEXT NOT NULL,
-                tags TEXT,
-                due_date TEXT,
-                category TEXT NOT NULL DEFAULT 'general',
-                created_at DATETIME NOT NULL,
-                updated_at DATETIME NOT NULL
-            )
-            "#,
-        )
-        .execute(pool)
-        .await?;
+fn sanitize_todo(mut todo: Todo) -> Todo {
+    sanitize_json_list(&mut todo.tags, "todos", &todo.id);
+    todo
+}
 
-        // 子任务表
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS subtasks (
-                id TEXT PRIMARY KEY,
-                todo_id TEXT NOT NULL,
-                title TEXT NOT NULL,
-                completed BOOLEAN NOT NULL DEFAULT FALSE,
-                created_at DATETIME NOT NULL,
-                FOREIGN KEY (todo_id) REFERENCES todos (id) ON DELETE CASCADE
-            )
-            "#,
-        )
-        .execute(pool)
-        .await?;
+fn sanitize_note(mut note: Note) -> Note {
+    sanitize_json_list(&mut note.tags, "notes", &note.id);
+    note
+}
 
-        // 番茄钟会话表
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS pomodoro_sessions (
-                id TEXT PRIMARY KEY,
-                session_type TEXT NOT NULL,
-                duration INTEGER NOT NULL,
-                completed BOOLEAN NOT NULL DEFAULT FALSE,
-                task_title TEXT,
-                notes TEXT,
-                date TEXT NOT NULL,
-                started_at DATETIME,
-                ended_at DATETIME,
-                created_at DATETIME NOT NULL
-            )
-            "#,
-        )
-        .execute(pool)
-        .await?;
+// 接受 RFC 3339（换算为本地时间）或本地时间 "YYYY-MM-DD HH:MM[:SS]" / "YYYY-MM-DDTHH:MM[:SS]"
+fn parse_local_datetime(value: &str) -> Result<NaiveDateTime, Box<dyn std::error::Error>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.with_timezone(&Local).naive_local());
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .ok_or_else(|| validation_error(format!("无效的时间: {}", value)))
+}
 
-        // 番茄钟设置表
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS pomodoro_settings (
-                id TEXT PRIMARY KEY,
-                work_time INTEGER NOT NULL DEFAULT 25,
-                short_break INTEGER NOT NULL DEFAULT 5,
-                long_break INTEGER NOT NULL DEFAULT 15,
-                long_break_interval INTEGER NOT NULL DEFAULT 4,
-                auto_start_breaks BOOLEAN NOT NULL DEFAULT FALSE,
-                auto_start_work BOOLEAN NOT NULL DEFAULT FALSE,
-                notification_enabled BOOLEAN NOT NULL DEFAULT TRUE,
-                created_at DATETIME NOT NULL,
-                updated_at DATETIME NOT NULL
-            )
-            "#,
-        )
-        .execute(pool)
-        .await?;
+// 截止日期可能是 YYYY-MM-DD 或 YYYY-MM-DD HH:mm:ss，只取日期部分
+pub(crate) fn parse_due_date(due_date: &str) -> Option<NaiveDate> {
+    due_date
+        .get(..10)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+}
 
-        // 便笺表
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS notes (
-                id TEXT PRIMARY KEY,
-                title TEXT NOT NULL,
-                content TEXT NOT NULL,
-                tags TEXT,
-                category TEXT NOT NULL DEFAULT 'general',
-                color TEXT NOT NULL DEFAULT '#fef3c7',
-                is_pinned BOOLEAN NOT NULL DEFAULT FALSE,
-                is_archived BOOLEAN NOT NULL DEFAULT FALSE,
-                created_at DATETIME NOT NULL,
-                updated_at DATETIME NOT NULL
-            )
-            "#,
-        )
-        .execute(pool)
-        .await?;
+// 计算重复日程在 [start, end] 内的所有发生日期，不重复的日程只发生在 first 当天
+fn occurrence_dates(first: NaiveDate, repeat_type: Option<&str>, start: NaiveDate, end: NaiveDate) -> Vec<NaiveDate> {
+    let mut dates = Vec::new();
+    if first > end {
+        return dates;
+    }
 
-        // 插入默认番茄钟设置（如果不存在）
-        let exists = sqlx::query("SELECT COUNT(*) as count FROM pomodoro_settings")
-            .fetch_one(pool)
-            .await?
-            .get::<i64, _>("count");
+    match repeat_type.unwrap_or("none") {
+        repeat @ ("daily" | "weekly") => {
+            let step = if repeat == "daily" { 1 } else { 7 };
+            let offset = (start - first).num_days();
+            let skipped = if offset > 0 { (offset + step - 1) / step } else { 0 };
+            let mut day = first + Duration::days(skipped * step);
+            while day <= end {
+                dates.push(day);
+                day += Duration::days(step);
+            }
+        }
+        repeat @ ("monthly" | "yearly") => {
+            let step = if repeat == "monthly" { 1 } else { 12 };
+            let mut months = first.month0() as i32;
+            loop {
+                let year = first.year() + months.div_euclid(12);
+                let month = months.rem_euclid(12) as u32 + 1;
+                match NaiveDate::from_ymd_opt(year, month, 1) {
+                    Some(month_start) if month_start <= end => {}
+                    _ => break,
+                }
+                // 当月没有这一天（如 31 号、2 月 29 日）时跳过
+                if let Some(day) = NaiveDate::from_ymd_opt(year, month, first.day()) {
+                    if day > end {
+                        break;
+                    }
+                    if day >= start {
+                        dates.push(day);
+                    }
+                }
+                months += step;
+            }
+        }
+        _ => {
+            if first >= start {
+                dates.push(first);
+            }
+        }
+    }
 
-        if exists == 0 {
-            let id = Uuid::new_v4().to_string();
-            let now = Utc::now();
-            sqlx::query(
-                r#"
-                INSERT INTO pomodoro_settings (
-                    id, work_time, short_break, long_break, long_break_interval,
-                    auto_start_breaks, auto_start_work, notification_enabled,
-                    created_at, updated_at
-                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-                "#,
-            )
-            .bind(id)
-            .bind(25)
-            .bind(5)
-            .bind(15)
-            .bind(4)
-            .bind(false)
-            .bind(false)
-            .bind(true)
-            .bind(now)
-            .bind(now)
-            .execute(pool)
-            .await?;
+    dates
+}
+
+// 重复日程的最后一次允许发生的日期：repeat_until 与第 repeat_count 次发生取较早者；不重复或没有限制时为 None
+fn repeat_end(first: NaiveDate, event: &CalendarEvent) -> Option<NaiveDate> {
+    let repeat = event.repeat_type.as_deref().unwrap_or("none");
+    if repeat == "none" {
+        return None;
+    }
+
+    let until = event.repeat_until.as_deref().and_then(|date| parse_date(date).ok());
+    let by_count = event.repeat_count.and_then(|count| nth_occurrence(first, repeat, count.max(1) as u32));
+    match (until, by_count) {
+        (Some(until), Some(by_count)) => Some(until.min(by_count)),
+        (until, by_count) => until.or(by_count),
+    }
+}
+
+// 第 n 次（从 1 开始）发生的日期，每月/每年重复时跳过当月没有的那一天
+fn nth_occurrence(first: NaiveDate, repeat_type: &str, n: u32) -> Option<NaiveDate> {
+    match repeat_type {
+        "daily" => first.checked_add_signed(Duration::days(n as i64 - 1)),
+        "weekly" => first.checked_add_signed(Duration::days(7 * (n as i64 - 1))),
+        "monthly" | "yearly" => {
+            let step = if repeat_type == "monthly" { 1 } else { 12 };
+            let mut months = first.month0() as i32;
+            let mut found = 0;
+            loop {
+                let year = first.year() + months.div_euclid(12);
+                if year > 9999 {
+                    return None;
+                }
+                if let Some(day) = NaiveDate::from_ymd_opt(year, months.rem_euclid(12) as u32 + 1, first.day()) {
+                    found += 1;
+                    if found == n {
+                        return Some(day);
+                    }
+                }
+                months += step;
+            }
         }
+        _ => Some(first),
+    }
+}
 
-        Ok(())
+// 在 [start, end] 内展开日程的发生日期，并遵守重复的结束日期和次数上限
+fn event_occurrence_dates(event: &CalendarEvent, first: NaiveDate, start: NaiveDate, end: NaiveDate) -> Vec<NaiveDate> {
+    let end = repeat_end(first, event).map_or(end, |last| last.min(end));
+    occurrence_dates(first, event.repeat_type.as_deref(), start, end)
+}
+
+fn validate_repeat_limit(date: &str, repeat_until: Option<&str>, repeat_count: Option<i32>) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(repeat_until) = repeat_until {
+        if parse_date(repeat_until)? < parse_date(date)? {
+            return Err(validation_error("重复结束日期不能早于开始日期"));
+        }
+    }
+    if let Some(count) = repeat_count {
+        if count < 1 {
+            return Err(validation_error(format!("重复次数至少为 1，当前为 {}", count)));
+        }
     }
 
-    // 日程事件相关方法
-    pub async fn create_event(&self, request: CreateEventRequest) -> Result<CalendarEvent, Box<dyn std::error::Error>> {
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
-        let attendees_json = if let Some(attendees) = &request.attendees {
-            Some(serde_json::to_string(attendees)?)
-        } else {
-            None
-        };
+    Ok(())
+}
 
-        sqlx::query(
-            r#"
+fn apply_occurrence_override(event: &mut CalendarEvent, changes: EventOccurrenceOverride) {
+    if let Some(title) = changes.title {
+        event.title = title;
+    }
+    if changes.description.is_some() {
+        event.description = changes.description;
+    }
+    if changes.start_time.is_some() {
+        event.start_time = changes.start_time;
+    }
+    if changes.end_time.is_some() {
+        event.end_time = changes.end_time;
+    }
+    if let Some(priority) = changes.priority {
+        event.priority = priority;
+    }
+    if let Some(is_all_day) = changes.is_all_day {
+        event.is_all_day = is_all_day;
+    }
+    if changes.location.is_some() {
+        event.location = changes.location;
+    }
+    event.duration_minutes = event_duration_minutes(event);
+}
+
+// day 所在周的第一天，week_start 为周日或周一
+fn week_start_of(day: NaiveDate, week_start: Weekday) -> NaiveDate {
+    let offset = (7 + day.weekday().num_days_from_monday() - week_start.num_days_from_monday()) % 7;
+    day - Duration::days(offset as i64)
+}
+
+// 按周统计连续达标的周数，返回 (当前连续, 最长连续)；本周尚未达标不算中断
+fn weekly_streaks(completed: &HashSet<NaiveDate>, target: i32, first_day: NaiveDate, today: NaiveDate, week_start: Weekday) -> (i32, i32) {
+    let mut counts: HashMap<NaiveDate, i32> = HashMap::new();
+    for day in completed.iter().filter(|day| **day <= today) {
+        *counts.entry(week_start_of(*day, week_start)).or_default() += 1;
+    }
+
+    let this_week = week_start_of(today, week_start);
+    let mut week = week_start_of(first_day, week_start);
+    let mut run = 0;
+    let mut longest = 0;
+    while week <= this_week {
+        if counts.get(&week).copied().unwrap_or(0) >= target {
+            run += 1;
+            longest = longest.max(run);
+        } else if week != this_week {
+            run = 0;
+        }
+        week += Duration::days(7);
+    }
+
+    (run, longest)
+}
+
+// 每完成 long_break_interval 个工作番茄后长休息；还没有完成的番茄时不算
+fn should_take_long_break(completed_work_sessions_today: i64, long_break_interval: i32) -> bool {
+    completed_work_sessions_today > 0 && completed_work_sessions_today % long_break_interval.max(1) as i64 == 0
+}
+
+fn validate_weekly_target(weekly_target: Option<i32>) -> Result<(), Box<dyn std::error::Error>> {
+    match weekly_target {
+        Some(target) if !(1..=7).contains(&target) => Err(validation_error("每周目标次数必须在 1 到 7 之间")),
+        _ => Ok(()),
+    }
+}
+
+// 追加 "(?, ?, ...)" 并逐个绑定 id，批量操作一律通过这里拼接 IN 子句，不直接拼接字符串
+// 空列表生成空的子查询，使 IN 恒为假、NOT IN 恒为真
+fn push_id_list(builder: &mut QueryBuilder<'_, Sqlite>, ids: &[String]) {
+    if ids.is_empty() {
+        builder.push("(SELECT NULL WHERE 0)");
+        return;
+    }
+    builder.push("(");
+    let mut separated = builder.separated(", ");
+    for id in ids {
+        separated.push_bind(id.clone());
+    }
+    builder.push(")");
+}
+
+// 习惯频率：frequency 字段为 "daily"、"weekly"，或 {"type":"custom","days":[1,3,5]}（1 = 周一 … 7 = 周日）
+#[derive(Debug, Clone, PartialEq)]
+enum HabitFrequency {
+    Daily,
+    Weekly,
+    Custom(Vec<Weekday>),
+}
+
+#[derive(Deserialize)]
+struct CustomFrequencySpec {
+    #[serde(rename = "type")]
+    kind: String,
+    days: Vec<u32>,
+}
+
+const ISO_WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+fn parse_frequency(frequency: &str) -> Result<HabitFrequency, Box<dyn std::error::Error>> {
+    match frequency.trim() {
+        "daily" => Ok(HabitFrequency::Daily),
+        "weekly" => Ok(HabitFrequency::Weekly),
+        raw if raw.starts_with('{') => {
+            let spec: CustomFrequencySpec = serde_json::from_str(raw)
+                .map_err(|e| validation_error(format!("无效的自定义频率: {}", e)))?;
+            if spec.kind != "custom" {
+                return Err(validation_error(format!("无效的频率类型: {}", spec.kind)));
+            }
+
+            let mut days = Vec::with_capacity(spec.days.len());
+            for day in spec.days {
+                if !(1..=7).contains(&day) {
+                    return Err(validation_error(format!("星期必须在 1 到 7 之间: {}", day)));
+                }
+                let weekday = ISO_WEEKDAYS[day as usize - 1];
+                if !days.contains(&weekday) {
+                    days.push(weekday);
+                }
+            }
+            if days.is_empty() {
+                return Err(validation_error("自定义频率至少需要选择一天"));
+            }
+            days.sort_by_key(|day| day.num_days_from_monday());
+            Ok(HabitFrequency::Custom(days))
+        }
+        other => Err(validation_error(format!("无效的习惯频率: {}", other))),
+    }
+}
+
+// 读取已有数据时无法识别的频率按每日处理，只有新建/更新时才严格校验
+fn habit_frequency(frequency: &str) -> HabitFrequency {
+    parse_frequency(frequency).unwrap_or(HabitFrequency::Daily)
+}
+
+fn is_scheduled_on(frequency: &str, day: NaiveDate) -> bool {
+    match habit_frequency(frequency) {
+        HabitFrequency::Custom(days) => days.contains(&day.weekday()),
+        HabitFrequency::Daily | HabitFrequency::Weekly => true,
+    }
+}
+
+// 跳过的日期和自定义频率之外的日期既不算完成也不算中断
+fn is_habit_day_skipped(habit: &Habit, skips: &HashSet<NaiveDate>, day: NaiveDate) -> bool {
+    !is_scheduled_on(&habit.frequency, day)
+        || (habit.skip_weekends && matches!(day.weekday(), Weekday::Sat | Weekday::Sun))
+        || skips.contains(&day)
+}
+
+fn validate_attendees(attendees: Option<&[Attendee]>) -> Result<(), Box<dyn std::error::Error>> {
+    for attendee in attendees.unwrap_or_default() {
+        if attendee.name.trim().is_empty() {
+            return Err(validation_error("参与者姓名不能为空"));
+        }
+        if !ATTENDEE_STATUSES.contains(&attendee.status.as_str()) {
+            return Err(validation_error(format!("无效的参与者状态: {}", attendee.status)));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_priority(priority: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if PRIORITIES.contains(&priority) {
+        Ok(())
+    } else {
+        Err(validation_error(format!("无效的优先级: {}", priority)))
+    }
+}
+
+fn validate_session_type(session_type: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if POMODORO_SESSION_TYPES.contains(&session_type) {
+        Ok(())
+    } else {
+        Err(validation_error(format!("无效的番茄钟会话类型: {}", session_type)))
+    }
+}
+
+// 解析并校验保留策略 JSON：字段不能多也不能少，每种实体只允许特定的动作，且同一实体和动作只能出现一次
+fn parse_retention_policy(raw: &str) -> Result<RetentionPolicy, Box<dyn std::error::Error>> {
+    let policy: RetentionPolicy = serde_json::from_str(raw)
+        .map_err(|e| validation_error(format!("保留策略格式不正确: {}", e)))?;
+
+    let mut seen = HashSet::new();
+    for rule in &policy.rules {
+        if rule.older_than_days == 0 {
+            return Err(validation_error("保留天数必须大于 0"));
+        }
+        if let (RetentionEntity::PomodoroSession, RetentionAction::Archive) = (rule.entity, rule.action) {
+            return Err(validation_error("番茄钟会话没有归档状态，只能清除"));
+        }
+        if !seen.insert((rule.entity, rule.action)) {
+            return Err(validation_error("同一实体的同一动作只能配置一条规则"));
+        }
+    }
+
+    Ok(policy)
+}
+
+const MAX_BUSY_RETRIES: u32 = 4;
+const BUSY_RETRY_BASE_DELAY_MS: u64 = 50;
+
+// SQLITE_BUSY (5) 或 SQLITE_LOCKED (6)，扩展错误码的低 8 位是主错误码
+fn is_busy_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    match error.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::Database(db_error)) => db_error
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .is_some_and(|code| matches!(code & 0xff, 5 | 6)),
+        _ => false,
+    }
+}
+
+// SQLITE_CORRUPT / SQLITE_NOTADB 及其扩展码，或完整性检查未通过
+fn is_corrupt_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    if error.is::<CorruptDatabaseError>() {
+        return true;
+    }
+    match error.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::Database(db_error)) => db_error
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .is_some_and(|code| matches!(code & 0xff, 11 | 26)),
+        _ => false,
+    }
+}
+
+// with_transaction 闭包返回的 future，借用事务连接
+pub type TxFuture<'c, T> = Pin<Box<dyn Future<Output = Result<T, Box<dyn std::error::Error>>> + Send + 'c>>;
+
+// 数据库被锁时按指数退避重新执行 operation，其他错误直接返回；事务和单条写语句共用
+async fn retry_on_busy<T, F, Fut>(mut operation: F) -> Result<T, Box<dyn std::error::Error>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Box<dyn std::error::Error>>>,
+{
+    let mut attempt = 0;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(e) if !is_busy_error(e.as_ref()) => return Err(e),
+            Err(_) if attempt == MAX_BUSY_RETRIES => return Err(database_busy_error()),
+            Err(_) => {}
+        }
+
+        tracing::warn!(attempt, "数据库被锁定，稍后重试");
+        tokio::time::sleep(std::time::Duration::from_millis(BUSY_RETRY_BASE_DELAY_MS << attempt)).await;
+        attempt += 1;
+    }
+}
+
+// 不在事务中的单条写语句用 execute_with_retry 代替 execute，被锁时同样重试；
+// execute 会消耗绑定的参数，所以先取出 SQL 和参数，每次重试重新构建查询
+trait ExecuteWithRetry<'q> {
+    fn execute_with_retry<'p>(self, pool: &'p SqlitePool) -> TxFuture<'p, SqliteQueryResult>
+    where
+        'q: 'p;
+}
+
+impl<'q> ExecuteWithRetry<'q> for Query<'q, Sqlite, SqliteArguments<'q>> {
+    fn execute_with_retry<'p>(mut self, pool: &'p SqlitePool) -> TxFuture<'p, SqliteQueryResult>
+    where
+        'q: 'p,
+    {
+        let sql = self.sql();
+        let arguments = self.take_arguments().unwrap_or_default();
+        Box::pin(retry_on_busy(move || {
+            let query = sqlx::query_with(sql, arguments.clone());
+            async move { query.execute(pool).await.map_err(|e| -> Box<dyn std::error::Error> { Box::new(e) }) }
+        }))
+    }
+}
+
+const UNDO_LOG_CAPACITY: usize = 20;
+
+// 修改或删除前的数据快照，删除时一并保存会被级联删除的子记录和实体关联
+#[derive(Clone)]
+enum UndoSnapshot {
+    Event { event: CalendarEvent, exceptions: Vec<EventException>, links: Vec<EntityLink> },
+    Todo { todo: Todo, subtasks: Vec<Subtask>, links: Vec<EntityLink> },
+    Note { note: Note, links: Vec<EntityLink> },
+    Habit { habit: Habit, records: Vec<HabitRecord>, skips: Vec<HabitSkip>, pauses: Vec<HabitPause> },
+}
+
+impl UndoSnapshot {
+    fn entity(&self) -> &'static str {
+        match self {
+            UndoSnapshot::Event { .. } => "event",
+            UndoSnapshot::Todo { .. } => "todo",
+            UndoSnapshot::Note { .. } => "note",
+            UndoSnapshot::Habit { .. } => "habit",
+        }
+    }
+
+    fn id(&self) -> &str {
+        match self {
+            UndoSnapshot::Event { event, .. } => &event.id,
+            UndoSnapshot::Todo { todo, .. } => &todo.id,
+            UndoSnapshot::Note { note, .. } => &note.id,
+            UndoSnapshot::Habit { habit, .. } => &habit.id,
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            UndoSnapshot::Event { event, .. } => format!("日程「{}」", event.title),
+            UndoSnapshot::Todo { todo, .. } => format!("待办「{}」", todo.title),
+            UndoSnapshot::Note { note, .. } => format!("便笺「{}」", note.title),
+            UndoSnapshot::Habit { habit, .. } => format!("习惯「{}」", habit.name),
+        }
+    }
+}
+
+struct UndoEntry {
+    action: &'static str, // 'updated' 或 'deleted'
+    snapshot: UndoSnapshot,
+}
+
+// 高频切换完成状态时先排队，合并后在一个事务中写入；只保存在内存中
+pub const PENDING_FLUSH_WINDOW_MS: u64 = 500;
+
+#[derive(Default)]
+struct PendingWrites {
+    toggles: Vec<(&'static str, String)>, // (实体类型, id)，按收到的顺序排列
+    queued_since: Option<std::time::Instant>,
+}
+
+// 最近的修改/删除操作，只保存在内存中，应用重启后清空
+#[derive(Default)]
+struct UndoLog {
+    entries: VecDeque<UndoEntry>,
+}
+
+impl UndoLog {
+    fn push(&mut self, entry: UndoEntry) {
+        if self.entries.len() == UNDO_LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+// 以下写入函数供撤销和备份导入共用：主记录按 id 覆盖，子记录只补回缺失的行
+async fn upsert_event(conn: &mut SqliteConnection, event: &CalendarEvent) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO calendar_events (
+            id, title, description, date, end_date, start_time, end_time, event_type, priority,
+            is_all_day, reminder, repeat_type, repeat_until, repeat_count, location, attendees, is_favorite, notification_enabled, created_at, updated_at
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET
+            title = excluded.title, description = excluded.description, date = excluded.date,
+            end_date = excluded.end_date, start_time = excluded.start_time, end_time = excluded.end_time,
+            event_type = excluded.event_type, priority = excluded.priority, is_all_day = excluded.is_all_day,
+            reminder = excluded.reminder, repeat_type = excluded.repeat_type, repeat_until = excluded.repeat_until,
+            repeat_count = excluded.repeat_count, location = excluded.location,
+            attendees = excluded.attendees, is_favorite = excluded.is_favorite,
+            notification_enabled = excluded.notification_enabled, updated_at = excluded.updated_at
+        "#,
+    )
+    .bind(&event.id)
+    .bind(&event.title)
+    .bind(&event.description)
+    .bind(&event.date)
+    .bind(&event.end_date)
+    .bind(&event.start_time)
+    .bind(&event.end_time)
+    .bind(&event.event_type)
+    .bind(&event.priority)
+    .bind(event.is_all_day)
+    .bind(event.reminder)
+    .bind(&event.repeat_type)
+    .bind(&event.repeat_until)
+    .bind(event.repeat_count)
+    .bind(&event.location)
+    .bind(&event.attendees)
+    .bind(event.is_favorite)
+    .bind(event.notification_enabled)
+    .bind(event.created_at)
+    .bind(event.updated_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn insert_event_exception(conn: &mut SqliteConnection, exception: &EventException) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT OR IGNORE INTO event_exceptions (id, event_id, occurrence_date, is_cancelled, override_data, created_at) VALUES (?, ?, ?, ?, ?, ?)"
+    )
+    .bind(&exception.id)
+    .bind(&exception.event_id)
+    .bind(&exception.occurrence_date)
+    .bind(exception.is_cancelled)
+    .bind(&exception.override_data)
+    .bind(exception.created_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn upsert_todo(conn: &mut SqliteConnection, todo: &Todo) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO todos (id, title, title_normalized, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET
+            title = excluded.title, title_normalized = excluded.title_normalized, description = excluded.description, completed = excluded.completed,
+            priority = excluded.priority, tags = excluded.tags, due_date = excluded.due_date,
+            category = excluded.category, auto_complete_on_subtasks = excluded.auto_complete_on_subtasks, is_archived = excluded.is_archived,
+            updated_at = excluded.updated_at
+        "#,
+    )
+    .bind(&todo.id)
+    .bind(&todo.title)
+    .bind(search::normalize(&todo.title))
+    .bind(&todo.description)
+    .bind(todo.completed)
+    .bind(&todo.priority)
+    .bind(&todo.tags)
+    .bind(&todo.due_date)
+    .bind(&todo.category)
+    .bind(todo.auto_complete_on_subtasks)
+    .bind(todo.is_archived)
+    .bind(todo.created_at)
+    .bind(todo.updated_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+// 子任务切换后同步开启了 auto_complete_on_subtasks 的父待办：完成最后一个子任务时完成待办，
+// 取消完成子任务时重新打开已完成的待办；父待办状态改变时返回它
+async fn sync_parent_todo(conn: &mut SqliteConnection, subtask: &Subtask, now: DateTime<Utc>) -> Result<Option<Todo>, sqlx::Error> {
+    let target = if subtask.completed {
+        let remaining = sqlx::query("SELECT COUNT(*) AS count FROM subtasks WHERE todo_id = ? AND completed = FALSE")
+            .bind(&subtask.todo_id)
+            .fetch_one(&mut *conn)
+            .await?
+            .get::<i64, _>("count");
+        if remaining > 0 {
+            return Ok(None);
+        }
+        true
+    } else {
+        false
+    };
+
+    let result = sqlx::query("UPDATE todos SET completed = ?, updated_at = ? WHERE id = ? AND auto_complete_on_subtasks = TRUE AND completed != ?")
+        .bind(target)
+        .bind(now)
+        .bind(&subtask.todo_id)
+        .bind(target)
+        .execute(&mut *conn)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    let todo = sqlx::query_as::<_, Todo>(
+        "SELECT id, title, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at FROM todos WHERE id = ?"
+    )
+    .bind(&subtask.todo_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(todo.map(sanitize_todo))
+}
+
+// 数值型习惯的打卡由 value 是否达到 target 决定完成状态；没有 value、非数值型习惯或调用方显式覆盖时保持传入的 completed
+fn derive_record_completion(value_based: bool, target: i32, value: Option<i32>, completed: bool, completed_override: bool) -> bool {
+    match value {
+        Some(value) if value_based && !completed_override => value >= target,
+        _ => completed,
+    }
+}
+
+async fn resolve_record_completion(conn: &mut SqliteConnection, habit_id: &str, value: Option<i32>, completed: bool, completed_override: bool) -> Result<bool, sqlx::Error> {
+    let rule = sqlx::query("SELECT value_based, target FROM habits WHERE id = ?")
+        .bind(habit_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(match rule {
+        Some(row) => derive_record_completion(row.get("value_based"), row.get("target"), value, completed, completed_override),
+        None => completed,
+    })
+}
+
+fn week_start_weekday(week_start: u8) -> Weekday {
+    match week_start {
+        0 => Weekday::Sun,
+        _ => Weekday::Mon,
+    }
+}
+
+async fn load_week_start(conn: &mut SqliteConnection) -> Result<u8, Box<dyn std::error::Error>> {
+    let value = sqlx::query("SELECT value FROM app_settings WHERE key = 'week_start'")
+        .fetch_optional(&mut *conn)
+        .await?
+        .map(|row| row.get::<String, _>("value"));
+
+    match value {
+        Some(value) => Ok(serde_json::from_str(&value)?),
+        None => Ok(1),
+    }
+}
+
+async fn load_habit_completed_dates(conn: &mut SqliteConnection, habit_id: &str) -> Result<HashSet<NaiveDate>, sqlx::Error> {
+    let dates = sqlx::query("SELECT date FROM habit_records WHERE habit_id = ? AND completed = TRUE")
+        .bind(habit_id)
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .filter_map(|row| parse_date(&row.get::<String, _>("date")).ok())
+        .collect();
+
+    Ok(dates)
+}
+
+// 跳过的日期加上暂停区间内的每一天，统计连续天数和完成率时都视为免打卡
+async fn load_habit_skip_dates(conn: &mut SqliteConnection, habit_id: &str) -> Result<HashSet<NaiveDate>, sqlx::Error> {
+    let mut dates: HashSet<NaiveDate> = sqlx::query("SELECT date FROM habit_skips WHERE habit_id = ?")
+        .bind(habit_id)
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .filter_map(|row| parse_date(&row.get::<String, _>("date")).ok())
+        .collect();
+
+    let pauses = sqlx::query("SELECT start_date, end_date FROM habit_pauses WHERE habit_id = ?")
+        .bind(habit_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    for pause in &pauses {
+        let (Ok(start), Ok(end)) = (parse_date(&pause.get::<String, _>("start_date")), parse_date(&pause.get::<String, _>("end_date"))) else {
+            continue;
+        };
+        let mut day = start;
+        while day <= end {
+            dates.insert(day);
+            day += Duration::days(1);
+        }
+    }
+
+    Ok(dates)
+}
+
+// 所有数据都从同一个连接读取，在事务中调用时能看到一致的快照
+async fn compute_habit_streak(conn: &mut SqliteConnection, habit_id: &str, today: NaiveDate) -> Result<HabitStreak, Box<dyn std::error::Error>> {
+    let habit = sqlx::query_as::<_, Habit>(
+        "SELECT id, name, description, category, color, target, unit, frequency, is_active, skip_weekends, weekly_target, value_based, current_streak, longest_streak, created_at, updated_at FROM habits WHERE id = ?"
+    )
+    .bind(habit_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| not_found_error("习惯", habit_id))?;
+    let completed = load_habit_completed_dates(conn, habit_id).await?;
+    let skips = load_habit_skip_dates(conn, habit_id).await?;
+
+    let first_day = completed
+        .iter()
+        .copied()
+        .chain(std::iter::once(habit.created_at.date_naive()))
+        .min()
+        .unwrap_or(today);
+
+    if habit_frequency(&habit.frequency) == HabitFrequency::Weekly {
+        let week_start = week_start_weekday(load_week_start(conn).await?);
+        let (current_streak, longest_streak) =
+            weekly_streaks(&completed, habit.weekly_target.unwrap_or(1), first_day, today, week_start);
+        return Ok(HabitStreak {
+            habit_id: habit.id,
+            current_streak,
+            longest_streak,
+        });
+    }
+
+    // 当天尚未打卡不算中断
+    let mut current_streak = 0;
+    let mut day = today;
+    while day >= first_day {
+        if completed.contains(&day) {
+            current_streak += 1;
+        } else if day != today && !is_habit_day_skipped(&habit, &skips, day) {
+            break;
+        }
+        match day.pred_opt() {
+            Some(previous) => day = previous,
+            None => break,
+        }
+    }
+
+    let mut longest_streak = 0;
+    let mut run = 0;
+    for day in first_day.iter_days().take_while(|day| *day <= today) {
+        if completed.contains(&day) {
+            run += 1;
+            longest_streak = longest_streak.max(run);
+        } else if day != today && !is_habit_day_skipped(&habit, &skips, day) {
+            run = 0;
+        }
+    }
+
+    Ok(HabitStreak {
+        habit_id: habit.id,
+        current_streak,
+        longest_streak,
+    })
+}
+
+// 按 today 重新计算连续天数并写回 habits 上的缓存；调用方负责把它和打卡记录的写入放在同一个事务中
+async fn store_habit_streak(conn: &mut SqliteConnection, habit_id: &str, today: NaiveDate) -> Result<HabitStreak, Box<dyn std::error::Error>> {
+    let streak = compute_habit_streak(conn, habit_id, today).await?;
+    sqlx::query("UPDATE habits SET current_streak = ?, longest_streak = ?, streak_computed_on = ? WHERE id = ?")
+        .bind(streak.current_streak)
+        .bind(streak.longest_streak)
+        .bind(today.format("%Y-%m-%d").to_string())
+        .bind(habit_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(streak)
+}
+
+async fn insert_subtask(conn: &mut SqliteConnection, subtask: &Subtask) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT OR IGNORE INTO subtasks (id, todo_id, title, completed, created_at) VALUES (?, ?, ?, ?, ?)")
+        .bind(&subtask.id)
+        .bind(&subtask.todo_id)
+        .bind(&subtask.title)
+        .bind(subtask.completed)
+        .bind(subtask.created_at)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+async fn upsert_note(conn: &mut SqliteConnection, note: &Note) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO notes (id, title, title_normalized, content, tags, category, color, is_pinned, is_archived, pin_order, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET
+            title = excluded.title, title_normalized = excluded.title_normalized, content = excluded.content, tags = excluded.tags,
+            category = excluded.category, color = excluded.color, is_pinned = excluded.is_pinned,
+            is_archived = excluded.is_archived, pin_order = excluded.pin_order, updated_at = excluded.updated_at
+        "#,
+    )
+    .bind(&note.id)
+    .bind(&note.title)
+    .bind(search::normalize(&note.title))
+    .bind(&note.content)
+    .bind(&note.tags)
+    .bind(&note.category)
+    .bind(&note.color)
+    .bind(note.is_pinned)
+    .bind(note.is_archived)
+    .bind(note.pin_order)
+    .bind(note.created_at)
+    .bind(note.updated_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn upsert_note_template(conn: &mut SqliteConnection, template: &NoteTemplate) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO note_templates (id, name, content, tags, category, color, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET
+            name = excluded.name, content = excluded.content, tags = excluded.tags,
+            category = excluded.category, color = excluded.color, updated_at = excluded.updated_at
+        "#,
+    )
+    .bind(&template.id)
+    .bind(&template.name)
+    .bind(&template.content)
+    .bind(&template.tags)
+    .bind(&template.category)
+    .bind(&template.color)
+    .bind(template.created_at)
+    .bind(template.updated_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn upsert_habit(conn: &mut SqliteConnection, habit: &Habit) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO habits (
+            id, name, description, category, color, target, unit, frequency,
+            is_active, skip_weekends, weekly_target, value_based, current_streak, longest_streak, created_at, updated_at
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET
+            name = excluded.name, description = excluded.description, category = excluded.category,
+            color = excluded.color, target = excluded.target, unit = excluded.unit,
+            frequency = excluded.frequency, is_active = excluded.is_active,
+            skip_weekends = excluded.skip_weekends, weekly_target = excluded.weekly_target,
+            value_based = excluded.value_based, updated_at = excluded.updated_at,
+            streak_computed_on = NULL
+        "#,
+    )
+    .bind(&habit.id)
+    .bind(&habit.name)
+    .bind(&habit.description)
+    .bind(&habit.category)
+    .bind(&habit.color)
+    .bind(habit.target)
+    .bind(&habit.unit)
+    .bind(&habit.frequency)
+    .bind(habit.is_active)
+    .bind(habit.skip_weekends)
+    .bind(habit.weekly_target)
+    .bind(habit.value_based)
+    .bind(habit.current_streak)
+    .bind(habit.longest_streak)
+    .bind(habit.created_at)
+    .bind(habit.updated_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn insert_habit_record(conn: &mut SqliteConnection, record: &HabitRecord) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT OR IGNORE INTO habit_records (id, habit_id, date, completed, value, note, created_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(&record.id)
+    .bind(&record.habit_id)
+    .bind(&record.date)
+    .bind(record.completed)
+    .bind(record.value)
+    .bind(&record.note)
+    .bind(record.created_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn insert_habit_pause(conn: &mut SqliteConnection, pause: &HabitPause) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT OR IGNORE INTO habit_pauses (id, habit_id, start_date, end_date, created_at) VALUES (?, ?, ?, ?, ?)")
+        .bind(&pause.id)
+        .bind(&pause.habit_id)
+        .bind(&pause.start_date)
+        .bind(&pause.end_date)
+        .bind(pause.created_at)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+async fn insert_habit_skip(conn: &mut SqliteConnection, skip: &HabitSkip) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT OR IGNORE INTO habit_skips (id, habit_id, date, created_at) VALUES (?, ?, ?, ?)")
+        .bind(&skip.id)
+        .bind(&skip.habit_id)
+        .bind(&skip.date)
+        .bind(skip.created_at)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+async fn load_entity_links(conn: &mut SqliteConnection, entity_type: &str, id: &str) -> Result<Vec<EntityLink>, sqlx::Error> {
+    sqlx::query_as::<_, EntityLink>(
+        "SELECT id, source_type, source_id, target_type, target_id, created_at FROM entity_links WHERE (source_type = ? AND source_id = ?) OR (target_type = ? AND target_id = ?)"
+    )
+    .bind(entity_type)
+    .bind(id)
+    .bind(entity_type)
+    .bind(id)
+    .fetch_all(&mut *conn)
+    .await
+}
+
+// 只在两端的实体都存在时写入，撤销或导入时不会留下指向已删除实体的关联
+async fn insert_entity_link(conn: &mut SqliteConnection, link: &EntityLink) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT OR IGNORE INTO entity_links (id, source_type, source_id, target_type, target_id, created_at)
+        SELECT ?, ?, ?, ?, ?, ?
+        WHERE EXISTS (SELECT 1 FROM notes WHERE id = ?)
+          AND (EXISTS (SELECT 1 FROM todos WHERE id = ? AND ? = 'todo')
+               OR EXISTS (SELECT 1 FROM calendar_events WHERE id = ? AND ? = 'event'))
+        "#,
+    )
+    .bind(&link.id)
+    .bind(&link.source_type)
+    .bind(&link.source_id)
+    .bind(&link.target_type)
+    .bind(&link.target_id)
+    .bind(link.created_at)
+    .bind(&link.source_id)
+    .bind(&link.target_id)
+    .bind(&link.target_type)
+    .bind(&link.target_id)
+    .bind(&link.target_type)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+// 删除实体时一并删除它两侧的关联
+async fn delete_entity_links(conn: &mut SqliteConnection, entity_type: &str, id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "DELETE FROM entity_links WHERE (source_type = ? AND source_id = ?) OR (target_type = ? AND target_id = ?)"
+    )
+    .bind(entity_type)
+    .bind(id)
+    .bind(entity_type)
+    .bind(id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn insert_pomodoro_session(conn: &mut SqliteConnection, session: &PomodoroSession) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT OR REPLACE INTO pomodoro_sessions (
+            id, session_type, duration, completed, task_title, notes, date,
+            started_at, ended_at, interrupted, interruption_reason, created_at
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&session.id)
+    .bind(&session.session_type)
+    .bind(session.duration)
+    .bind(session.completed)
+    .bind(&session.task_title)
+    .bind(&session.notes)
+    .bind(&session.date)
+    .bind(session.started_at)
+    .bind(session.ended_at)
+    .bind(session.interrupted)
+    .bind(&session.interruption_reason)
+    .bind(session.created_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+// 把快照写回数据库
+async fn restore_snapshot(conn: &mut SqliteConnection, snapshot: UndoSnapshot) -> Result<(), Box<dyn std::error::Error>> {
+    match snapshot {
+        UndoSnapshot::Event { event, exceptions, links } => {
+            upsert_event(conn, &event).await?;
+            for exception in &exceptions {
+                insert_event_exception(conn, exception).await?;
+            }
+            for link in &links {
+                insert_entity_link(conn, link).await?;
+            }
+        }
+        UndoSnapshot::Todo { todo, subtasks, links } => {
+            upsert_todo(conn, &todo).await?;
+            for subtask in &subtasks {
+                insert_subtask(conn, subtask).await?;
+            }
+            for link in &links {
+                insert_entity_link(conn, link).await?;
+            }
+        }
+        UndoSnapshot::Note { note, links } => {
+            upsert_note(conn, &note).await?;
+            for link in &links {
+                insert_entity_link(conn, link).await?;
+            }
+        }
+        UndoSnapshot::Habit { habit, records, skips, pauses } => {
+            upsert_habit(conn, &habit).await?;
+            for record in &records {
+                insert_habit_record(conn, record).await?;
+            }
+            for skip in &skips {
+                insert_habit_skip(conn, skip).await?;
+            }
+            for pause in &pauses {
+                insert_habit_pause(conn, pause).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+const BACKUP_SCHEMA_VERSION: u32 = 2;
+const MAX_BACKUP_ERRORS: usize = 50;
+
+// 备份包含的数据表，父表在前，导入时按此顺序写入
+const BACKUP_TABLES: [&str; 12] = [
+    "calendar_events",
+    "event_exceptions",
+    "habits",
+    "habit_records",
+    "habit_skips",
+    "habit_pauses",
+    "todos",
+    "subtasks",
+    "pomodoro_sessions",
+    "notes",
+    "note_templates",
+    "entity_links",
+];
+
+// 在某个 schema_version 中新增的数据表，更早版本的备份缺少这些表时按空表导入
+const BACKUP_TABLES_SINCE: [(&str, u64); 3] = [("habit_pauses", 2), ("note_templates", 2), ("entity_links", 2)];
+
+fn backup_table_required(table: &str, version: u64) -> bool {
+    BACKUP_TABLES_SINCE
+        .iter()
+        .find(|(name, _)| *name == table)
+        .map_or(true, |(_, since)| version >= *since)
+}
+
+fn backup_table_counts(backup: &Backup) -> Vec<TableRowCount> {
+    let counts = [
+        backup.calendar_events.len(),
+        backup.event_exceptions.len(),
+        backup.habits.len(),
+        backup.habit_records.len(),
+        backup.habit_skips.len(),
+        backup.habit_pauses.len(),
+        backup.todos.len(),
+        backup.subtasks.len(),
+        backup.pomodoro_sessions.len(),
+        backup.notes.len(),
+        backup.note_templates.len(),
+        backup.entity_links.len(),
+    ];
+
+    BACKUP_TABLES
+        .iter()
+        .zip(counts)
+        .map(|(table, rows)| TableRowCount {
+            table: table.to_string(),
+            rows: rows as i64,
+        })
+        .collect()
+}
+
+fn check_backup_rows<T: DeserializeOwned>(table: &str, rows: &[serde_json::Value], errors: &mut Vec<String>) {
+    for (index, row) in rows.iter().enumerate() {
+        if let Err(e) = serde_json::from_value::<T>(row.clone()) {
+            errors.push(format!("{}[{}]: {}", table, index, e));
+        }
+    }
+}
+
+// 只做解析和结构检查，不写数据库；校验通过时同时返回解析好的备份
+fn validate_backup_json(raw: &str) -> (BackupValidation, Option<Backup>) {
+    let mut validation = BackupValidation {
+        valid: false,
+        schema_version: None,
+        tables: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    let value: serde_json::Value = match serde_json::from_str(raw) {
+        Ok(value) => value,
+        Err(e) => {
+            validation.errors.push(format!("JSON 解析失败: {}", e));
+            return (validation, None);
+        }
+    };
+    let Some(object) = value.as_object() else {
+        validation.errors.push("备份文件的顶层必须是对象".to_string());
+        return (validation, None);
+    };
+
+    validation.schema_version = object.get("schema_version").and_then(|version| version.as_u64());
+    match validation.schema_version {
+        None => validation.errors.push("缺少 schema_version".to_string()),
+        Some(version) if version == 0 || version > BACKUP_SCHEMA_VERSION as u64 => {
+            validation.errors.push(format!("不支持的 schema_version: {}（当前为 {}）", version, BACKUP_SCHEMA_VERSION))
+        }
+        Some(_) => {}
+    }
+
+    let version = validation.schema_version.unwrap_or(BACKUP_SCHEMA_VERSION as u64);
+    for table in BACKUP_TABLES {
+        let Some(rows) = object.get(table) else {
+            if backup_table_required(table, version) {
+                validation.errors.push(format!("缺少数据表: {}", table));
+            }
+            continue;
+        };
+        let Some(rows) = rows.as_array() else {
+            validation.errors.push(format!("{} 不是数组", table));
+            continue;
+        };
+        validation.tables.push(TableRowCount {
+            table: table.to_string(),
+            rows: rows.len() as i64,
+        });
+
+        let errors = &mut validation.errors;
+        match table {
+            "calendar_events" => check_backup_rows::<CalendarEvent>(table, rows, errors),
+            "event_exceptions" => check_backup_rows::<EventException>(table, rows, errors),
+            "habits" => check_backup_rows::<Habit>(table, rows, errors),
+            "habit_records" => check_backup_rows::<HabitRecord>(table, rows, errors),
+            "habit_skips" => check_backup_rows::<HabitSkip>(table, rows, errors),
+            "habit_pauses" => check_backup_rows::<HabitPause>(table, rows, errors),
+            "todos" => check_backup_rows::<Todo>(table, rows, errors),
+            "subtasks" => check_backup_rows::<Subtask>(table, rows, errors),
+            "pomodoro_sessions" => check_backup_rows::<PomodoroSession>(table, rows, errors),
+            "notes" => check_backup_rows::<Note>(table, rows, errors),
+            "note_templates" => check_backup_rows::<NoteTemplate>(table, rows, errors),
+            _ => check_backup_rows::<EntityLink>(table, rows, errors),
+        }
+    }
+
+    let backup = if validation.errors.is_empty() {
+        match serde_json::from_value::<Backup>(value) {
+            Ok(backup) => Some(backup),
+            Err(e) => {
+                validation.errors.push(format!("备份结构无效: {}", e));
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    if validation.errors.len() > MAX_BACKUP_ERRORS {
+        let omitted = validation.errors.len() - MAX_BACKUP_ERRORS;
+        validation.errors.truncate(MAX_BACKUP_ERRORS);
+        validation.errors.push(format!("另有 {} 个错误未列出", omitted));
+    }
+    validation.valid = backup.is_some();
+
+    (validation, backup)
+}
+
+const DATABASE_FILE: &str = "toolbox.db";
+
+const READONLY_QUERY_MAX_ROWS: usize = 1000;
+const READONLY_QUERY_TIMEOUT_SECS: u64 = 5;
+// 每执行这么多条虚拟机指令检查一次是否超时
+const READONLY_QUERY_PROGRESS_OPS: i32 = 1000;
+// 即使出现在 SELECT 语句中也拒绝，只读连接之外的又一道防线
+const READONLY_FORBIDDEN_KEYWORDS: [&str; 17] = [
+    "INSERT", "UPDATE", "DELETE", "CREATE", "DROP", "ALTER", "ATTACH", "DETACH", "PRAGMA",
+    "VACUUM", "REINDEX", "ANALYZE", "BEGIN", "COMMIT", "ROLLBACK", "SAVEPOINT", "RELEASE",
+];
+
+// 只允许单条 SELECT（可以带 WITH）；跳过字符串、带引号的标识符和注释后逐词检查，返回去掉结尾分号的语句
+fn validate_readonly_sql(sql: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let sql = sql.trim().trim_end_matches(|c: char| c == ';' || c.is_whitespace());
+    if sql.is_empty() {
+        return Err(validation_error("查询语句不能为空"));
+    }
+
+    let chars: Vec<char> = sql.chars().collect();
+    let mut words: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            quote @ ('\'' | '"' | '`') => {
+                i += 1;
+                while i < chars.len() {
+                    if chars[i] == quote {
+                        // 连续两个引号是转义
+                        if chars.get(i + 1) == Some(&quote) {
+                            i += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    i += 1;
+                }
+                if i >= chars.len() {
+                    return Err(validation_error("查询语句中有未闭合的引号"));
+                }
+                i += 1;
+            }
+            '[' => {
+                while i < chars.len() && chars[i] != ']' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            ';' => return Err(validation_error("只能执行一条查询语句")),
+            c if c.is_alphanumeric() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                words.push(chars[start..i].iter().collect::<String>().to_uppercase());
+            }
+            _ => i += 1,
+        }
+    }
+
+    if !matches!(words.first().map(String::as_str), Some("SELECT" | "WITH")) {
+        return Err(validation_error("只允许执行 SELECT 查询"));
+    }
+    for (index, word) in words.iter().enumerate() {
+        // replace() 是常用的字符串函数，只有 REPLACE INTO 才是写入
+        let forbidden = READONLY_FORBIDDEN_KEYWORDS.contains(&word.as_str())
+            || (word == "REPLACE" && words.get(index + 1).is_some_and(|next| next == "INTO"));
+        if forbidden {
+            return Err(validation_error(format!("查询语句中不允许使用 {}", word)));
+        }
+    }
+
+    Ok(sql.to_string())
+}
+
+// SQLite 的值是动态类型，按每个值实际的存储类型转换；BLOB 转成十六进制字符串
+fn sqlite_value_to_json(row: &sqlx::sqlite::SqliteRow, index: usize) -> Result<serde_json::Value, sqlx::Error> {
+    let raw = row.try_get_raw(index)?;
+    if raw.is_null() {
+        return Ok(serde_json::Value::Null);
+    }
+    let type_name = raw.type_info().name().to_string();
+    Ok(match type_name.as_str() {
+        "INTEGER" | "BOOLEAN" => serde_json::Value::from(row.try_get::<i64, _>(index)?),
+        "REAL" => serde_json::Value::from(row.try_get::<f64, _>(index)?),
+        "BLOB" => serde_json::Value::from(
+            row.try_get::<Vec<u8>, _>(index)?
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>(),
+        ),
+        _ => serde_json::Value::from(row.try_get::<String, _>(index)?),
+    })
+}
+
+pub struct DatabaseService {
+    pool: SqlitePool,
+    path: String, // 打开的数据库文件，只读查询在它上面单独建连接
+    undo_log: Mutex<UndoLog>,
+    pending_writes: Mutex<PendingWrites>,
+    startup_status: StartupStatus,
+}
+
+impl DatabaseService {
+    pub async fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        Self::open(DATABASE_FILE).await
+    }
+
+    // 打开指定路径的数据库文件；测试用独立的临时文件
+    pub(crate) async fn open(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let database_url = format!("sqlite://{}", path);
+
+        // 数据库损坏时把原文件改名保留，再新建一个空数据库，保证应用能启动；其他错误照常返回
+        let opened = Self::open_database(&database_url)
+            .await
+            .map_err(|e| (is_corrupt_error(e.as_ref()), e.to_string()));
+        let (pool, startup_status) = match opened {
+            Ok(pool) => (pool, StartupStatus::default()),
+            Err((true, message)) => {
+                let backup_path = Self::quarantine_corrupt_database(path)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                tracing::warn!(backup_path = %backup_path, error = %message, "数据库文件已损坏，已备份并新建数据库");
+                let pool = Self::open_database(&database_url).await?;
+                let status = StartupStatus {
+                    recovered_from_corruption: true,
+                    corrupt_backup_path: Some(backup_path),
+                    error: Some(message),
+                    open_failed: false,
+                };
+                (pool, status)
+            }
+            Err((false, message)) => return Err(message.into()),
+        };
+
+        let service = DatabaseService {
+            pool,
+            path: path.to_string(),
+            undo_log: Mutex::new(UndoLog::default()),
+            pending_writes: Mutex::new(PendingWrites::default()),
+            startup_status,
+        };
+
+        // 保留策略开启了 auto_apply 时在启动时自动归档或清理旧数据，失败不影响启动
+        let retention = match service.get_retention_policy().await {
+            Ok(policy) if policy.auto_apply => service.apply_retention_now().await.map(|_| ()),
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = retention {
+            tracing::warn!(error = %e, "自动执行保留策略失败");
+        }
+
+        // 启动时刷新过期的连续天数缓存，失败时留到读取习惯时再刷新
+        if let Err(e) = service.refresh_stale_habit_streaks(None).await {
+            tracing::warn!(error = %e, "刷新习惯连续天数失败");
+        }
+
+        Ok(service)
+    }
+
+    // 打开数据库并检查完整性后建表；失败时先关闭连接池，以便调用方移走损坏的文件
+    async fn open_database(database_url: &str) -> Result<SqlitePool, Box<dyn std::error::Error>> {
+        // 创建数据库（如果不存在）
+        if !Sqlite::database_exists(database_url).await.unwrap_or(false) {
+            Sqlite::create_database(database_url).await?;
+        }
+
+        let pool = SqlitePool::connect(database_url).await?;
+
+        let checked = match Self::check_integrity(&pool).await.map_err(|e| (is_corrupt_error(e.as_ref()), e.to_string())) {
+            // 创建所有表
+            Ok(()) => Self::create_tables(&pool).await.map_err(|e| (is_corrupt_error(e.as_ref()), e.to_string())),
+            Err(e) => Err(e),
+        };
+        if let Err((corrupt, message)) = checked {
+            pool.close().await;
+            let error: Box<dyn std::error::Error> = if corrupt { Box::new(CorruptDatabaseError(message)) } else { message.into() };
+            return Err(error);
+        }
+
+        Ok(pool)
+    }
+
+    // quick_check 跳过索引内容的校验，大数据库上也足够快，适合每次启动执行
+    async fn check_integrity(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Error>> {
+        let problems: Vec<String> = sqlx::query("PRAGMA quick_check")
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|row| row.get::<String, _>(0))
+            .filter(|message| message != "ok")
+            .collect();
+
+        if !problems.is_empty() {
+            return Err(Box::new(CorruptDatabaseError(problems.join("; "))));
+        }
+        Ok(())
+    }
+
+    // 把损坏的数据库连同 -wal/-shm 文件改名为 .corrupt，已有同名备份时加上时间戳，返回备份路径
+    async fn quarantine_corrupt_database(path: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let mut backup_path = format!("{}.corrupt", path);
+        if tokio::fs::try_exists(&backup_path).await? {
+            backup_path = format!("{}.{}.corrupt", path, Local::now().format("%Y%m%d%H%M%S"));
+        }
+
+        tokio::fs::rename(path, &backup_path).await?;
+        for suffix in ["-wal", "-shm"] {
+            let sidecar = format!("{}{}", path, suffix);
+            if tokio::fs::try_exists(&sidecar).await? {
+                tokio::fs::rename(&sidecar, format!("{}{}", backup_path, suffix)).await?;
+            }
+        }
+
+        Ok(backup_path)
+    }
+
+    // 启动时是否从损坏的数据库中恢复，供前端提示用户
+    pub fn startup_status(&self) -> StartupStatus {
+        self.startup_status.clone()
+    }
+
+    async fn create_tables(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Error>> {
+        // 日程事件表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS calendar_events (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                description TEXT,
+                date TEXT NOT NULL,
+                end_date TEXT,
+                start_time TEXT,
+                end_time TEXT,
+                event_type TEXT NOT NULL,
+                priority TEXT NOT NULL,
+                is_all_day BOOLEAN NOT NULL,
+                reminder INTEGER,
+                repeat_type TEXT,
+                repeat_until TEXT,
+                repeat_count INTEGER,
+                location TEXT,
+                attendees TEXT,
+                notification_enabled BOOLEAN NOT NULL DEFAULT TRUE,
+                created_at DATETIME NOT NULL,
+                updated_at DATETIME NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 重复日程例外表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS event_exceptions (
+                id TEXT PRIMARY KEY,
+                event_id TEXT NOT NULL,
+                occurrence_date TEXT NOT NULL,
+                is_cancelled BOOLEAN NOT NULL DEFAULT FALSE,
+                override_data TEXT,
+                created_at DATETIME NOT NULL,
+                UNIQUE (event_id, occurrence_date),
+                FOREIGN KEY (event_id) REFERENCES calendar_events (id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 习惯表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS habits (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                description TEXT,
+                category TEXT NOT NULL,
+                color TEXT NOT NULL,
+                target INTEGER NOT NULL,
+                unit TEXT NOT NULL,
+                frequency TEXT NOT NULL,
+                is_active BOOLEAN NOT NULL,
+                skip_weekends BOOLEAN NOT NULL DEFAULT FALSE,
+                weekly_target INTEGER,
+                value_based BOOLEAN NOT NULL DEFAULT FALSE,
+                current_streak INTEGER NOT NULL DEFAULT 0,
+                longest_streak INTEGER NOT NULL DEFAULT 0,
+                created_at DATETIME NOT NULL,
+                updated_at DATETIME NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 习惯记录表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS habit_records (
+                id TEXT PRIMARY KEY,
+                habit_id TEXT NOT NULL,
+                date TEXT NOT NULL,
+                completed BOOLEAN NOT NULL,
+                value INTEGER,
+                note TEXT,
+                created_at DATETIME NOT NULL,
+                UNIQUE (habit_id, date),
+                FOREIGN KEY (habit_id) REFERENCES habits (id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 习惯跳过日期表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS habit_skips (
+                id TEXT PRIMARY KEY,
+                habit_id TEXT NOT NULL,
+                date TEXT NOT NULL,
+                created_at DATETIME NOT NULL,
+                UNIQUE (habit_id, date),
+                FOREIGN KEY (habit_id) REFERENCES habits (id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 习惯暂停区间表，起止日期都包含在内
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS habit_pauses (
+                id TEXT PRIMARY KEY,
+                habit_id TEXT NOT NULL,
+                start_date TEXT NOT NULL,
+                end_date TEXT NOT NULL,
+                created_at DATETIME NOT NULL,
+                FOREIGN KEY (habit_id) REFERENCES habits (id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 待办事项表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS todos (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                description TEXT,
+                completed BOOLEAN NOT NULL DEFAULT FALSE,
+                priority TEXT NOT NULL,
+                tags TEXT,
+                due_date TEXT,
+                category TEXT NOT NULL DEFAULT 'general',
+                auto_complete_on_subtasks BOOLEAN NOT NULL DEFAULT FALSE,
+                created_at DATETIME NOT NULL,
+                updated_at DATETIME NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 子任务表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS subtasks (
+                id TEXT PRIMARY KEY,
+                todo_id TEXT NOT NULL,
+                title TEXT NOT NULL,
+                completed BOOLEAN NOT NULL DEFAULT FALSE,
+                created_at DATETIME NOT NULL,
+                FOREIGN KEY (todo_id) REFERENCES todos (id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 番茄钟会话表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS pomodoro_sessions (
+                id TEXT PRIMARY KEY,
+                session_type TEXT NOT NULL,
+                duration INTEGER NOT NULL,
+                completed BOOLEAN NOT NULL DEFAULT FALSE,
+                task_title TEXT,
+                notes TEXT,
+                date TEXT NOT NULL,
+                started_at DATETIME,
+                ended_at DATETIME,
+                interrupted BOOLEAN NOT NULL DEFAULT FALSE,
+                interruption_reason TEXT,
+                created_at DATETIME NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 番茄钟设置表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS pomodoro_settings (
+                id TEXT PRIMARY KEY,
+                work_time INTEGER NOT NULL DEFAULT 1500,
+                short_break INTEGER NOT NULL DEFAULT 300,
+                long_break INTEGER NOT NULL DEFAULT 900,
+                long_break_interval INTEGER NOT NULL DEFAULT 4,
+                auto_start_breaks BOOLEAN NOT NULL DEFAULT FALSE,
+                auto_start_work BOOLEAN NOT NULL DEFAULT FALSE,
+                notification_enabled BOOLEAN NOT NULL DEFAULT TRUE,
+                daily_session_goal INTEGER NOT NULL DEFAULT 8,
+                created_at DATETIME NOT NULL,
+                updated_at DATETIME NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 便笺表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS notes (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                content TEXT NOT NULL,
+                tags TEXT,
+                category TEXT NOT NULL DEFAULT 'general',
+                color TEXT NOT NULL DEFAULT '#fef3c7',
+                is_pinned BOOLEAN NOT NULL DEFAULT FALSE,
+                is_archived BOOLEAN NOT NULL DEFAULT FALSE,
+                pin_order INTEGER,
+                created_at DATETIME NOT NULL,
+                updated_at DATETIME NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 旧数据库补充新增的列
+        Self::add_column_if_missing(pool, "calendar_events", "end_date", "TEXT").await?;
+        Self::add_column_if_missing(pool, "calendar_events", "is_favorite", "BOOLEAN NOT NULL DEFAULT FALSE").await?;
+        Self::add_column_if_missing(pool, "calendar_events", "repeat_until", "TEXT").await?;
+        Self::add_column_if_missing(pool, "calendar_events", "repeat_count", "INTEGER").await?;
+        Self::add_column_if_missing(pool, "calendar_events", "notification_enabled", "BOOLEAN NOT NULL DEFAULT TRUE").await?;
+        Self::add_column_if_missing(pool, "habits", "skip_weekends", "BOOLEAN NOT NULL DEFAULT FALSE").await?;
+        Self::add_column_if_missing(pool, "habits", "weekly_target", "INTEGER").await?;
+        Self::add_column_if_missing(pool, "habits", "current_streak", "INTEGER NOT NULL DEFAULT 0").await?;
+        Self::add_column_if_missing(pool, "habits", "longest_streak", "INTEGER NOT NULL DEFAULT 0").await?;
+        Self::add_column_if_missing(pool, "habits", "value_based", "BOOLEAN NOT NULL DEFAULT FALSE").await?;
+        Self::add_column_if_missing(pool, "notes", "pin_order", "INTEGER").await?;
+        Self::add_column_if_missing(pool, "pomodoro_settings", "daily_session_goal", "INTEGER NOT NULL DEFAULT 8").await?;
+        Self::add_column_if_missing(pool, "pomodoro_sessions", "interrupted", "BOOLEAN NOT NULL DEFAULT FALSE").await?;
+        Self::add_column_if_missing(pool, "pomodoro_sessions", "interruption_reason", "TEXT").await?;
+        Self::add_column_if_missing(pool, "todos", "title_normalized", "TEXT").await?;
+        Self::add_column_if_missing(pool, "todos", "auto_complete_on_subtasks", "BOOLEAN NOT NULL DEFAULT FALSE").await?;
+        Self::add_column_if_missing(pool, "notes", "title_normalized", "TEXT").await?;
+        Self::upgrade_legacy_attendees(pool).await?;
+        Self::backfill_normalized_titles(pool).await?;
+
+        // 便笺模板表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS note_templates (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                content TEXT NOT NULL,
+                tags TEXT,
+                category TEXT NOT NULL,
+                color TEXT NOT NULL,
+                created_at DATETIME NOT NULL,
+                updated_at DATETIME NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 实体关联表，目前只支持便笺与待办、便笺与日程，统一以便笺作为 source 存储
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS entity_links (
+                id TEXT PRIMARY KEY,
+                source_type TEXT NOT NULL,
+                source_id TEXT NOT NULL,
+                target_type TEXT NOT NULL,
+                target_id TEXT NOT NULL,
+                created_at DATETIME NOT NULL,
+                UNIQUE (source_type, source_id, target_type, target_id)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 应用设置表（键值对，值为 JSON）
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS app_settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                updated_at DATETIME NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 番茄钟设置只保留最早创建的一行，并禁止再插入第二行
+        sqlx::query(
+            r#"
+            DELETE FROM pomodoro_settings WHERE id NOT IN (
+                SELECT id FROM pomodoro_settings ORDER BY created_at, id LIMIT 1
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS pomodoro_settings_singleton
+            BEFORE INSERT ON pomodoro_settings
+            WHEN (SELECT COUNT(*) FROM pomodoro_settings) >= 1
+            BEGIN
+                SELECT RAISE(ABORT, 'pomodoro_settings 只允许存在一行');
+            END
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // 旧版本的番茄钟时长以分钟存储，统一换算为秒；换算完成后在 app_settings 中记录，避免重复换算
+        let converted = sqlx::query("SELECT 1 FROM app_settings WHERE key = 'pomodoro_settings_unit'")
+            .fetch_optional(pool)
+            .await?
+            .is_some();
+        if !converted {
+            sqlx::query("UPDATE pomodoro_settings SET work_time = work_time * 60, short_break = short_break * 60, long_break = long_break * 60")
+                .execute(pool)
+                .await?;
+            sqlx::query("INSERT INTO app_settings (key, value, updated_at) VALUES ('pomodoro_settings_unit', '\"seconds\"', ?)")
+                .bind(Utc::now())
+                .execute(pool)
+                .await?;
+        }
+
+        // 插入默认番茄钟设置（如果不存在）
+        let exists = sqlx::query("SELECT COUNT(*) as count FROM pomodoro_settings")
+            .fetch_one(pool)
+            .await?
+            .get::<i64, _>("count");
+
+        if exists == 0 {
+            let id = Uuid::new_v4().to_string();
+            let now = Utc::now();
+            sqlx::query(
+                r#"
+                INSERT INTO pomodoro_settings (
+                    id, work_time, short_break, long_break, long_break_interval,
+                    auto_start_breaks, auto_start_work, notification_enabled,
+                    created_at, updated_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(id)
+            .bind(25 * 60)
+            .bind(5 * 60)
+            .bind(15 * 60)
+            .bind(4)
+            .bind(false)
+            .bind(false)
+            .bind(true)
+            .bind(now)
+            .bind(now)
+            .execute(pool)
+            .await?;
+        }
+
+        Self::run_migrations(pool).await
+    }
+
+    // 版本 1 是上面 create_tables 建立的基础表结构（其中的补列操作可重复执行）。
+    // 之后的每个版本按 PRAGMA user_version 只执行一次，升级步骤和新版本号在同一个事务中提交
+    async fn run_migrations(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Error>> {
+        let current: i64 = sqlx::query_scalar("PRAGMA user_version").fetch_one(pool).await?;
+
+        for version in current + 1..=SCHEMA_VERSION {
+            let mut tx = pool.begin().await?;
+            match version {
+                1 => {}
+                // 习惯连续天数缓存的计算日期，跨天后据此刷新
+                2 => {
+                    sqlx::query("ALTER TABLE habits ADD COLUMN streak_computed_on TEXT")
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                // 每个习惯每天只保留一条打卡记录（保留最新写入的一条），批量打卡依赖这个唯一约束；
+                // 新建的表在 CREATE TABLE 中已带有约束，不再重复建索引
+                3 => {
+                    let unique_indexes: i64 = sqlx::query_scalar(
+                        "SELECT COUNT(*) FROM pragma_index_list('habit_records') WHERE \"unique\" = 1 AND origin != 'pk'"
+                    )
+                    .fetch_one(&mut *tx)
+                    .await?;
+                    if unique_indexes == 0 {
+                        sqlx::query(
+                            r#"
+                            DELETE FROM habit_records WHERE EXISTS (
+                                SELECT 1 FROM habit_records newer
+                                WHERE newer.habit_id = habit_records.habit_id AND newer.date = habit_records.date
+                                  AND (newer.created_at > habit_records.created_at
+                                       OR (newer.created_at = habit_records.created_at AND newer.rowid > habit_records.rowid))
+                            )
+                            "#,
+                        )
+                        .execute(&mut *tx)
+                        .await?;
+                        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_habit_records_habit_date ON habit_records (habit_id, date)")
+                            .execute(&mut *tx)
+                            .await?;
+                        // 删掉重复记录后缓存的连续天数可能已过期
+                        sqlx::query("UPDATE habits SET streak_computed_on = NULL").execute(&mut *tx).await?;
+                    }
+                }
+                // 已归档的待办不出现在待办列表中，由保留策略或手动归档设置
+                4 => {
+                    sqlx::query("ALTER TABLE todos ADD COLUMN is_archived BOOLEAN NOT NULL DEFAULT FALSE")
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                _ => return Err(format!("缺少升级到 schema 版本 {} 的步骤", version).into()),
+            }
+            // PRAGMA 不支持参数绑定，版本号是整数，拼接是安全的
+            sqlx::query(&format!("PRAGMA user_version = {}", version))
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+        }
+
+        Ok(())
+    }
+
+    // 在事务中执行 f：返回 Ok 时提交；返回 Err 时事务被丢弃，sqlx 会自动回滚，
+    // 因此错误不会跨越 await 存活（Box<dyn Error> 不是 Send）。
+    // 数据库被锁时按指数退避重试整个事务，所以 f 可能被调用多次
+    pub async fn with_transaction<T, F>(&self, f: F) -> Result<T, Box<dyn std::error::Error>>
+    where
+        T: Send,
+        F: for<'c> Fn(&'c mut SqliteConnection) -> TxFuture<'c, T> + Send + Sync,
+    {
+        let f = &f;
+        retry_on_busy(move || self.run_transaction(f)).await
+    }
+
+    async fn run_transaction<T, F>(&self, f: &F) -> Result<T, Box<dyn std::error::Error>>
+    where
+        T: Send,
+        F: for<'c> Fn(&'c mut SqliteConnection) -> TxFuture<'c, T> + Send + Sync,
+    {
+        let mut tx = self.pool.begin().await?;
+        let value = f(&mut *tx).await?;
+        tx.commit().await?;
+
+        Ok(value)
+    }
+
+    fn record_undo(&self, action: &'static str, snapshot: UndoSnapshot) {
+        self.undo_log
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(UndoEntry { action, snapshot });
+    }
+
+    // 撤销最近一次修改或删除；没有可撤销的操作时返回 None
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn undo_last(&self) -> Result<Option<UndoResult>, Box<dyn std::error::Error>> {
+        let entry = self.undo_log.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).entries.pop_back();
+        let Some(entry) = entry else {
+            return Ok(None);
+        };
+
+        let result = UndoResult {
+            entity: entry.snapshot.entity().to_string(),
+            action: entry.action.to_string(),
+            id: entry.snapshot.id().to_string(),
+            description: match entry.action {
+                "deleted" => format!("已恢复被删除的{}", entry.snapshot.label()),
+                _ => format!("已撤销对{}的修改", entry.snapshot.label()),
+            },
+        };
+
+        if let Err(e) = self.with_transaction(|conn| Box::pin(restore_snapshot(conn, entry.snapshot.clone()))).await {
+            // 恢复失败时放回日志，允许再次尝试
+            self.record_undo(entry.action, entry.snapshot);
+            return Err(e);
+        }
+
+        Ok(Some(result))
+    }
+
+    // 旧版本的参与者是字符串数组，升级为 { name, email: null, status: "unknown" } 对象
+    async fn upgrade_legacy_attendees(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Error>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, attendees FROM calendar_events
+            WHERE json_valid(attendees) AND json_type(attendees) = 'array'
+                AND EXISTS (SELECT 1 FROM json_each(attendees) WHERE type = 'text')
+            "#,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        for row in rows {
+            let id = row.get::<String, _>("id");
+            let entries: Vec<serde_json::Value> = serde_json::from_str(&row.get::<String, _>("attendees"))?;
+            let attendees = upgrade_attendee_entries(entries, &id);
+
+            sqlx::query("UPDATE calendar_events SET attendees = ? WHERE id = ?")
+                .bind(serde_json::to_string(&attendees)?)
+                .bind(&id)
+                .execute(pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // 规范化在 Rust 中完成，旧数据和新增列之前写入的行在启动时补齐
+    async fn backfill_normalized_titles(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Error>> {
+        for table in ["todos", "notes"] {
+            let rows = sqlx::query(&format!("SELECT id, title FROM {} WHERE title_normalized IS NULL", table))
+                .fetch_all(pool)
+                .await?;
+
+            for row in rows {
+                sqlx::query(&format!("UPDATE {} SET title_normalized = ? WHERE id = ?", table))
+                    .bind(search::normalize(&row.get::<String, _>("title")))
+                    .bind(row.get::<String, _>("id"))
+                    .execute(pool)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn add_column_if_missing(pool: &SqlitePool, table: &str, column: &str, definition: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let columns = sqlx::query(&format!("PRAGMA table_info({})", table))
+            .fetch_all(pool)
+            .await?;
+
+        if !columns.iter().any(|row| row.get::<String, _>("name") == column) {
+            sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                .execute(pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // 应用设置相关方法
+    async fn get_app_setting(&self, key: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let value = sqlx::query("SELECT value FROM app_settings WHERE key = ?")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|row| row.get::<String, _>("value"));
+
+        Ok(value)
+    }
+
+    async fn set_app_setting(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        sqlx::query(
+            r#"
+            INSERT INTO app_settings (key, value, updated_at) VALUES (?, ?, ?)
+            ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(key)
+        .bind(value)
+        .bind(Utc::now())
+        .execute_with_retry(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // 列表视图最后使用的排序/筛选状态，内容对后端不透明，只保证是合法 JSON
+    #[tracing::instrument(skip_all, fields(view = %view), err(Display))]
+    pub async fn get_view_state(&self, view: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        self.get_app_setting(&format!("view_state:{}", view.trim())).await
+    }
+
+    #[tracing::instrument(skip_all, fields(view = %view), err(Display))]
+    pub async fn save_view_state(&self, view: &str, state_json: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let view = view.trim();
+        if view.is_empty() {
+            return Err(validation_error("视图名称不能为空"));
+        }
+        let state: serde_json::Value = serde_json::from_str(state_json)
+            .map_err(|e| validation_error(format!("视图状态不是合法的 JSON: {}", e)))?;
+
+        self.set_app_setting(&format!("view_state:{}", view), &state.to_string()).await
+    }
+
+    // 每周从哪天开始：0 = 周日，1 = 周一（默认）；所有按周计算的地方都读取这里
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_week_start(&self) -> Result<u8, Box<dyn std::error::Error>> {
+        let mut conn = self.pool.acquire().await?;
+        load_week_start(&mut conn).await
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn set_week_start(&self, week_start: u8) -> Result<u8, Box<dyn std::error::Error>> {
+        if week_start > 1 {
+            return Err(validation_error("每周起始日只能是 0（周日）或 1（周一）"));
+        }
+
+        self.set_app_setting("week_start", &week_start.to_string()).await?;
+        // 每周习惯的连续周数依赖每周起始日，标记为过期，下次读取时重新计算
+        sqlx::query("UPDATE habits SET streak_computed_on = NULL WHERE frequency = 'weekly'")
+            .execute_with_retry(&self.pool)
+            .await?;
+        Ok(week_start)
+    }
+
+    // 新建待办/便笺未填写分类时使用的默认分类，setting key 为 default_{entity}_category
+    #[tracing::instrument(skip_all, fields(entity = %entity), err(Display))]
+    pub async fn get_default_category(&self, entity: &str) -> Result<String, Box<dyn std::error::Error>> {
+        if !DEFAULT_CATEGORY_ENTITIES.contains(&entity) {
+            return Err(validation_error(format!("不支持默认分类的类型: {}", entity)));
+        }
+
+        match self.get_app_setting(&format!("default_{}_category", entity)).await? {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Ok("general".to_string()),
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(entity = %entity), err(Display))]
+    pub async fn set_default_category(&self, entity: &str, category: &str) -> Result<String, Box<dyn std::error::Error>> {
+        if !DEFAULT_CATEGORY_ENTITIES.contains(&entity) {
+            return Err(validation_error(format!("不支持默认分类的类型: {}", entity)));
+        }
+        let category = category.trim();
+        if category.is_empty() {
+            return Err(validation_error("默认分类不能为空"));
+        }
+
+        self.set_app_setting(&format!("default_{}_category", entity), &serde_json::to_string(category)?).await?;
+        Ok(category.to_string())
+    }
+
+    // 每日习惯提醒时间（HH:MM），为空表示关闭提醒，默认 20:00
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_habit_reminder_time(&self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        match self.get_app_setting("habit_reminder_time").await? {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Ok(Some("20:00".to_string())),
+        }
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn set_habit_reminder_time(&self, time: Option<String>) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if let Some(time) = &time {
+            if NaiveTime::parse_from_str(time, "%H:%M").is_err() {
+                return Err(validation_error(format!("无效的提醒时间: {}", time)));
+            }
+        }
+
+        self.set_app_setting("habit_reminder_time", &serde_json::to_string(&time)?).await?;
+        Ok(time)
+    }
+
+    // 相对 UTC 的时区偏移（分钟），为空时使用系统本地时区（默认）
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_utc_offset_minutes(&self) -> Result<Option<i32>, Box<dyn std::error::Error>> {
+        match self.get_app_setting("utc_offset_minutes").await? {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn set_utc_offset_minutes(&self, minutes: Option<i32>) -> Result<Option<i32>, Box<dyn std::error::Error>> {
+        if minutes.is_some_and(|minutes| !(-720..=840).contains(&minutes)) {
+            return Err(validation_error("时区偏移必须在 -720 到 840 分钟之间"));
+        }
+
+        self.set_app_setting("utc_offset_minutes", &serde_json::to_string(&minutes)?).await?;
+        Ok(minutes)
+    }
+
+    // 新建每日日志便笺时套用的便笺模板，为空时不使用模板（默认）
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_journal_template_id(&self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        match self.get_app_setting("journal_template_id").await? {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(template_id = ?template_id), err(Display))]
+    pub async fn set_journal_template_id(&self, template_id: Option<String>) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if let Some(template_id) = &template_id {
+            self.get_note_template(template_id).await?;
+        }
+
+        self.set_app_setting("journal_template_id", &serde_json::to_string(&template_id)?).await?;
+        Ok(template_id)
+    }
+
+    // 番茄钟会话保留天数，0 表示永久保留（默认）
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_pomodoro_retention_days(&self) -> Result<u32, Box<dyn std::error::Error>> {
+        match self.get_app_setting("pomodoro_retention_days").await? {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Ok(0),
+        }
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn set_pomodoro_retention_days(&self, days: u32) -> Result<u32, Box<dyn std::error::Error>> {
+        self.set_app_setting("pomodoro_retention_days", &days.to_string()).await?;
+        Ok(days)
+    }
+
+    // 未配置保留策略时沿用旧的番茄钟保留天数设置（设置了天数即表示启动时自动清理）；配置后以策略为准
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_retention_policy(&self) -> Result<RetentionPolicy, Box<dyn std::error::Error>> {
+        if let Some(value) = self.get_app_setting("retention_policy").await? {
+            return parse_retention_policy(&value);
+        }
+
+        let days = self.get_pomodoro_retention_days().await?;
+        let rules = if days > 0 {
+            vec![RetentionRule {
+                entity: RetentionEntity::PomodoroSession,
+                action: RetentionAction::Purge,
+                older_than_days: days,
+            }]
+        } else {
+            Vec::new()
+        };
+        Ok(RetentionPolicy { rules, auto_apply: true })
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn set_retention_policy(&self, policy_json: &str) -> Result<RetentionPolicy, Box<dyn std::error::Error>> {
+        let policy = parse_retention_policy(policy_json)?;
+        self.set_app_setting("retention_policy", &serde_json::to_string(&policy)?).await?;
+        Ok(policy)
+    }
+
+    async fn get_week_start_weekday(&self) -> Result<Weekday, Box<dyn std::error::Error>> {
+        Ok(week_start_weekday(self.get_week_start().await?))
+    }
+
+    // 日程事件类型
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_event_types(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        match self.get_app_setting("event_types").await? {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Ok(DEFAULT_EVENT_TYPES.iter().map(|t| t.to_string()).collect()),
+        }
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn update_event_types(&self, event_types: Vec<String>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        if event_types.is_empty() || event_types.iter().any(|t| t.trim().is_empty()) {
+            return Err(validation_error("事件类型不能为空"));
+        }
+
+        self.set_app_setting("event_types", &serde_json::to_string(&event_types)?).await?;
+        Ok(event_types)
+    }
+
+    async fn validate_event_fields(&self, event_type: &str, priority: &str, date: &str, end_date: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        validate_priority(priority)?;
+        if let Some(end_date) = end_date {
+            if parse_date(end_date)? < parse_date(date)? {
+                return Err(validation_error("结束日期不能早于开始日期"));
+            }
+        }
+
+        let event_types = self.get_event_types().await?;
+        if !event_types.iter().any(|t| t == event_type) {
+            return Err(validation_error(format!("无效的事件类型: {}", event_type)));
+        }
+
+        Ok(())
+    }
+
+    // 日程事件相关方法
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn create_event(&self, mut request: CreateEventRequest) -> Result<CalendarEvent, Box<dyn std::error::Error>> {
+        request.title = sanitize_title(&request.title, "日程标题")?;
+        self.validate_event_fields(&request.event_type, &request.priority, &request.date, request.end_date.as_deref())
+            .await?;
+        validate_event_times(request.start_time.as_deref(), request.end_time.as_deref())?;
+        validate_repeat_limit(&request.date, request.repeat_until.as_deref(), request.repeat_count)?;
+        validate_attendees(request.attendees.as_deref())?;
+
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let attendees_json = if let Some(attendees) = &request.attendees {
+            Some(serde_json::to_string(attendees)?)
+        } else {
+            None
+        };
+
+        sqlx::query(
+            r#"
             INSERT INTO calendar_events (
-                id, title, description, date, start_time, end_time, event_type, priority, 
-                is_all_day, reminder, repeat_type, location, attendees, created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                id, title, description, date, end_date, start_time, end_time, event_type, priority, 
+                is_all_day, reminder, repeat_type, repeat_until, repeat_count, location, attendees, notification_enabled,
+                created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&id)
+        .bind(&request.title)
+        .bind(&request.description)
+        .bind(&request.date)
+        .bind(&request.end_date)
+        .bind(&request.start_time)
+        .bind(&request.end_time)
+        .bind(&request.event_type)
+        .bind(&request.priority)
+        .bind(request.is_all_day)
+        .bind(request.reminder)
+        .bind(&request.repeat_type)
+        .bind(&request.repeat_until)
+        .bind(request.repeat_count)
+        .bind(&request.location)
+        .bind(&attendees_json)
+        .bind(request.notification_enabled)
+        .bind(now)
+        .bind(now)
+        .execute_with_retry(&self.pool)
+        .await
+        .map_err(map_constraint_error)?;
+
+        self.get_event_with_warnings(&id).await
+    }
+
+    async fn get_event_with_warnings(&self, id: &str) -> Result<CalendarEvent, Box<dyn std::error::Error>> {
+        let mut event = self.get_event(id).await?;
+        event.warnings = validation::event_warnings(&event, Local::now().date_naive());
+        Ok(event)
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn get_event(&self, id: &str) -> Result<CalendarEvent, Box<dyn std::error::Error>> {
+        let event = sqlx::query_as::<_, CalendarEvent>(
+            "SELECT id, title, description, date, end_date, start_time, end_time, event_type, priority, is_all_day, reminder, repeat_type, repeat_until, repeat_count, location, attendees, is_favorite, notification_enabled, created_at, updated_at FROM calendar_events WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(sanitize_event(event))
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_all_events(&self) -> Result<Vec<CalendarEvent>, Box<dyn std::error::Error>> {
+        let events = sqlx::query_as::<_, CalendarEvent>(
+            "SELECT id, title, description, date, end_date, start_time, end_time, event_type, priority, is_all_day, reminder, repeat_type, repeat_until, repeat_count, location, attendees, is_favorite, notification_enabled, created_at, updated_at FROM calendar_events ORDER BY date, start_time"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(events.into_iter().map(sanitize_event).collect())
+    }
+
+    // 重复日程会展开为范围内的每一次发生，并应用取消/修改例外
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_events_by_date_range(&self, start_date: &str, end_date: &str) -> Result<Vec<CalendarEvent>, Box<dyn std::error::Error>> {
+        let start = parse_date(start_date)?;
+        let end = parse_date(end_date)?;
+        if start > end {
+            return Ok(Vec::new());
+        }
+
+        let events = sqlx::query_as::<_, CalendarEvent>(
+            "SELECT id, title, description, date, end_date, start_time, end_time, event_type, priority, is_all_day, reminder, repeat_type, repeat_until, repeat_count, location, attendees, is_favorite, notification_enabled, created_at, updated_at FROM calendar_events WHERE date <= ? AND (COALESCE(end_date, date) >= ? OR (repeat_type IS NOT NULL AND repeat_type != 'none')) ORDER BY date, start_time"
+        )
+        .bind(end_date)
+        .bind(start_date)
+        .fetch_all(&self.pool)
+        .await?;
+
+        // 多日日程额外持续的天数，单日日程为 0
+        let spans: Vec<i64> = events
+            .iter()
+            .map(|event| {
+                match (parse_date(&event.date).ok(), event.end_date.as_deref().and_then(|date| parse_date(date).ok())) {
+                    (Some(first), Some(last)) => (last - first).num_days().max(0),
+                    _ => 0,
+                }
+            })
+            .collect();
+        // 在查询范围之前开始、但仍持续到范围内的那次发生也需要它的例外
+        let earliest = start - Duration::days(spans.iter().copied().max().unwrap_or(0));
+
+        let mut exceptions: HashMap<(String, String), EventException> = sqlx::query_as::<_, EventException>(
+            "SELECT id, event_id, occurrence_date, is_cancelled, override_data, created_at FROM event_exceptions WHERE occurrence_date >= ? AND occurrence_date <= ?"
+        )
+        .bind(earliest.format("%Y-%m-%d").to_string())
+        .bind(end_date)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|exception| ((exception.event_id.clone(), exception.occurrence_date.clone()), exception))
+        .collect();
+
+        let mut occurrences = Vec::new();
+        for (event, span) in events.into_iter().map(sanitize_event).zip(spans) {
+            let Ok(first) = parse_date(&event.date) else {
+                continue;
+            };
+
+            let window_start = start - Duration::days(span);
+            for occurrence_start in event_occurrence_dates(&event, first, window_start, end) {
+                let date = occurrence_start.format("%Y-%m-%d").to_string();
+                let exception = exceptions.remove(&(event.id.clone(), date.clone()));
+                if exception.as_ref().is_some_and(|exception| exception.is_cancelled) {
+                    continue;
+                }
+
+                let mut occurrence = event.clone();
+                if let Some(changes) = exception
+                    .and_then(|exception| exception.override_data)
+                    .and_then(|data| serde_json::from_str::<EventOccurrenceOverride>(&data).ok())
+                {
+                    apply_occurrence_override(&mut occurrence, changes);
+                }
+
+                // 多日日程在与查询范围相交的每一天各返回一次
+                let occurrence_end = occurrence_start + Duration::days(span);
+                if span > 0 {
+                    occurrence.end_date = Some(occurrence_end.format("%Y-%m-%d").to_string());
+                }
+                for day in occurrence_start.iter_days().take_while(|day| *day <= occurrence_end) {
+                    if !(start..=end).contains(&day) {
+                        continue;
+                    }
+                    let mut copy = occurrence.clone();
+                    copy.date = day.format("%Y-%m-%d").to_string();
+                    occurrences.push(copy);
+                }
+            }
+        }
+
+        occurrences.sort_by(|a, b| (&a.date, &a.start_time).cmp(&(&b.date, &b.start_time)));
+        Ok(occurrences)
+    }
+
+    // 按类型筛选范围内的日程，在展开重复日程之后过滤，单次覆盖修改的类型同样生效
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_events_by_type(&self, event_type: &str, start_date: &str, end_date: &str) -> Result<Vec<CalendarEvent>, Box<dyn std::error::Error>> {
+        let event_types = self.get_event_types().await?;
+        if !event_types.iter().any(|t| t == event_type) {
+            return Err(validation_error(format!("无效的事件类型: {}", event_type)));
+        }
+
+        let occurrences = self.get_events_by_date_range(start_date, end_date).await?;
+        Ok(occurrences
+            .into_iter()
+            .filter(|event| event.event_type == event_type)
+            .collect())
+    }
+
+    // 检查日程当天紧挨着它的前一个和后一个定时日程，地点不同且间隔小于 buffer_minutes 时返回；
+    // 全天日程、没有时间或地点的日程不参与比较，重复日程按首次发生的日期检查
+    #[tracing::instrument(skip_all, fields(event_id = %event_id), err(Display))]
+    pub async fn check_event_buffer(&self, event_id: &str, buffer_minutes: i64) -> Result<Vec<EventTransition>, Box<dyn std::error::Error>> {
+        if buffer_minutes < 0 {
+            return Err(validation_error(format!("缓冲时间不能为负数，当前为 {}", buffer_minutes)));
+        }
+        let event = self.get_event(event_id).await?;
+
+        // 以当天 00:00 起的分钟数表示起止时间，跨越午夜的结束时间会超过 24 * 60
+        let timed = |event: &CalendarEvent| -> Option<(i64, i64)> {
+            if event.is_all_day || event.location.as_deref().is_none_or(|location| location.trim().is_empty()) {
+                return None;
+            }
+            let start = parse_event_time(event.start_time.as_deref()?).ok()?;
+            let start = (start.hour() * 60 + start.minute()) as i64;
+            Some((start, start + event.duration_minutes?))
+        };
+        let Some((start, end)) = timed(&event) else {
+            return Ok(Vec::new());
+        };
+
+        let mut previous: Option<(CalendarEvent, i64)> = None;
+        let mut next: Option<(CalendarEvent, i64)> = None;
+        for other in self.get_events_by_date_range(&event.date, &event.date).await? {
+            if other.id == event.id || other.date != event.date {
+                continue;
+            }
+            let Some((other_start, other_end)) = timed(&other) else {
+                continue;
+            };
+            if other_start < start {
+                if previous.as_ref().is_none_or(|(_, latest_end)| other_end > *latest_end) {
+                    previous = Some((other, other_end));
+                }
+            } else if next.as_ref().is_none_or(|(_, earliest_start)| other_start < *earliest_start) {
+                next = Some((other, other_start));
+            }
+        }
+
+        let same_location = |a: &CalendarEvent, b: &CalendarEvent| {
+            let normalize = |event: &CalendarEvent| event.location.as_deref().map(|location| location.trim().to_lowercase());
+            normalize(a) == normalize(b)
+        };
+        let mut transitions = Vec::new();
+        if let Some((other, other_end)) = previous {
+            let gap_minutes = start - other_end;
+            if gap_minutes < buffer_minutes && !same_location(&other, &event) {
+                transitions.push(EventTransition { from: other, to: event.clone(), gap_minutes });
+            }
+        }
+        if let Some((other, other_start)) = next {
+            let gap_minutes = other_start - end;
+            if gap_minutes < buffer_minutes && !same_location(&event, &other) {
+                transitions.push(EventTransition { from: event, to: other, gap_minutes });
+            }
+        }
+
+        Ok(transitions)
+    }
+
+    // 提醒时间（开始时间减去提前分钟数）落在 [from, from + horizon_minutes] 内的日程，已展开重复规则；
+    // 全天日程按当天 00:00 开始计算
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_upcoming_reminders(&self, from: &str, horizon_minutes: i64) -> Result<Vec<UpcomingReminder>, Box<dyn std::error::Error>> {
+        if horizon_minutes < 0 {
+            return Err(validation_error("提醒范围不能为负数"));
+        }
+        let window_start = parse_local_datetime(from)?;
+        let window_end = window_start + Duration::minutes(horizon_minutes);
+
+        // 提醒可能提前很久，查询范围需要向后延伸最大的提前量
+        let max_reminder = sqlx::query("SELECT COALESCE(MAX(reminder), 0) AS max_reminder FROM calendar_events WHERE reminder IS NOT NULL")
+            .fetch_one(&self.pool)
+            .await?
+            .get::<i64, _>("max_reminder");
+        let last_day = (window_end + Duration::minutes(max_reminder.max(0))).date();
+
+        // 多日日程每天都会返回一次，只在开始那天提醒
+        let spans: HashMap<String, i64> = sqlx::query("SELECT id, date, end_date FROM calendar_events WHERE end_date IS NOT NULL")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .filter_map(|row| {
+                let first = parse_date(&row.get::<String, _>("date")).ok()?;
+                let last = parse_date(&row.get::<String, _>("end_date")).ok()?;
+                Some((row.get::<String, _>("id"), (last - first).num_days().max(0)))
+            })
+            .collect();
+
+        let occurrences = self
+            .get_events_by_date_range(
+                &window_start.date().format("%Y-%m-%d").to_string(),
+                &last_day.format("%Y-%m-%d").to_string(),
+            )
+            .await?;
+
+        let mut reminders = Vec::new();
+        for event in occurrences {
+            let Some(reminder) = event.reminder else {
+                continue;
+            };
+            let Ok(day) = parse_date(&event.date) else {
+                continue;
+            };
+            if let (Some(span), Some(end_date)) = (spans.get(&event.id), event.end_date.as_deref()) {
+                if parse_date(end_date).ok() != Some(day + Duration::days(*span)) {
+                    continue;
+                }
+            }
+
+            let start_time = if event.is_all_day {
+                Some(NaiveTime::MIN)
+            } else {
+                event.start_time.as_deref().and_then(|time| parse_event_time(time).ok())
+            };
+            let Some(start_time) = start_time else {
+                continue;
+            };
+
+            let remind_at = day.and_time(start_time) - Duration::minutes(reminder as i64);
+            if (window_start..=window_end).contains(&remind_at) {
+                reminders.push(UpcomingReminder {
+                    event,
+                    remind_at: remind_at.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                });
+            }
+        }
+
+        reminders.sort_by(|a, b| a.remind_at.cmp(&b.remind_at));
+        Ok(reminders)
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %request.id), err(Display))]
+    pub async fn update_event(&self, mut request: UpdateEventRequest) -> Result<CalendarEvent, Box<dyn std::error::Error>> {
+        request.title = sanitize_title(&request.title, "日程标题")?;
+        let previous = self.get_event(&request.id).await?;
+        // 编辑表单没有结束日期字段，缺省时保持原来的天数跨度，避免多日日程被改回单日
+        if request.clear_end_date {
+            request.end_date = None;
+        } else if request.end_date.is_none() {
+            if let Some(previous_end) = &previous.end_date {
+                let span = parse_date(previous_end)? - parse_date(&previous.date)?;
+                request.end_date = Some((parse_date(&request.date)? + span).format("%Y-%m-%d").to_string());
+            }
+        }
+        // 重复的结束日期和次数同理：缺省时保持原值，clear_repeat_limit 为 true 时一并清除
+        if request.clear_repeat_limit {
+            request.repeat_until = None;
+            request.repeat_count = None;
+        } else {
+            if request.repeat_until.is_none() {
+                request.repeat_until = previous.repeat_until.clone();
+            }
+            if request.repeat_count.is_none() {
+                request.repeat_count = previous.repeat_count;
+            }
+        }
+        self.validate_event_fields(&request.event_type, &request.priority, &request.date, request.end_date.as_deref())
+            .await?;
+        validate_event_times(request.start_time.as_deref(), request.end_time.as_deref())?;
+        validate_repeat_limit(&request.date, request.repeat_until.as_deref(), request.repeat_count)?;
+        validate_attendees(request.attendees.as_deref())?;
+
+        let now = Utc::now();
+        let attendees_json = if let Some(attendees) = &request.attendees {
+            Some(serde_json::to_string(attendees)?)
+        } else {
+            None
+        };
+
+        let result = sqlx::query(
+            r#"
+            UPDATE calendar_events SET 
+                title = ?, description = ?, date = ?, end_date = ?, start_time = ?, end_time = ?, 
+                event_type = ?, priority = ?, is_all_day = ?, reminder = ?, 
+                repeat_type = ?, repeat_until = ?, repeat_count = ?, location = ?, attendees = ?,
+                notification_enabled = COALESCE(?, notification_enabled), updated_at = ?
+            WHERE id = ? AND (? IS NULL OR updated_at = ?)
+            "#,
+        )
+        .bind(&request.title)
+        .bind(&request.description)
+        .bind(&request.date)
+        .bind(&request.end_date)
+        .bind(&request.start_time)
+        .bind(&request.end_time)
+        .bind(&request.event_type)
+        .bind(&request.priority)
+        .bind(request.is_all_day)
+        .bind(request.reminder)
+        .bind(&request.repeat_type)
+        .bind(&request.repeat_until)
+        .bind(request.repeat_count)
+        .bind(&request.location)
+        .bind(&attendees_json)
+        .bind(request.notification_enabled)
+        .bind(now)
+        .bind(&request.id)
+        .bind(request.expected_updated_at)
+        .bind(request.expected_updated_at)
+        .execute_with_retry(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 && request.expected_updated_at.is_some() {
+            let current = self.get_event(&request.id).await?;
+            return Err(conflict_error(&current));
+        }
+        self.record_undo("updated", UndoSnapshot::Event { event: previous, exceptions: Vec::new(), links: Vec::new() });
+
+        self.get_event_with_warnings(&request.id).await
+    }
+
+    // 读取和删除在同一事务中完成，返回被删除的日程；不存在时返回 None
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn delete_event(&self, id: &str) -> Result<Option<CalendarEvent>, Box<dyn std::error::Error>> {
+        let id = id.to_string();
+        let deleted = self
+            .with_transaction(|conn| {
+                let id = id.clone();
+                Box::pin(async move {
+                    let Some(event) = sqlx::query_as::<_, CalendarEvent>(
+                        "SELECT id, title, description, date, end_date, start_time, end_time, event_type, priority, is_all_day, reminder, repeat_type, repeat_until, repeat_count, location, attendees, is_favorite, notification_enabled, created_at, updated_at FROM calendar_events WHERE id = ?"
+                    )
+                    .bind(&id)
+                    .fetch_optional(&mut *conn)
+                    .await?
+                    else {
+                        return Ok::<_, Box<dyn std::error::Error>>(None);
+                    };
+                    let exceptions = sqlx::query_as::<_, EventException>(
+                        "SELECT id, event_id, occurrence_date, is_cancelled, override_data, created_at FROM event_exceptions WHERE event_id = ?"
+                    )
+                    .bind(&id)
+                    .fetch_all(&mut *conn)
+                    .await?;
+                    let links = load_entity_links(conn, "event", &id).await?;
+
+                    sqlx::query("DELETE FROM calendar_events WHERE id = ?")
+                        .bind(&id)
+                        .execute(&mut *conn)
+                        .await?;
+                    delete_entity_links(conn, "event", &id).await?;
+
+                    Ok(Some((sanitize_event(event), exceptions, links)))
+                })
+            })
+            .await?;
+
+        Ok(deleted.map(|(event, exceptions, links)| {
+            self.record_undo("deleted", UndoSnapshot::Event { event: event.clone(), exceptions, links });
+            event
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn toggle_event_favorite(&self, id: &str) -> Result<CalendarEvent, Box<dyn std::error::Error>> {
+        let result = sqlx::query("UPDATE calendar_events SET is_favorite = NOT is_favorite, updated_at = ? WHERE id = ?")
+            .bind(Utc::now())
+            .bind(id)
+            .execute_with_retry(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(not_found_error("日程", id));
+        }
+
+        self.get_event(id).await
+    }
+
+    // 收藏的日程按原始定义返回，不展开重复规则
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_favorite_events(&self) -> Result<Vec<CalendarEvent>, Box<dyn std::error::Error>> {
+        let events = sqlx::query_as::<_, CalendarEvent>(
+            "SELECT id, title, description, date, end_date, start_time, end_time, event_type, priority, is_all_day, reminder, repeat_type, repeat_until, repeat_count, location, attendees, is_favorite, notification_enabled, created_at, updated_at FROM calendar_events WHERE is_favorite = TRUE ORDER BY date, start_time"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(events.into_iter().map(sanitize_event).collect())
+    }
+
+    // 把 from_date 当天开始的日程复制到 to_date；重复日程按原始定义复制，不展开
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn copy_events(&self, from_date: &str, to_date: &str) -> Result<Vec<CalendarEvent>, Box<dyn std::error::Error>> {
+        let offset = parse_date(to_date)? - parse_date(from_date)?;
+        let from_date = from_date.to_string();
+        let to_date = to_date.to_string();
+
+        self.with_transaction(|conn| {
+            let from_date = from_date.clone();
+            let to_date = to_date.clone();
+            Box::pin(async move {
+                let events = sqlx::query_as::<_, CalendarEvent>(
+                    "SELECT id, title, description, date, end_date, start_time, end_time, event_type, priority, is_all_day, reminder, repeat_type, repeat_until, repeat_count, location, attendees, is_favorite, notification_enabled, created_at, updated_at FROM calendar_events WHERE date = ? ORDER BY start_time"
+                )
+                .bind(&from_date)
+                .fetch_all(&mut *conn)
+                .await?;
+
+                let now = Utc::now();
+                let mut copies = Vec::with_capacity(events.len());
+                for event in events.into_iter().map(sanitize_event) {
+                    // 多日日程保持原有跨度
+                    let end_date = match event.end_date.as_deref() {
+                        Some(end_date) => Some((parse_date(end_date)? + offset).format("%Y-%m-%d").to_string()),
+                        None => None,
+                    };
+                    let copy = CalendarEvent {
+                        id: Uuid::new_v4().to_string(),
+                        date: to_date.clone(),
+                        end_date,
+                        is_favorite: false,
+                        created_at: now,
+                        updated_at: now,
+                        ..event
+                    };
+
+                    sqlx::query(
+                        r#"
+                        INSERT INTO calendar_events (
+                            id, title, description, date, end_date, start_time, end_time, event_type, priority,
+                            is_all_day, reminder, repeat_type, repeat_until, repeat_count, location, attendees, notification_enabled,
+                            created_at, updated_at
+                        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                        "#,
+                    )
+                    .bind(&copy.id)
+                    .bind(&copy.title)
+                    .bind(&copy.description)
+                    .bind(&copy.date)
+                    .bind(&copy.end_date)
+                    .bind(&copy.start_time)
+                    .bind(&copy.end_time)
+                    .bind(&copy.event_type)
+                    .bind(&copy.priority)
+                    .bind(copy.is_all_day)
+                    .bind(copy.reminder)
+                    .bind(&copy.repeat_type)
+                    .bind(&copy.repeat_until)
+                    .bind(copy.repeat_count)
+                    .bind(&copy.location)
+                    .bind(&copy.attendees)
+                    .bind(copy.notification_enabled)
+                    .bind(copy.created_at)
+                    .bind(copy.updated_at)
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(map_constraint_error)?;
+
+                    copies.push(copy);
+                }
+
+                Ok::<_, Box<dyn std::error::Error>>(copies)
+            })
+        })
+        .await
+    }
+
+    // 月视图一次取齐每天的日程、截止待办数和完成的番茄数，按日期排列
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_month_overview(&self, year: i32, month: u32) -> Result<Vec<DayOverview>, Box<dyn std::error::Error>> {
+        let first = NaiveDate::from_ymd_opt(year, month, 1)
+            .ok_or_else(|| validation_error(format!("无效的月份: {}-{}", year, month)))?;
+        let next_month = if month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(year, month + 1, 1)
+        }
+        .ok_or_else(|| validation_error(format!("无效的月份: {}-{}", year, month)))?;
+        let last = next_month - Duration::days(1);
+        let start_date = first.format("%Y-%m-%d").to_string();
+        let end_date = last.format("%Y-%m-%d").to_string();
+
+        let mut events_by_date: HashMap<String, Vec<CalendarEvent>> = HashMap::new();
+        for event in self.get_events_by_date_range(&start_date, &end_date).await? {
+            events_by_date.entry(event.date.clone()).or_default().push(event);
+        }
+
+        let todos_due: HashMap<String, i64> = sqlx::query(
+            "SELECT substr(due_date, 1, 10) AS day, COUNT(*) AS count FROM todos WHERE completed = FALSE AND substr(due_date, 1, 10) BETWEEN ? AND ? GROUP BY day"
+        )
+        .bind(&start_date)
+        .bind(&end_date)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.get::<String, _>("day"), row.get::<i64, _>("count")))
+        .collect();
+
+        let pomodoros: HashMap<String, i64> = sqlx::query(
+            "SELECT date, COUNT(*) AS count FROM pomodoro_sessions WHERE session_type = 'work' AND completed = TRUE AND date >= ? AND date <= ? GROUP BY date"
+        )
+        .bind(&start_date)
+        .bind(&end_date)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.get::<String, _>("date"), row.get::<i64, _>("count")))
+        .collect();
+
+        Ok(first
+            .iter_days()
+            .take_while(|day| *day <= last)
+            .map(|day| {
+                let date = day.format("%Y-%m-%d").to_string();
+                let events = events_by_date.remove(&date).unwrap_or_default();
+                DayOverview {
+                    event_count: events.len() as i64,
+                    events,
+                    todos_due: todos_due.get(&date).copied().unwrap_or(0),
+                    completed_pomodoros: pomodoros.get(&date).copied().unwrap_or(0),
+                    date,
+                }
+            })
+            .collect())
+    }
+
+    // date 所在周的日程（已展开重复日程）
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_week_agenda(&self, date: &str) -> Result<Vec<CalendarEvent>, Box<dyn std::error::Error>> {
+        let day = parse_date(date)?;
+        let start = week_start_of(day, self.get_week_start_weekday().await?);
+        let end = start + Duration::days(6);
+
+        self.get_events_by_date_range(&start.format("%Y-%m-%d").to_string(), &end.format("%Y-%m-%d").to_string())
+            .await
+    }
+
+    // 按标题、地点或参与人搜索日程；地点/参与人为空的日程不会因此命中
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn search_events(&self, query: &str) -> Result<Vec<CalendarEvent>, Box<dyn std::error::Error>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let pattern = search::like_contains(query);
+
+        let events = sqlx::query_as::<_, CalendarEvent>(
+            r#"
+            SELECT id, title, description, date, end_date, start_time, end_time, event_type, priority, is_all_day, reminder, repeat_type, repeat_until, repeat_count, location, attendees, is_favorite, notification_enabled, created_at, updated_at
+            FROM calendar_events e
+            WHERE e.title LIKE ?1 ESCAPE '\'
+                OR e.location LIKE ?1 ESCAPE '\'
+                OR CASE WHEN json_valid(e.attendees)
+                    THEN EXISTS (
+                        SELECT 1 FROM json_each(e.attendees) a
+                        WHERE CASE a.type
+                            WHEN 'object' THEN json_extract(a.value, '$.name') LIKE ?1 ESCAPE '\'
+                                OR json_extract(a.value, '$.email') LIKE ?1 ESCAPE '\'
+                            ELSE a.value LIKE ?1 ESCAPE '\' END
+                    )
+                    ELSE 0 END
+            ORDER BY date, start_time
+            "#,
+        )
+        .bind(&pattern)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(events.into_iter().map(sanitize_event).collect())
+    }
+
+    // 重复日程例外相关方法
+    async fn upsert_event_exception(&self, event_id: &str, occurrence_date: &str, is_cancelled: bool, override_data: Option<String>) -> Result<EventException, Box<dyn std::error::Error>> {
+        let event = self.get_event(event_id).await?;
+        let day = parse_date(occurrence_date)?;
+        let first = parse_date(&event.date)?;
+        if event_occurrence_dates(&event, first, day, day).is_empty() {
+            return Err(validation_error(format!("日程在 {} 没有发生", occurrence_date)));
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO event_exceptions (id, event_id, occurrence_date, is_cancelled, override_data, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(event_id, occurrence_date) DO UPDATE SET
+                is_cancelled = excluded.is_cancelled, override_data = excluded.override_data
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(event_id)
+        .bind(occurrence_date)
+        .bind(is_cancelled)
+        .bind(&override_data)
+        .bind(Utc::now())
+        .execute_with_retry(&self.pool)
+        .await?;
+
+        let exception = sqlx::query_as::<_, EventException>(
+            "SELECT id, event_id, occurrence_date, is_cancelled, override_data, created_at FROM event_exceptions WHERE event_id = ? AND occurrence_date = ?"
+        )
+        .bind(event_id)
+        .bind(occurrence_date)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(exception)
+    }
+
+    #[tracing::instrument(skip_all, fields(event_id = %event_id), err(Display))]
+    pub async fn cancel_event_occurrence(&self, event_id: &str, date: &str) -> Result<EventException, Box<dyn std::error::Error>> {
+        self.upsert_event_exception(event_id, date, true, None).await
+    }
+
+    #[tracing::instrument(skip_all, fields(event_id = %request.event_id), err(Display))]
+    pub async fn modify_event_occurrence(&self, request: ModifyEventOccurrenceRequest) -> Result<EventException, Box<dyn std::error::Error>> {
+        if let Some(priority) = &request.changes.priority {
+            validate_priority(priority)?;
+        }
+        validate_event_times(request.changes.start_time.as_deref(), request.changes.end_time.as_deref())?;
+
+        let override_data = serde_json::to_string(&request.changes)?;
+        self.upsert_event_exception(&request.event_id, &request.occurrence_date, false, Some(override_data))
+            .await
+    }
+
+    // 待办事项相关方法
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn create_todo(&self, mut request: CreateTodoRequest) -> Result<Todo, Box<dyn std::error::Error>> {
+        request.title = sanitize_title(&request.title, "待办标题")?;
+        validate_priority(&request.priority)?;
+        if request.category.trim().is_empty() {
+            request.category = self.get_default_category("todo").await?;
+        }
+
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let tags_json = if let Some(tags) = &request.tags {
+            Some(serde_json::to_string(tags)?)
+        } else {
+            None
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO todos (
+                id, title, title_normalized, description, completed, priority, tags, due_date, category,
+                auto_complete_on_subtasks, created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&id)
+        .bind(&request.title)
+        .bind(search::normalize(&request.title))
+        .bind(&request.description)
+        .bind(false)
+        .bind(&request.priority)
+        .bind(&tags_json)
+        .bind(&request.due_date)
+        .bind(&request.category)
+        .bind(request.auto_complete_on_subtasks)
+        .bind(now)
+        .bind(now)
+        .execute_with_retry(&self.pool)
+        .await
+        .map_err(map_constraint_error)?;
+
+        self.get_todo_with_warnings(&id).await
+    }
+
+    // 例如 "Buy milk tomorrow #groceries !high"，相对日期以本地日期为准
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn create_todo_from_text(&self, input: &str) -> Result<Todo, Box<dyn std::error::Error>> {
+        let request = quickadd::parse_quick_todo(input, Local::now().date_naive());
+        if request.title.is_empty() {
+            return Err(validation_error("待办标题不能为空"));
+        }
+
+        self.create_todo(request).await
+    }
+
+    async fn get_todo_with_warnings(&self, id: &str) -> Result<Todo, Box<dyn std::error::Error>> {
+        let mut todo = self.get_todo(id).await?;
+        todo.warnings = validation::todo_warnings(&todo, Local::now().date_naive());
+        Ok(todo)
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn get_todo(&self, id: &str) -> Result<Todo, Box<dyn std::error::Error>> {
+        let todo = sqlx::query_as::<_, Todo>(
+            "SELECT id, title, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at FROM todos WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(sanitize_todo(todo))
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_all_todos(&self, sort_by: Option<&str>) -> Result<Vec<Todo>, Box<dyn std::error::Error>> {
+        let todos = sqlx::query_as::<_, Todo>(&format!(
+            "SELECT id, title, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at FROM todos WHERE is_archived = FALSE ORDER BY {}",
+            todo_order_by(sort_by)?
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(todos.into_iter().map(sanitize_todo).collect())
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn query_todos(&self, request: QueryTodosRequest) -> Result<PaginatedResult<Todo>, Box<dyn std::error::Error>> {
+        let page = request.page.unwrap_or(1);
+        let page_size = request.page_size.unwrap_or(20);
+        if page < 1 {
+            return Err(validation_error("页码必须从 1 开始"));
+        }
+        if !(1..=200).contains(&page_size) {
+            return Err(validation_error("每页数量必须在 1 到 200 之间"));
+        }
+        let order_by = todo_order_by(request.sort_by.as_deref())?;
+
+        let counts = sqlx::query(
+            "SELECT COUNT(*) AS total_count, COALESCE(SUM(CASE WHEN completed THEN 1 ELSE 0 END), 0) AS completed_count FROM todos WHERE is_archived = FALSE AND (? IS NULL OR category = ?)"
+        )
+        .bind(&request.category)
+        .bind(&request.category)
+        .fetch_one(&self.pool)
+        .await?;
+        let total_count = counts.get::<i64, _>("total_count");
+        let completed_count = counts.get::<i64, _>("completed_count");
+        drop(counts);
+
+        let items = sqlx::query_as::<_, Todo>(&format!(
+            "SELECT id, title, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at FROM todos WHERE is_archived = FALSE AND (? IS NULL OR category = ?) ORDER BY {} LIMIT ? OFFSET ?",
+            order_by
+        ))
+        .bind(&request.category)
+        .bind(&request.category)
+        .bind(page_size)
+        .bind((page - 1) * page_size)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(PaginatedResult {
+            items: items.into_iter().map(sanitize_todo).collect(),
+            page,
+            page_size,
+            total_count,
+            completed_count: Some(completed_count),
+        })
+    }
+
+    // 模糊搜索：先用二元组 LIKE 粗筛候选，再在内存中按编辑距离打分排序
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn search_todos(&self, query: &str) -> Result<Vec<Todo>, Box<dyn std::error::Error>> {
+        let tokens = search::tokenize(query);
+        let grams = search::bigrams(&tokens);
+        if grams.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            "SELECT id, title, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at FROM todos WHERE is_archived = FALSE AND ("
+        );
+        for (i, gram) in grams.iter().enumerate() {
+            if i > 0 {
+                builder.push(" OR ");
+            }
+            let pattern = search::like_contains(gram);
+            builder
+                .push("title_normalized LIKE ")
+                .push_bind(pattern.clone())
+                .push(" ESCAPE '\\' OR description LIKE ")
+                .push_bind(pattern)
+                .push(" ESCAPE '\\'");
+        }
+        builder.push(") LIMIT 500");
+
+        let candidates = builder.build_query_as::<Todo>().fetch_all(&self.pool).await?;
+        let candidates = candidates.into_iter().map(sanitize_todo);
+
+        // 标题命中的权重高于描述，每个词都必须命中
+        let mut scored: Vec<(f64, Todo)> = candidates
+            .filter_map(|todo| {
+                let mut total = 0.0;
+                for token in &tokens {
+                    let title_score = search::token_score(token, &todo.title) * 2.0;
+                    let description_score = todo
+                        .description
+                        .as_deref()
+                        .map(|description| search::token_score(token, description))
+                        .unwrap_or(0.0);
+                    let best = title_score.max(description_score);
+                    if best == 0.0 {
+                        return None;
+                    }
+                    total += best;
+                }
+                Some((total, todo))
+            })
+            .collect();
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Ok(scored.into_iter().map(|(_, todo)| todo).collect())
+    }
+
+    // 组合条件搜索：解析搜索框中的 field:value 条件，SQL 负责能直接比较的字段，标签和自由文本在内存中过滤
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn filter_todos(&self, query: &str) -> Result<TodoSearchResult, Box<dyn std::error::Error>> {
+        let today = Local::now().date_naive();
+        let (filter, warnings) = search::parse_todo_query(query, today, self.get_week_start_weekday().await?);
+
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            "SELECT id, title, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at FROM todos WHERE is_archived = FALSE"
+        );
+        if let Some(priority) = &filter.priority {
+            builder.push(" AND priority = ").push_bind(priority.clone());
+        }
+        if let Some(category) = &filter.category {
+            builder.push(" AND category = ").push_bind(category.clone());
+        }
+        if let Some(completed) = filter.completed {
+            builder.push(" AND completed = ").push_bind(completed);
+        }
+        if let (Some(from), Some(to)) = (filter.due_from, filter.due_to) {
+            builder
+                .push(" AND due_date IS NOT NULL AND substr(due_date, 1, 10) BETWEEN ")
+                .push_bind(from.format("%Y-%m-%d").to_string())
+                .push(" AND ")
+                .push_bind(to.format("%Y-%m-%d").to_string());
+        }
+        if filter.overdue {
+            builder
+                .push(" AND completed = FALSE AND due_date IS NOT NULL AND substr(due_date, 1, 10) < ")
+                .push_bind(today.format("%Y-%m-%d").to_string());
+        }
+        builder.push(" ORDER BY due_date IS NULL, due_date, created_at");
+
+        let todos = builder.build_query_as::<Todo>().fetch_all(&self.pool).await?;
+        let items = todos
+            .into_iter()
+            .map(sanitize_todo)
+            .filter(|todo| {
+                let tags: Vec<String> = todo
+                    .tags
+                    .as_deref()
+                    .and_then(|tags| serde_json::from_str(tags).ok())
+                    .unwrap_or_default();
+                filter.tags.iter().all(|tag| tags.contains(tag))
+            })
+            .filter(|todo| {
+                let text = search::normalize(&format!("{} {}", todo.title, todo.description.as_deref().unwrap_or("")));
+                filter.text.iter().all(|word| text.contains(word.as_str()))
+            })
+            .collect();
+
+        Ok(TodoSearchResult { items, warnings })
+    }
+
+    // 未完成且截止日期在 [today, today + days] 内的待办，按截止日期排序
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_todos_due_within(&self, today: &str, days: i64) -> Result<Vec<Todo>, Box<dyn std::error::Error>> {
+        if days < 0 {
+            return Err(validation_error("天数不能为负数"));
+        }
+        let start = parse_date(today)?;
+        let end = start
+            .checked_add_signed(Duration::days(days))
+            .ok_or_else(|| validation_error("天数过大"))?;
+
+        let todos = sqlx::query_as::<_, Todo>(
+            "SELECT id, title, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at FROM todos WHERE completed = FALSE AND is_archived = FALSE AND due_date IS NOT NULL AND substr(due_date, 1, 10) BETWEEN ? AND ?"
+        )
+        .bind(start.format("%Y-%m-%d").to_string())
+        .bind(end.format("%Y-%m-%d").to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut todos: Vec<Todo> = todos.into_iter().map(sanitize_todo).collect();
+        todos.retain(|todo| {
+            todo.due_date
+                .as_deref()
+                .and_then(parse_due_date)
+                .is_some_and(|due| (start..=end).contains(&due))
+        });
+        todos.sort_by(|a, b| a.due_date.cmp(&b.due_date));
+
+        Ok(todos)
+    }
+
+    #[tracing::instrument(skip_all, fields(count = ids.len()), err(Display))]
+    pub async fn add_tag_to_todos(&self, ids: &[String], tag: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        self.update_todos_tag(ids, tag, true).await
+    }
+
+    #[tracing::instrument(skip_all, fields(count = ids.len()), err(Display))]
+    pub async fn remove_tag_from_todos(&self, ids: &[String], tag: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        self.update_todos_tag(ids, tag, false).await
+    }
+
+    // 在 Rust 中增删 tags JSON 数组中的标签，返回实际修改的待办数
+    async fn update_todos_tag(&self, ids: &[String], tag: &str, add: bool) -> Result<u64, Box<dyn std::error::Error>> {
+        let tag = tag.trim().to_string();
+        if tag.is_empty() {
+            return Err(validation_error("标签不能为空"));
+        }
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let ids = ids.to_vec();
+
+        self.with_transaction(|conn| {
+            let ids = ids.clone();
+            let tag = tag.clone();
+            Box::pin(async move {
+                let now = Utc::now();
+                let mut modified = 0;
+                let mut builder = QueryBuilder::<Sqlite>::new("SELECT id, tags FROM todos WHERE id IN ");
+                push_id_list(&mut builder, &ids);
+                let rows = builder.build().fetch_all(&mut *conn).await?;
+
+                for row in rows {
+                    let id = row.get::<String, _>("id");
+                    let mut tags: Vec<String> = row
+                        .get::<Option<String>, _>("tags")
+                        .and_then(|raw| serde_json::from_str(&raw).ok())
+                        .unwrap_or_default();
+
+                    let changed = if add {
+                        if tags.contains(&tag) {
+                            false
+                        } else {
+                            tags.push(tag.clone());
+                            true
+                        }
+                    } else {
+                        let before = tags.len();
+                        tags.retain(|existing| existing != &tag);
+                        tags.len() != before
+                    };
+                    if !changed {
+                        continue;
+                    }
+
+                    sqlx::query("UPDATE todos SET tags = ?, updated_at = ? WHERE id = ?")
+                        .bind(serde_json::to_string(&tags)?)
+                        .bind(now)
+                        .bind(&id)
+                        .execute(&mut *conn)
+                        .await?;
+                    modified += 1;
+                }
+                Ok::<_, Box<dyn std::error::Error>>(modified)
+            })
+        })
+        .await
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_todos_with_subtask_counts(&self) -> Result<Vec<TodoWithSubtaskCounts>, Box<dyn std::error::Error>> {
+        let todos = sqlx::query_as::<_, TodoWithSubtaskCounts>(
+            r#"
+            SELECT
+                t.id, t.title, t.description, t.completed, t.priority, t.tags, t.due_date, t.category, t.auto_complete_on_subtasks, t.is_archived, t.created_at, t.updated_at,
+                COUNT(s.id) AS subtask_total,
+                COALESCE(SUM(CASE WHEN s.completed THEN 1 ELSE 0 END), 0) AS subtask_completed
+            FROM todos t
+            LEFT JOIN subtasks s ON s.todo_id = t.id
+            WHERE t.is_archived = FALSE
+            GROUP BY t.id
+            ORDER BY t.created_at DESC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(todos
+            .into_iter()
+            .map(|mut item| {
+                item.todo = sanitize_todo(item.todo);
+                item
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %request.id), err(Display))]
+    pub async fn update_todo(&self, mut request: UpdateTodoRequest) -> Result<Todo, Box<dyn std::error::Error>> {
+        request.title = sanitize_title(&request.title, "待办标题")?;
+        validate_priority(&request.priority)?;
+        let previous = self.get_todo(&request.id).await?;
+
+        let now = Utc::now();
+        let tags_json = if let Some(tags) = &request.tags {
+            Some(serde_json::to_string(tags)?)
+        } else {
+            None
+        };
+
+        let result = sqlx::query(
+            r#"
+            UPDATE todos SET 
+                title = ?, title_normalized = ?, description = ?, completed = ?, priority = ?, 
+                tags = ?, due_date = ?, category = ?,
+                auto_complete_on_subtasks = COALESCE(?, auto_complete_on_subtasks), updated_at = ?
+            WHERE id = ? AND (? IS NULL OR updated_at = ?)
+            "#,
+        )
+        .bind(&request.title)
+        .bind(search::normalize(&request.title))
+        .bind(&request.description)
+        .bind(request.completed)
+        .bind(&request.priority)
+        .bind(&tags_json)
+        .bind(&request.due_date)
+        .bind(&request.category)
+        .bind(request.auto_complete_on_subtasks)
+        .bind(now)
+        .bind(&request.id)
+        .bind(request.expected_updated_at)
+        .bind(request.expected_updated_at)
+        .execute_with_retry(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 && request.expected_updated_at.is_some() {
+            let current = self.get_todo(&request.id).await?;
+            return Err(conflict_error(&current));
+        }
+        self.record_undo("updated", UndoSnapshot::Todo { todo: previous, subtasks: Vec::new(), links: Vec::new() });
+
+        self.get_todo_with_warnings(&request.id).await
+    }
+
+    // 读取和删除在同一事务中完成，返回被删除的待办；不存在时返回 None
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn delete_todo(&self, id: &str) -> Result<Option<Todo>, Box<dyn std::error::Error>> {
+        let id = id.to_string();
+        let deleted = self
+            .with_transaction(|conn| {
+                let id = id.clone();
+                Box::pin(async move {
+                    let Some(todo) = sqlx::query_as::<_, Todo>(
+                        "SELECT id, title, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at FROM todos WHERE id = ?"
+                    )
+                    .bind(&id)
+                    .fetch_optional(&mut *conn)
+                    .await?
+                    else {
+                        return Ok::<_, Box<dyn std::error::Error>>(None);
+                    };
+                    let subtasks = sqlx::query_as::<_, Subtask>(
+                        "SELECT id, todo_id, title, completed, created_at FROM subtasks WHERE todo_id = ? ORDER BY created_at"
+                    )
+                    .bind(&id)
+                    .fetch_all(&mut *conn)
+                    .await?;
+                    let links = load_entity_links(conn, "todo", &id).await?;
+
+                    sqlx::query("DELETE FROM todos WHERE id = ?")
+                        .bind(&id)
+                        .execute(&mut *conn)
+                        .await?;
+                    delete_entity_links(conn, "todo", &id).await?;
+
+                    Ok(Some((sanitize_todo(todo), subtasks, links)))
+                })
+            })
+            .await?;
+
+        Ok(deleted.map(|(todo, subtasks, links)| {
+            self.record_undo("deleted", UndoSnapshot::Todo { todo: todo.clone(), subtasks, links });
+            todo
+        }))
+    }
+
+    // 把 secondary 合并进 primary：子任务保持完成状态并按原顺序排到 primary 已有子任务之后，
+    // 描述依次拼接，标签取并集，关联转移到 primary，最后删除 secondary
+    #[tracing::instrument(skip_all, fields(primary_id = %primary_id, secondary_id = %secondary_id), err(Display))]
+    pub async fn merge_todos(&self, primary_id: &str, secondary_id: &str) -> Result<Todo, Box<dyn std::error::Error>> {
+        if primary_id == secondary_id {
+            return Err(validation_error("不能将待办与自身合并"));
+        }
+        let primary_id = primary_id.to_string();
+        let secondary_id = secondary_id.to_string();
+
+        let merged = self
+            .with_transaction(|conn| {
+                let primary_id = primary_id.clone();
+                let secondary_id = secondary_id.clone();
+                Box::pin(async move {
+                    let mut todos = Vec::with_capacity(2);
+                    for id in [&primary_id, &secondary_id] {
+                        let todo = sqlx::query_as::<_, Todo>(
+                            "SELECT id, title, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at FROM todos WHERE id = ?"
+                        )
+                        .bind(id)
+                        .fetch_optional(&mut *conn)
+                        .await?
+                        .ok_or_else(|| not_found_error("待办", id))?;
+                        todos.push(sanitize_todo(todo));
+                    }
+                    let (primary, secondary) = (todos.remove(0), todos.remove(0));
+
+                    // 子任务按创建时间排序，顺延 created_at 使移入的子任务排在最后
+                    let mut last_created_at = sqlx::query("SELECT MAX(created_at) AS created_at FROM subtasks WHERE todo_id = ?")
+                        .bind(&primary_id)
+                        .fetch_one(&mut *conn)
+                        .await?
+                        .get::<Option<DateTime<Utc>>, _>("created_at");
+                    let moved = sqlx::query_as::<_, Subtask>(
+                        "SELECT id, todo_id, title, completed, created_at FROM subtasks WHERE todo_id = ? ORDER BY created_at"
+                    )
+                    .bind(&secondary_id)
+                    .fetch_all(&mut *conn)
+                    .await?;
+                    for subtask in &moved {
+                        let created_at = match last_created_at {
+                            Some(last) if subtask.created_at <= last => last + Duration::milliseconds(1),
+                            _ => subtask.created_at,
+                        };
+                        sqlx::query("UPDATE subtasks SET todo_id = ?, created_at = ? WHERE id = ?")
+                            .bind(&primary_id)
+                            .bind(created_at)
+                            .bind(&subtask.id)
+                            .execute(&mut *conn)
+                            .await?;
+                        last_created_at = Some(created_at);
+                    }
+
+                    let descriptions: Vec<&str> = [primary.description.as_deref(), secondary.description.as_deref()]
+                        .into_iter()
+                        .flatten()
+                        .filter(|description| !description.trim().is_empty())
+                        .collect();
+                    let description = if descriptions.is_empty() {
+                        primary.description.clone()
+                    } else {
+                        Some(descriptions.join("\n\n"))
+                    };
+                    let parse_tags = |tags: &Option<String>| -> Vec<String> {
+                        tags.as_deref().and_then(|raw| serde_json::from_str(raw).ok()).unwrap_or_default()
+                    };
+                    let mut tags = parse_tags(&primary.tags);
+                    for tag in parse_tags(&secondary.tags) {
+                        if !tags.contains(&tag) {
+                            tags.push(tag);
+                        }
+                    }
+                    let tags_json = if tags.is_empty() && primary.tags.is_none() && secondary.tags.is_none() {
+                        None
+                    } else {
+                        Some(serde_json::to_string(&tags)?)
+                    };
+
+                    sqlx::query("UPDATE todos SET description = ?, tags = ?, updated_at = ? WHERE id = ?")
+                        .bind(&description)
+                        .bind(&tags_json)
+                        .bind(Utc::now())
+                        .bind(&primary_id)
+                        .execute(&mut *conn)
+                        .await?;
+
+                    // 已经与 primary 关联的便笺保留原关联，其余转移过去
+                    sqlx::query("UPDATE OR IGNORE entity_links SET target_id = ? WHERE target_type = 'todo' AND target_id = ?")
+                        .bind(&primary_id)
+                        .bind(&secondary_id)
+                        .execute(&mut *conn)
+                        .await?;
+                    delete_entity_links(conn, "todo", &secondary_id).await?;
+                    sqlx::query("DELETE FROM todos WHERE id = ?")
+                        .bind(&secondary_id)
+                        .execute(&mut *conn)
+                        .await?;
+
+                    let merged = sqlx::query_as::<_, Todo>(
+                        "SELECT id, title, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at FROM todos WHERE id = ?"
+                    )
+                    .bind(&primary_id)
+                    .fetch_one(&mut *conn)
+                    .await?;
+                    Ok::<_, Box<dyn std::error::Error>>(sanitize_todo(merged))
+                })
+            })
+            .await?;
+
+        Ok(merged)
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn toggle_todo_completion(&self, id: &str) -> Result<Todo, Box<dyn std::error::Error>> {
+        let now = Utc::now();
+        sqlx::query("UPDATE todos SET completed = NOT completed, updated_at = ? WHERE id = ?")
+            .bind(now)
+            .bind(id)
+            .execute_with_retry(&self.pool)
+            .await?;
+
+        self.get_todo(id).await
+    }
+
+    // 把一次完成状态切换放入队列，返回队列中的操作数；由 flush_pending 统一写入
+    pub fn enqueue_toggle(&self, entity_type: &str, id: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let entity_type = match entity_type {
+            "todo" => "todo",
+            "subtask" => "subtask",
+            other => return Err(validation_error(format!("不支持排队切换的类型: {}", other))),
+        };
+
+        let mut pending = self.pending_writes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        pending.toggles.push((entity_type, id.to_string()));
+        pending.queued_since.get_or_insert_with(std::time::Instant::now);
+        Ok(pending.toggles.len())
+    }
+
+    // 最早一次排队距今超过合并窗口时返回 true，供后台任务决定是否自动写入
+    pub fn has_stale_pending(&self) -> bool {
+        self.pending_writes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .queued_since
+            .is_some_and(|since| since.elapsed() >= std::time::Duration::from_millis(PENDING_FLUSH_WINDOW_MS))
+    }
+
+    // 同一实体切换偶数次等于没变，只对奇数次的执行一次切换；返回每个涉及实体的最终状态，按首次排队的顺序排列
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn flush_pending(&self) -> Result<FlushResult, Box<dyn std::error::Error>> {
+        let toggles = {
+            let mut pending = self.pending_writes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            pending.queued_since = None;
+            std::mem::take(&mut pending.toggles)
+        };
+        if toggles.is_empty() {
+            return Ok(FlushResult::default());
+        }
+
+        let mut order: Vec<(&'static str, String)> = Vec::new();
+        let mut counts: HashMap<(&'static str, String), usize> = HashMap::new();
+        for toggle in &toggles {
+            let count = counts.entry(toggle.clone()).or_insert(0);
+            if *count == 0 {
+                order.push(toggle.clone());
+            }
+            *count += 1;
+        }
+        let changed: Vec<(&'static str, String)> = order
+            .iter()
+            .filter(|toggle| counts[*toggle] % 2 == 1)
+            .cloned()
+            .collect();
+
+        let result = self
+            .with_transaction(|conn| {
+                let changed = changed.clone();
+                let order = order.clone();
+                Box::pin(async move {
+                    let now = Utc::now();
+                    for (entity_type, id) in &changed {
+                        if *entity_type == "todo" {
+                            sqlx::query("UPDATE todos SET completed = NOT completed, updated_at = ? WHERE id = ?")
+                                .bind(now)
+                                .bind(id)
+                                .execute(&mut *conn)
+                                .await?;
+                        } else {
+                            sqlx::query("UPDATE subtasks SET completed = NOT completed WHERE id = ?")
+                                .bind(id)
+                                .execute(&mut *conn)
+                                .await?;
+                        }
+                    }
+
+                    // 状态改变的子任务可能连带完成或重新打开父待办
+                    let mut synced_todos = Vec::new();
+                    for (_, id) in changed.iter().filter(|(entity_type, _)| *entity_type == "subtask") {
+                        let subtask = sqlx::query_as::<_, Subtask>(
+                            "SELECT id, todo_id, title, completed, created_at FROM subtasks WHERE id = ?"
+                        )
+                        .bind(id)
+                        .fetch_optional(&mut *conn)
+                        .await?;
+                        if let Some(subtask) = subtask {
+                            synced_todos.extend(sync_parent_todo(conn, &subtask, now).await?);
+                        }
+                    }
+
+                    // 已被删除的实体直接跳过
+                    let mut result = FlushResult::default();
+                    for (entity_type, id) in &order {
+                        if *entity_type == "todo" {
+                            let todo = sqlx::query_as::<_, Todo>(
+                                "SELECT id, title, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at FROM todos WHERE id = ?"
+                            )
+                            .bind(id)
+                            .fetch_optional(&mut *conn)
+                            .await?;
+                            result.todos.extend(todo.map(sanitize_todo));
+                        } else {
+                            let subtask = sqlx::query_as::<_, Subtask>(
+                                "SELECT id, todo_id, title, completed, created_at FROM subtasks WHERE id = ?"
+                            )
+                            .bind(id)
+                            .fetch_optional(&mut *conn)
+                            .await?;
+                            result.subtasks.extend(subtask);
+                        }
+                    }
+                    for todo in synced_todos {
+                        result.todos.retain(|existing| existing.id != todo.id);
+                        result.todos.push(todo);
+                    }
+                    Ok::<_, Box<dyn std::error::Error>>(result)
+                })
+            })
+            .await;
+
+        if result.is_err() {
+            // 写入失败时放回队列最前面，保持原有顺序，下次 flush 重试
+            let mut pending = self.pending_writes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let newer = std::mem::replace(&mut pending.toggles, toggles);
+            pending.toggles.extend(newer);
+            pending.queued_since.get_or_insert_with(std::time::Instant::now);
+        }
+        result
+    }
+
+    // 日期按配置的时区划分；当天还没有完成待办不算中断
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_todo_completion_streak(&self, today: &str) -> Result<TodoCompletionStreak, Box<dyn std::error::Error>> {
+        let today = parse_date(today)?;
+        let offset = self
+            .get_utc_offset_minutes()
+            .await?
+            .and_then(|minutes| FixedOffset::east_opt(minutes * 60));
+
+        let timestamps = sqlx::query("SELECT updated_at FROM todos WHERE completed = TRUE")
+            .fetch_all(&self.pool)
+            .await?;
+        let days: BTreeSet<NaiveDate> = timestamps
+            .iter()
+            .map(|row| {
+                let updated_at = row.get::<DateTime<Utc>, _>("updated_at");
+                match offset {
+                    Some(offset) => updated_at.with_timezone(&offset).date_naive(),
+                    None => updated_at.with_timezone(&Local).date_naive(),
+                }
+            })
+            .filter(|day| *day <= today)
+            .collect();
+
+        let mut current_streak = 0;
+        let mut day = if days.contains(&today) { Some(today) } else { today.pred_opt() };
+        while let Some(current) = day.filter(|current| days.contains(current)) {
+            current_streak += 1;
+            day = current.pred_opt();
+        }
+
+        let mut longest_streak = 0;
+        let mut run = 0;
+        let mut previous: Option<NaiveDate> = None;
+        for day in &days {
+            run = if previous.and_then(|previous| previous.succ_opt()) == Some(*day) { run + 1 } else { 1 };
+            longest_streak = longest_streak.max(run);
+            previous = Some(*day);
+        }
+
+        Ok(TodoCompletionStreak { current_streak, longest_streak })
+    }
+
+    // 归档只是从待办列表和搜索中隐藏，子任务和关联都会保留。
+    // 不改 updated_at：已完成待办的 updated_at 就是完成时间，连续完成天数和报表都依赖它
+    async fn set_todo_archived(&self, id: &str, is_archived: bool) -> Result<Todo, Box<dyn std::error::Error>> {
+        sqlx::query("UPDATE todos SET is_archived = ? WHERE id = ?")
+            .bind(is_archived)
+            .bind(id)
+            .execute_with_retry(&self.pool)
+            .await?;
+
+        self.get_todo(id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn archive_todo(&self, id: &str) -> Result<Todo, Box<dyn std::error::Error>> {
+        self.set_todo_archived(id, true).await
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn unarchive_todo(&self, id: &str) -> Result<Todo, Box<dyn std::error::Error>> {
+        self.set_todo_archived(id, false).await
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_archived_todos(&self) -> Result<Vec<Todo>, Box<dyn std::error::Error>> {
+        let todos = sqlx::query_as::<_, Todo>(
+            "SELECT id, title, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at FROM todos WHERE is_archived = TRUE ORDER BY updated_at DESC"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(todos.into_iter().map(sanitize_todo).collect())
+    }
+
+    // 已归档的待办不计入
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_todo_categories(&self) -> Result<Vec<CategoryCount>, Box<dyn std::error::Error>> {
+        let categories = sqlx::query_as::<_, CategoryCount>(
+            "SELECT category, COUNT(*) AS count FROM todos WHERE is_archived = FALSE GROUP BY category ORDER BY count DESC, category"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(categories)
+    }
+
+    // 子任务相关方法
+    #[tracing::instrument(skip_all, fields(todo_id = %request.todo_id), err(Display))]
+    pub async fn create_subtask(&self, mut request: CreateSubtaskRequest) -> Result<Subtask, Box<dyn std::error::Error>> {
+        request.title = sanitize_title(&request.title, "子任务标题")?;
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        self.with_transaction(|conn| {
+            let id = id.clone();
+            let request = request.clone();
+            Box::pin(async move {
+                // 先确认待办存在，避免产生孤立的子任务
+                let todo = sqlx::query("SELECT id FROM todos WHERE id = ?")
+                    .bind(&request.todo_id)
+                    .fetch_optional(&mut *conn)
+                    .await?;
+                if todo.is_none() {
+                    return Err(not_found_error("待办", &request.todo_id));
+                }
+
+                sqlx::query(
+                    "INSERT INTO subtasks (id, todo_id, title, completed, created_at) VALUES (?, ?, ?, ?, ?)"
+                )
+                .bind(&id)
+                .bind(&request.todo_id)
+                .bind(&request.title)
+                .bind(false)
+                .bind(now)
+                .execute(&mut *conn)
+                .await
+                .map_err(map_constraint_error)?;
+
+                let subtask = sqlx::query_as::<_, Subtask>(
+                    "SELECT id, todo_id, title, completed, created_at FROM subtasks WHERE id = ?"
+                )
+                .bind(&id)
+                .fetch_one(&mut *conn)
+                .await?;
+
+                Ok::<_, Box<dyn std::error::Error>>(subtask)
+            })
+        })
+        .await
+    }
+
+    #[tracing::instrument(skip_all, fields(todo_id = %todo_id), err(Display))]
+    pub async fn get_subtasks_by_todo(&self, todo_id: &str) -> Result<Vec<Subtask>, Box<dyn std::error::Error>> {
+        let subtasks = sqlx::query_as::<_, Subtask>(
+            "SELECT id, todo_id, title, completed, created_at FROM subtasks WHERE todo_id = ? ORDER BY created_at"
+        )
+        .bind(todo_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(subtasks)
+    }
+
+    // 所有待办的子任务平铺成一个清单，待办顺序与待办列表一致，同一待办内按创建时间排序
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_all_subtasks(&self, completed: Option<bool>) -> Result<Vec<SubtaskWithParent>, Box<dyn std::error::Error>> {
+        let subtasks = sqlx::query_as::<_, SubtaskWithParent>(
+            r#"
+            SELECT s.id, s.todo_id, s.title, s.completed, s.created_at, t.title AS todo_title
+            FROM subtasks s
+            JOIN todos t ON t.id = s.todo_id
+            WHERE (? IS NULL OR s.completed = ?)
+            ORDER BY t.created_at DESC, t.id, s.created_at
+            "#,
+        )
+        .bind(completed)
+        .bind(completed)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(subtasks)
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn toggle_subtask_completion(&self, id: &str) -> Result<SubtaskToggleResult, Box<dyn std::error::Error>> {
+        let id = id.to_string();
+        self.with_transaction(|conn| {
+            let id = id.clone();
+            Box::pin(async move {
+                sqlx::query("UPDATE subtasks SET completed = NOT completed WHERE id = ?")
+                    .bind(&id)
+                    .execute(&mut *conn)
+                    .await?;
+
+                let subtask = sqlx::query_as::<_, Subtask>(
+                    "SELECT id, todo_id, title, completed, created_at FROM subtasks WHERE id = ?"
+                )
+                .bind(&id)
+                .fetch_one(&mut *conn)
+                .await?;
+                let todo = sync_parent_todo(conn, &subtask, Utc::now()).await?;
+
+                Ok::<_, Box<dyn std::error::Error>>(SubtaskToggleResult { subtask, todo })
+            })
+        })
+        .await
+    }
+
+    // 子任务按创建时间排序，没有单独的位置字段，移动后保持原有的创建时间
+    #[tracing::instrument(skip_all, fields(subtask_id = %subtask_id, new_todo_id = %new_todo_id), err(Display))]
+    pub async fn move_subtask(&self, subtask_id: &str, new_todo_id: &str) -> Result<Subtask, Box<dyn std::error::Error>> {
+        let subtask_id = subtask_id.to_string();
+        let new_todo_id = new_todo_id.to_string();
+
+        self.with_transaction(|conn| {
+            let subtask_id = subtask_id.clone();
+            let new_todo_id = new_todo_id.clone();
+            Box::pin(async move {
+                let todo = sqlx::query("SELECT id FROM todos WHERE id = ?")
+                    .bind(&new_todo_id)
+                    .fetch_optional(&mut *conn)
+                    .await?;
+                if todo.is_none() {
+                    return Err(not_found_error("待办", &new_todo_id));
+                }
+
+                let result = sqlx::query("UPDATE subtasks SET todo_id = ? WHERE id = ?")
+                    .bind(&new_todo_id)
+                    .bind(&subtask_id)
+                    .execute(&mut *conn)
+                    .await?;
+                if result.rows_affected() == 0 {
+                    return Err(not_found_error("子任务", &subtask_id));
+                }
+
+                let subtask = sqlx::query_as::<_, Subtask>(
+                    "SELECT id, todo_id, title, completed, created_at FROM subtasks WHERE id = ?"
+                )
+                .bind(&subtask_id)
+                .fetch_one(&mut *conn)
+                .await?;
+
+                Ok::<_, Box<dyn std::error::Error>>(subtask)
+            })
+        })
+        .await
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn delete_subtask(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        sqlx::query("DELETE FROM subtasks WHERE id = ?")
+            .bind(id)
+            .execute_with_retry(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // 清除待办下所有已完成的子任务，返回删除的数量；子任务按创建时间排序，无需重排
+    #[tracing::instrument(skip_all, fields(todo_id = %todo_id), err(Display))]
+    pub async fn delete_completed_subtasks(&self, todo_id: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        let result = sqlx::query("DELETE FROM subtasks WHERE todo_id = ? AND completed = TRUE")
+            .bind(todo_id)
+            .execute_with_retry(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 番茄钟会话相关方法
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn create_pomodoro_session(&self, request: CreatePomodoroSessionRequest) -> Result<PomodoroSession, Box<dyn std::error::Error>> {
+        validate_session_type(&request.session_type)?;
+        if request.duration <= 0 {
+            return Err(validation_error(format!("会话时长必须大于 0 秒，当前为 {}", request.duration)));
+        }
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO pomodoro_sessions (
+                id, session_type, duration, completed, task_title, notes, date, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&id)
+        .bind(&request.session_type)
+        .bind(request.duration)
+        .bind(false)
+        .bind(&request.task_title)
+        .bind(&request.notes)
+        .bind(&request.date)
+        .bind(now)
+        .execute_with_retry(&self.pool)
+        .await
+        .map_err(map_constraint_error)?;
+
+        let session = sqlx::query_as::<_, PomodoroSession>(
+            "SELECT id, session_type, duration, completed, task_title, notes, date, started_at, ended_at, interrupted, interruption_reason, created_at FROM pomodoro_sessions WHERE id = ?"
+        )
+        .bind(&id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(session)
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn get_pomodoro_session(&self, id: &str) -> Result<PomodoroSession, Box<dyn std::error::Error>> {
+        let session = sqlx::query_as::<_, PomodoroSession>(
+            "SELECT id, session_type, duration, completed, task_title, notes, date, started_at, ended_at, interrupted, interruption_reason, created_at FROM pomodoro_sessions WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| not_found_error("番茄钟会话", id))?;
+
+        Ok(session)
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %request.id), err(Display))]
+    pub async fn update_pomodoro_session(&self, request: UpdatePomodoroSessionRequest) -> Result<PomodoroSession, Box<dyn std::error::Error>> {
+        if request.completed && request.interrupted {
+            return Err(validation_error("番茄钟会话不能同时为已完成和已中断"));
+        }
+        if let Some(session_type) = &request.session_type {
+            validate_session_type(session_type)?;
+        }
+        if let Some(duration) = request.duration {
+            if duration <= 0 {
+                return Err(validation_error(format!("会话时长必须大于 0 秒，当前为 {}", duration)));
+            }
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE pomodoro_sessions SET 
+                completed = ?, task_title = ?, notes = ?, ended_at = ?,
+                interrupted = ?, interruption_reason = ?,
+                session_type = COALESCE(?, session_type), duration = COALESCE(?, duration)
+            WHERE id = ?
+            "#,
+        )
+        .bind(request.completed)
+        .bind(&request.task_title)
+        .bind(&request.notes)
+        .bind(&request.ended_at)
+        .bind(request.interrupted)
+        .bind(&request.interruption_reason)
+        .bind(&request.session_type)
+        .bind(request.duration)
+        .bind(&request.id)
+        .execute_with_retry(&self.pool)
+        .await?;
+
+        let session = sqlx::query_as::<_, PomodoroSession>(
+            "SELECT id, session_type, duration, completed, task_title, notes, date, started_at, ended_at, interrupted, interruption_reason, created_at FROM pomodoro_sessions WHERE id = ?"
+        )
+        .bind(&request.id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(session)
+    }
+
+    // 删除单个会话，返回是否确实删除了一行
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn delete_pomodoro_session(&self, id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let result = sqlx::query("DELETE FROM pomodoro_sessions WHERE id = ?")
+            .bind(id)
+            .execute_with_retry(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 删除 date 早于 older_than 的会话，返回删除的数量
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn purge_pomodoro_sessions(&self, older_than: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        parse_date(older_than)?;
+
+        let result = sqlx::query("DELETE FROM pomodoro_sessions WHERE date < ?")
+            .bind(older_than)
+            .execute_with_retry(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 所有规则在同一事务中按配置顺序执行，任一规则失败则全部回滚
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn apply_retention_now(&self) -> Result<RetentionReport, Box<dyn std::error::Error>> {
+        let policy = self.get_retention_policy().await?;
+        if policy.rules.is_empty() {
+            return Ok(RetentionReport::default());
+        }
+
+        let now = Utc::now();
+        let today = Local::now().date_naive();
+        let results = self
+            .with_transaction(|conn| {
+                let rules = policy.rules.clone();
+                Box::pin(async move {
+                    let mut results = Vec::with_capacity(rules.len());
+                    for rule in rules {
+                        let cutoff = now - Duration::days(rule.older_than_days as i64);
+                        let affected = match (rule.entity, rule.action) {
+                            (RetentionEntity::Todo, RetentionAction::Purge) => {
+                                let affected = sqlx::query("DELETE FROM todos WHERE completed = TRUE AND updated_at < ?")
+                                    .bind(cutoff)
+                                    .execute(&mut *conn)
+                                    .await?
+                                    .rows_affected();
+                                sqlx::query(
+                                    "DELETE FROM entity_links WHERE (source_type = 'todo' AND source_id NOT IN (SELECT id FROM todos)) OR (target_type = 'todo' AND target_id NOT IN (SELECT id FROM todos))"
+                                )
+                                .execute(&mut *conn)
+                                .await?;
+                                affected
+                            }
+                            // 与手动归档一样保留 updated_at（完成时间）
+                            (RetentionEntity::Todo, RetentionAction::Archive) => {
+                                sqlx::query("UPDATE todos SET is_archived = TRUE WHERE completed = TRUE AND is_archived = FALSE AND updated_at < ?")
+                                    .bind(cutoff)
+                                    .execute(&mut *conn)
+                                    .await?
+                                    .rows_affected()
+                            }
+                            // 置顶的便笺视为仍在使用，不自动归档
+                            (RetentionEntity::Note, RetentionAction::Archive) => {
+                                sqlx::query("UPDATE notes SET is_archived = TRUE, updated_at = ? WHERE is_archived = FALSE AND is_pinned = FALSE AND updated_at < ?")
+                                    .bind(now)
+                                    .bind(cutoff)
+                                    .execute(&mut *conn)
+                                    .await?
+                                    .rows_affected()
+                            }
+                            (RetentionEntity::Note, RetentionAction::Purge) => {
+                                let affected = sqlx::query("DELETE FROM notes WHERE is_archived = TRUE AND updated_at < ?")
+                                    .bind(cutoff)
+                                    .execute(&mut *conn)
+                                    .await?
+                                    .rows_affected();
+                                sqlx::query(
+                                    "DELETE FROM entity_links WHERE (source_type = 'note' AND source_id NOT IN (SELECT id FROM notes)) OR (target_type = 'note' AND target_id NOT IN (SELECT id FROM notes))"
+                                )
+                                .execute(&mut *conn)
+                                .await?;
+                                affected
+                            }
+                            (RetentionEntity::PomodoroSession, RetentionAction::Purge) => {
+                                let cutoff_date = today - Duration::days(rule.older_than_days as i64);
+                                sqlx::query("DELETE FROM pomodoro_sessions WHERE date < ?")
+                                    .bind(cutoff_date.format("%Y-%m-%d").to_string())
+                                    .execute(&mut *conn)
+                                    .await?
+                                    .rows_affected()
+                            }
+                            // parse_retention_policy 已拒绝其余组合，这里只防御手工改过的数据库
+                            (entity, action) => {
+                                return Err(validation_error(format!("不支持的保留规则: {:?} {:?}", entity, action)));
+                            }
+                        };
+                        results.push(RetentionActionResult {
+                            entity: rule.entity,
+                            action: rule.action,
+                            older_than_days: rule.older_than_days,
+                            affected,
+                        });
+                    }
+                    Ok::<_, Box<dyn std::error::Error>>(results)
+                })
+            })
+            .await?;
+
+        Ok(RetentionReport { results })
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_pomodoro_sessions_by_date(&self, date: &str) -> Result<Vec<PomodoroSession>, Box<dyn std::error::Error>> {
+        let sessions = sqlx::query_as::<_, PomodoroSession>(
+            "SELECT id, session_type, duration, completed, task_title, notes, date, started_at, ended_at, interrupted, interruption_reason, created_at FROM pomodoro_sessions WHERE date = ? ORDER BY created_at"
+        )
+        .bind(date)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_pomodoro_sessions_by_date_range(&self, start_date: &str, end_date: &str) -> Result<Vec<PomodoroSession>, Box<dyn std::error::Error>> {
+        if is_reversed_range(start_date, end_date) {
+            return Ok(Vec::new());
+        }
+
+        let sessions = sqlx::query_as::<_, PomodoroSession>(
+            "SELECT id, session_type, duration, completed, task_title, notes, date, started_at, ended_at, interrupted, interruption_reason, created_at FROM pomodoro_sessions WHERE date >= ? AND date <= ? ORDER BY date, created_at"
+        )
+        .bind(start_date)
+        .bind(end_date)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_pomodoro_stats(&self, start_date: &str, end_date: &str) -> Result<PomodoroStats, Box<dyn std::error::Error>> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                COUNT(*) AS total_sessions,
+                COALESCE(SUM(CASE WHEN completed THEN 1 ELSE 0 END), 0) AS completed_sessions,
+                COALESCE(SUM(CASE WHEN interrupted THEN 1 ELSE 0 END), 0) AS interrupted_sessions,
+                COALESCE(SUM(CASE WHEN NOT completed AND NOT interrupted THEN 1 ELSE 0 END), 0) AS in_progress_sessions,
+                COALESCE(SUM(CASE WHEN completed AND session_type = 'work' THEN duration ELSE 0 END), 0) AS focus_seconds
+            FROM pomodoro_sessions
+            WHERE date >= ? AND date <= ?
+            "#,
+        )
+        .bind(start_date)
+        .bind(end_date)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(PomodoroStats {
+            start_date: start_date.to_string(),
+            end_date: end_date.to_string(),
+            total_sessions: row.get("total_sessions"),
+            completed_sessions: row.get("completed_sessions"),
+            interrupted_sessions: row.get("interrupted_sessions"),
+            in_progress_sessions: row.get("in_progress_sessions"),
+            focus_seconds: row.get("focus_seconds"),
+        })
+    }
+
+    // 每日专注分钟数及向前 window_days 天的滑动平均；范围开头几天的窗口会取到 start_date 之前的数据
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_focus_trend(&self, start_date: &str, end_date: &str, window_days: u32) -> Result<Vec<FocusTrendPoint>, Box<dyn std::error::Error>> {
+        let start = parse_date(start_date)?;
+        let end = parse_date(end_date)?;
+        if end < start {
+            return Err(validation_error("结束日期不能早于开始日期"));
+        }
+        if window_days == 0 {
+            return Err(validation_error("滑动窗口天数必须大于 0"));
+        }
+        let window_start = start - Duration::days(window_days as i64 - 1);
+
+        let seconds_by_date: HashMap<String, i64> = sqlx::query(
+            r#"
+            SELECT date, COALESCE(SUM(duration), 0) AS focus_seconds
+            FROM pomodoro_sessions
+            WHERE completed = TRUE AND session_type = 'work' AND date >= ? AND date <= ?
+            GROUP BY date
+            "#,
+        )
+        .bind(window_start.format("%Y-%m-%d").to_string())
+        .bind(end_date)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.get::<String, _>("date"), row.get::<i64, _>("focus_seconds")))
+        .collect();
+
+        // 没有番茄的日期按 0 计入平均
+        let daily_minutes: Vec<(NaiveDate, i64)> = window_start
+            .iter_days()
+            .take_while(|day| *day <= end)
+            .map(|day| {
+                let seconds = seconds_by_date.get(&day.format("%Y-%m-%d").to_string()).copied().unwrap_or(0);
+                (day, seconds / 60)
+            })
+            .collect();
+
+        let window = window_days as usize;
+        let mut window_sum = 0;
+        let mut trend = Vec::new();
+        for (index, (day, minutes)) in daily_minutes.iter().enumerate() {
+            window_sum += minutes;
+            if index >= window {
+                window_sum -= daily_minutes[index - window].1;
+            }
+            if *day >= start {
+                trend.push(FocusTrendPoint {
+                    date: day.format("%Y-%m-%d").to_string(),
+                    focus_minutes: *minutes,
+                    moving_average: window_sum as f64 / window as f64,
+                });
+            }
+        }
+
+        Ok(trend)
+    }
+
+    // 只统计已完成的工作番茄
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_daily_pomodoro_progress(&self, date: &str) -> Result<DailyPomodoroProgress, Box<dyn std::error::Error>> {
+        let completed_sessions = sqlx::query(
+            "SELECT COUNT(*) AS count FROM pomodoro_sessions WHERE date = ? AND session_type = 'work' AND completed = TRUE"
+        )
+        .bind(date)
+        .fetch_one(&self.pool)
+        .await?
+        .get::<i64, _>("count");
+
+        let goal = self.get_pomodoro_settings().await?.daily_session_goal;
+
+        Ok(DailyPomodoroProgress {
+            date: date.to_string(),
+            completed_sessions,
+            goal,
+            achieved: completed_sessions >= goal as i64,
+        })
+    }
+
+    // 根据当天最后一个结束的会话推荐下一个会话：工作之后休息，
+    // 每完成 long_break_interval 个工作番茄后长休息；休息之后或当天还没有会话时开始工作。
+    // 中断的会话不计入工作番茄数，中断之后总是重新开始工作，不会因此提前触发长休息
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_next_pomodoro_session(&self, date: &str) -> Result<NextPomodoroSession, Box<dyn std::error::Error>> {
+        let settings = self.get_pomodoro_settings().await?;
+
+        let last_session_type = sqlx::query(
+            "SELECT session_type, interrupted FROM pomodoro_sessions WHERE date = ? AND (completed = TRUE OR interrupted = TRUE) ORDER BY COALESCE(ended_at, created_at) DESC LIMIT 1"
+        )
+        .bind(date)
+        .fetch_optional(&self.pool)
+        .await?
+        .filter(|row| !row.get::<bool, _>("interrupted"))
+        .map(|row| row.get::<String, _>("session_type"));
+
+        let completed_work_sessions = self.count_completed_work_sessions(date).await?;
+
+        let (session_type, duration, auto_start) = match last_session_type.as_deref() {
+            Some("work") if should_take_long_break(completed_work_sessions, settings.long_break_interval) => {
+                ("long_break", settings.long_break, settings.auto_start_breaks)
+            }
+            Some("work") => ("short_break", settings.short_break, settings.auto_start_breaks),
+            _ => ("work", settings.work_time, settings.auto_start_work),
+        };
+
+        Ok(NextPomodoroSession {
+            session_type: session_type.to_string(),
+            duration,
+            auto_start,
+            completed_work_sessions,
+        })
+    }
+
+    async fn count_completed_work_sessions(&self, date: &str) -> Result<i64, Box<dyn std::error::Error>> {
+        let count = sqlx::query(
+            "SELECT COUNT(*) AS count FROM pomodoro_sessions WHERE date = ? AND session_type = 'work' AND completed = TRUE"
+        )
+        .bind(date)
+        .fetch_one(&self.pool)
+        .await?
+        .get::<i64, _>("count");
+
+        Ok(count)
+    }
+
+    // 当天下一次休息是否应为长休息，规则与 get_next_pomodoro_session 一致
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn should_take_long_break(&self, date: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        parse_date(date)?;
+        let settings = self.get_pomodoro_settings().await?;
+        let completed_work_sessions = self.count_completed_work_sessions(date).await?;
+
+        Ok(should_take_long_break(completed_work_sessions, settings.long_break_interval))
+    }
+
+    // 番茄钟设置相关方法
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_pomodoro_settings(&self) -> Result<PomodoroSettings, Box<dyn std::error::Error>> {
+        let settings = sqlx::query_as::<_, PomodoroSettings>(
+            "SELECT id, work_time, short_break, long_break, long_break_interval, auto_start_breaks, auto_start_work, notification_enabled, daily_session_goal, created_at, updated_at FROM pomodoro_settings ORDER BY created_at, id LIMIT 1"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(settings)
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn update_pomodoro_settings(&self, request: UpdatePomodoroSettingsRequest) -> Result<PomodoroSettings, Box<dyn std::error::Error>> {
+        for (name, seconds) in [
+            ("工作时长", request.work_time),
+            ("短休息时长", request.short_break),
+            ("长休息时长", request.long_break),
+        ] {
+            if seconds <= 0 {
+                return Err(validation_error(format!("{}必须大于 0 秒，当前为 {}", name, seconds)));
+            }
+        }
+        if request.long_break_interval < 1 {
+            return Err(validation_error(format!("长休息间隔至少为 1，当前为 {}", request.long_break_interval)));
+        }
+        if request.daily_session_goal.is_some_and(|goal| goal < 1) {
+            return Err(validation_error("每日目标番茄数必须大于 0"));
+        }
+        let now = Utc::now();
+        let current = self.get_pomodoro_settings().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE pomodoro_settings SET 
+                work_time = ?, short_break = ?, long_break = ?, long_break_interval = ?,
+                auto_start_breaks = ?, auto_start_work = ?, notification_enabled = ?,
+                daily_session_goal = COALESCE(?, daily_session_goal), updated_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(request.work_time)
+        .bind(request.short_break)
+        .bind(request.long_break)
+        .bind(request.long_break_interval)
+        .bind(request.auto_start_breaks)
+        .bind(request.auto_start_work)
+        .bind(request.notification_enabled)
+        .bind(request.daily_session_goal)
+        .bind(now)
+        .bind(&current.id)
+        .execute_with_retry(&self.pool)
+        .await?;
+
+        self.get_pomodoro_settings().await
+    }
+
+    // 便笺相关方法
+    pub fn get_default_note_colors(&self) -> Vec<NoteColor> {
+        DEFAULT_NOTE_COLORS
+            .iter()
+            .map(|(label, value)| NoteColor {
+                label: label.to_string(),
+                value: value.to_string(),
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn create_note(&self, mut request: CreateNoteRequest) -> Result<Note, Box<dyn std::error::Error>> {
+        request.title = sanitize_title(&request.title, "便笺标题")?;
+        let color = normalize_hex_color(&request.color)?;
+        if request.category.trim().is_empty() {
+            request.category = self.get_default_category("note").await?;
+        }
+        // 同名便笺只提示不阻止，重名有时是有意为之
+        let duplicates = self.find_notes_by_title(&request.title).await?;
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let tags_json = if let Some(tags) = &request.tags {
+            Some(serde_json::to_string(tags)?)
+        } else {
+            None
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO notes (
+                id, title, title_normalized, content, tags, category, color, is_pinned, is_archived, created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&id)
+        .bind(&request.title)
+        .bind(search::normalize(&request.title))
+        .bind(&request.content)
+        .bind(&tags_json)
+        .bind(&request.category)
+        .bind(&color)
+        .bind(false)
+        .bind(false)
+        .bind(now)
+        .bind(now)
+        .execute_with_retry(&self.pool)
+        .await
+        .map_err(map_constraint_error)?;
+
+        let mut note = self.get_note(&id).await?;
+        if !duplicates.is_empty() {
+            note.warnings.push(format!("已存在 {} 个同名便笺", duplicates.len()));
+        }
+        Ok(note)
+    }
+
+    // 标题相同（忽略首尾空白、大小写和变音符号）的未归档便笺
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn find_notes_by_title(&self, title: &str) -> Result<Vec<Note>, Box<dyn std::error::Error>> {
+        let notes = sqlx::query_as::<_, Note>(
+            "SELECT id, title, content, tags, category, color, is_pinned, is_archived, pin_order, created_at, updated_at FROM notes WHERE is_archived = FALSE AND TRIM(title_normalized) = ? ORDER BY updated_at DESC"
+        )
+        .bind(search::normalize(title.trim()))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(notes.into_iter().map(sanitize_note).collect())
+    }
+
+    // 与 search_todos 相同的模糊搜索，忽略大小写和变音符号，只搜索未归档的便笺
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn search_notes(&self, query: &str) -> Result<Vec<Note>, Box<dyn std::error::Error>> {
+        let tokens = search::tokenize(query);
+        let grams = search::bigrams(&tokens);
+        if grams.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            "SELECT id, title, content, tags, category, color, is_pinned, is_archived, pin_order, created_at, updated_at FROM notes WHERE is_archived = FALSE AND ("
+        );
+        for (i, gram) in grams.iter().enumerate() {
+            if i > 0 {
+                builder.push(" OR ");
+            }
+            let pattern = search::like_contains(gram);
+            builder
+                .push("title_normalized LIKE ")
+                .push_bind(pattern.clone())
+                .push(" ESCAPE '\\' OR content LIKE ")
+                .push_bind(pattern)
+                .push(" ESCAPE '\\'");
+        }
+        builder.push(") LIMIT 500");
+
+        let candidates = builder.build_query_as::<Note>().fetch_all(&self.pool).await?;
+
+        // 标题命中的权重高于正文，每个词都必须命中
+        let mut scored: Vec<(f64, Note)> = candidates
+            .into_iter()
+            .map(sanitize_note)
+            .filter_map(|note| {
+                let mut total = 0.0;
+                for token in &tokens {
+                    let title_score = search::token_score(token, &note.title) * 2.0;
+                    let content_score = search::token_score(token, &note.content);
+                    let best = title_score.max(content_score);
+                    if best == 0.0 {
+                        return None;
+                    }
+                    total += best;
+                }
+                Some((total, note))
+            })
+            .collect();
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Ok(scored.into_iter().map(|(_, note)| note).collect())
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn get_note(&self, id: &str) -> Result<Note, Box<dyn std::error::Error>> {
+        let note = sqlx::query_as::<_, Note>(
+            "SELECT id, title, content, tags, category, color, is_pinned, is_archived, pin_order, created_at, updated_at FROM notes WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(sanitize_note(note))
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_all_notes(&self) -> Result<Vec<Note>, Box<dyn std::error::Error>> {
+        let notes = sqlx::query_as::<_, Note>(
+            "SELECT id, title, content, tags, category, color, is_pinned, is_archived, pin_order, created_at, updated_at FROM notes WHERE is_archived = FALSE ORDER BY is_pinned DESC, pin_order ASC, updated_at DESC"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(notes.into_iter().map(sanitize_note).collect())
+    }
+
+    // tags 为空或不是合法 JSON 的便笺不会匹配
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_notes_by_tag(&self, tag: &str) -> Result<Vec<Note>, Box<dyn std::error::Error>> {
+        let notes = sqlx::query_as::<_, Note>(
+            r#"
+            SELECT id, title, content, tags, category, color, is_pinned, is_archived, pin_order, created_at, updated_at
+            FROM notes n
+            WHERE n.is_archived = FALSE
+                AND CASE WHEN json_valid(n.tags)
+                    THEN EXISTS (SELECT 1 FROM json_each(n.tags) t WHERE t.value = ?)
+                    ELSE FALSE END
+            ORDER BY is_pinned DESC, pin_order ASC, updated_at DESC
+            "#
+        )
+        .bind(tag)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(notes.into_iter().map(sanitize_note).collect())
+    }
+
+    // 按配置的时区把 created_at 换算为本地日期后分桶，范围内没有便笺的桶计为 0；include_archived 为 false 时不统计已归档的便笺
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_notes_created_histogram(&self, start_date: &str, end_date: &str, bucket: Bucket, include_archived: bool) -> Result<Vec<HistogramPoint>, Box<dyn std::error::Error>> {
+        let start = parse_date(start_date)?;
+        let end = parse_date(end_date)?;
+        if end < start {
+            return Err(validation_error("结束日期不能早于开始日期"));
+        }
+        let offset = self
+            .get_utc_offset_minutes()
+            .await?
+            .and_then(|minutes| FixedOffset::east_opt(minutes * 60));
+        let week_start = self.get_week_start_weekday().await?;
+
+        let bucket_of = |day: NaiveDate| match bucket {
+            Bucket::Day => day,
+            Bucket::Week => week_start_of(day, week_start),
+            Bucket::Month => day.with_day(1).unwrap_or(day),
+        };
+
+        // 本地日期与 UTC 日期最多相差一天，先按前后各放宽一天粗筛
+        let timestamps = sqlx::query("SELECT created_at FROM notes WHERE created_at >= ? AND created_at < ? AND (? OR is_archived = FALSE)")
+            .bind((start - Duration::days(1)).format("%Y-%m-%d").to_string())
+            .bind((end + Duration::days(2)).format("%Y-%m-%d").to_string())
+            .bind(include_archived)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut counts: HashMap<NaiveDate, i64> = HashMap::new();
+        for row in &timestamps {
+            let created_at = row.get::<DateTime<Utc>, _>("created_at");
+            let day = match offset {
+                Some(offset) => created_at.with_timezone(&offset).date_naive(),
+                None => created_at.with_timezone(&Local).date_naive(),
+            };
+            if day >= start && day <= end {
+                *counts.entry(bucket_of(day)).or_insert(0) += 1;
+            }
+        }
+
+        let mut histogram = Vec::new();
+        let mut bucket_start = bucket_of(start);
+        while bucket_start <= end {
+            histogram.push(HistogramPoint {
+                bucket_start: bucket_start.format("%Y-%m-%d").to_string(),
+                count: counts.get(&bucket_start).copied().unwrap_or(0),
+            });
+            bucket_start = match bucket {
+                Bucket::Day => bucket_start + Duration::days(1),
+                Bucket::Week => bucket_start + Duration::days(7),
+                Bucket::Month if bucket_start.month() == 12 => {
+                    NaiveDate::from_ymd_opt(bucket_start.year() + 1, 1, 1).ok_or_else(|| validation_error("日期超出范围"))?
+                }
+                Bucket::Month => {
+                    NaiveDate::from_ymd_opt(bucket_start.year(), bucket_start.month() + 1, 1).ok_or_else(|| validation_error("日期超出范围"))?
+                }
+            };
+        }
+
+        Ok(histogram)
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %request.id), err(Display))]
+    pub async fn update_note(&self, mut request: UpdateNoteRequest) -> Result<Note, Box<dyn std::error::Error>> {
+        request.title = sanitize_title(&request.title, "便笺标题")?;
+        let color = normalize_hex_color(&request.color)?;
+        let previous = self.get_note(&request.id).await?;
+        let now = Utc::now();
+        let tags_json = if let Some(tags) = &request.tags {
+            Some(serde_json::to_string(tags)?)
+        } else {
+            None
+        };
+
+        let result = sqlx::query(&format!(
+            r#"
+            UPDATE notes SET 
+                title = ?, title_normalized = ?, content = ?, tags = ?, category = ?, color = ?, 
+                is_pinned = ?, is_archived = ?, {}, updated_at = ?
+            WHERE id = ? AND (? IS NULL OR updated_at = ?)
+            "#,
+            PIN_ORDER_ASSIGNMENT
+        ))
+        .bind(&request.title)
+        .bind(search::normalize(&request.title))
+        .bind(&request.content)
+        .bind(&tags_json)
+        .bind(&request.category)
+        .bind(&color)
+        .bind(request.is_pinned)
+        .bind(request.is_archived)
+        .bind(request.is_pinned)
+        .bind(now)
+        .bind(&request.id)
+        .bind(request.expected_updated_at)
+        .bind(request.expected_updated_at)
+        .execute_with_retry(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 && request.expected_updated_at.is_some() {
+            let current = self.get_note(&request.id).await?;
+            return Err(conflict_error(&current));
+        }
+        self.record_undo("updated", UndoSnapshot::Note { note: previous, links: Vec::new() });
+
+        self.get_note(&request.id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %request.id), err(Display))]
+    pub async fn patch_note(&self, mut request: PatchNoteRequest) -> Result<Note, Box<dyn std::error::Error>> {
+        request.title = request.title.as_deref().map(|title| sanitize_title(title, "便笺标题")).transpose()?;
+        let color = request.color.as_deref().map(normalize_hex_color).transpose()?;
+        let tags_json = request.tags.as_ref().map(serde_json::to_string).transpose()?;
+        let previous = self.get_note(&request.id).await?;
+
+        let mut builder = QueryBuilder::<Sqlite>::new("UPDATE notes SET updated_at = ");
+        builder.push_bind(Utc::now());
+        if let Some(title) = request.title {
+            builder.push(", title_normalized = ").push_bind(search::normalize(&title));
+            builder.push(", title = ").push_bind(title);
+        }
+        if let Some(content) = request.content {
+            builder.push(", content = ").push_bind(content);
+        }
+        if let Some(tags_json) = tags_json {
+            builder.push(", tags = ").push_bind(tags_json);
+        }
+        if let Some(category) = request.category {
+            builder.push(", category = ").push_bind(category);
+        }
+        if let Some(color) = color {
+            builder.push(", color = ").push_bind(color);
+        }
+        if let Some(is_pinned) = request.is_pinned {
+            builder.push(", is_pinned = ").push_bind(is_pinned);
+            // PIN_ORDER_ASSIGNMENT 中唯一的占位符绑定是否置顶
+            let (before, after) = PIN_ORDER_ASSIGNMENT.split_once('?').expect("PIN_ORDER_ASSIGNMENT 应含有一个占位符");
+            builder.push(", ").push(before).push_bind(is_pinned).push(after);
+        }
+        if let Some(is_archived) = request.is_archived {
+            builder.push(", is_archived = ").push_bind(is_archived);
+        }
+        builder.push(" WHERE id = ").push_bind(request.id.clone());
+        if let Some(expected_updated_at) = request.expected_updated_at {
+            builder.push(" AND updated_at = ").push_bind(expected_updated_at);
+        }
+
+        let result = builder.build().execute_with_retry(&self.pool).await?;
+        if result.rows_affected() == 0 && request.expected_updated_at.is_some() {
+            let current = self.get_note(&request.id).await?;
+            return Err(conflict_error(&current));
+        }
+        self.record_undo("updated", UndoSnapshot::Note { note: previous, links: Vec::new() });
+
+        self.get_note(&request.id).await
+    }
+
+    // 读取和删除在同一事务中完成，返回被删除的便笺；不存在时返回 None
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn delete_note(&self, id: &str) -> Result<Option<Note>, Box<dyn std::error::Error>> {
+        let id = id.to_string();
+        let deleted = self
+            .with_transaction(|conn| {
+                let id = id.clone();
+                Box::pin(async move {
+                    let note = sqlx::query_as::<_, Note>(
+                        "SELECT id, title, content, tags, category, color, is_pinned, is_archived, pin_order, created_at, updated_at FROM notes WHERE id = ?"
+                    )
+                    .bind(&id)
+                    .fetch_optional(&mut *conn)
+                    .await?;
+
+                    let Some(note) = note else {
+                        return Ok::<_, Box<dyn std::error::Error>>(None);
+                    };
+                    let links = load_entity_links(conn, "note", &id).await?;
+                    sqlx::query("DELETE FROM notes WHERE id = ?")
+                        .bind(&id)
+                        .execute(&mut *conn)
+                        .await?;
+                    delete_entity_links(conn, "note", &id).await?;
+
+                    Ok(Some((sanitize_note(note), links)))
+                })
+            })
+            .await?;
+
+        Ok(deleted.map(|(note, links)| {
+            self.record_undo("deleted", UndoSnapshot::Note { note: note.clone(), links });
+            note
+        }))
+    }
+
+    // 每个未归档便笺写成一个带 frontmatter 的 .md 文件，文件名附带 id 前缀避免重名覆盖
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn export_notes_markdown(&self, dir: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let notes = self.get_all_notes().await?;
+        let dir = std::path::Path::new(dir);
+        tokio::fs::create_dir_all(dir).await?;
+
+        let mut paths = Vec::new();
+        for note in &notes {
+            let id_suffix: String = note.id.chars().take(8).collect();
+            let path = dir.join(format!("{}-{}.md", slugify(&note.title), id_suffix));
+            tokio::fs::write(&path, note_to_markdown(note)).await?;
+            paths.push(path.to_string_lossy().into_owned());
+        }
+
+        Ok(paths)
+    }
+
+    // 导入目录下的 .md 文件，单个文件失败只记录原因，不影响其他文件
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn import_notes_markdown(&self, dir: &str) -> Result<NoteImportResult, Box<dyn std::error::Error>> {
+        let mut paths = Vec::new();
+        let mut entries = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("md")) && entry.file_type().await?.is_file() {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut result = NoteImportResult {
+            imported: 0,
+            skipped: 0,
+            errors: Vec::new(),
+        };
+        for path in paths {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            let parsed = match tokio::fs::read_to_string(&path).await {
+                Ok(raw) => parse_note_markdown(&raw),
+                Err(e) => Err(e.to_string()),
+            };
+            let parsed = match parsed {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    result.skipped += 1;
+                    result.errors.push(format!("{}: {}", file_name, e));
+                    continue;
+                }
+            };
+
+            let title = parsed.title.unwrap_or_else(|| {
+                path.file_stem().unwrap_or_default().to_string_lossy().into_owned()
+            });
+            let request = CreateNoteRequest {
+                title,
+                content: parsed.content,
+                tags: (!parsed.tags.is_empty()).then_some(parsed.tags),
+                category: parsed.category.unwrap_or_default(),
+                color: DEFAULT_NOTE_COLORS[0].1.to_string(),
+            };
+            match self.create_note(request).await {
+                Ok(_) => result.imported += 1,
+                Err(e) => {
+                    result.skipped += 1;
+                    result.errors.push(format!("{}: {}", file_name, e));
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn toggle_note_pin(&self, id: &str) -> Result<Note, Box<dyn std::error::Error>> {
+        let now = Utc::now();
+        let note = self.get_note(id).await?;
+
+        sqlx::query(&format!("UPDATE notes SET is_pinned = ?, {}, updated_at = ? WHERE id = ?", PIN_ORDER_ASSIGNMENT))
+            .bind(!note.is_pinned)
+            .bind(!note.is_pinned)
+            .bind(now)
+            .bind(id)
+            .execute_with_retry(&self.pool)
+            .await?;
+
+        self.get_note(id).await
+    }
+
+    // 按给定顺序重写置顶便笺的 pin_order，未置顶的 id 会被忽略
+    #[tracing::instrument(skip_all, fields(count = ordered_ids.len()), err(Display))]
+    pub async fn reorder_pinned_notes(&self, ordered_ids: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let ordered_ids = ordered_ids.to_vec();
+        self.with_transaction(|conn| {
+            let ordered_ids = ordered_ids.clone();
+            Box::pin(async move {
+                for (index, id) in ordered_ids.iter().enumerate() {
+                    sqlx::query("UPDATE notes SET pin_order = ? WHERE id = ? AND is_pinned = TRUE")
+                        .bind(index as i32 + 1)
+                        .bind(id)
+                        .execute(&mut *conn)
+                        .await?;
+                }
+                Ok::<_, Box<dyn std::error::Error>>(())
+            })
+        })
+        .await
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_note_categories(&self, include_archived: bool) -> Result<Vec<CategoryCount>, Box<dyn std::error::Error>> {
+        let categories = sqlx::query_as::<_, CategoryCount>(
+            "SELECT category, COUNT(*) AS count FROM notes WHERE (? OR is_archived = FALSE) GROUP BY category ORDER BY count DESC, category"
+        )
+        .bind(include_archived)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(categories)
+    }
+
+    // 便笺模板相关方法
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_note_templates(&self) -> Result<Vec<NoteTemplate>, Box<dyn std::error::Error>> {
+        let templates = sqlx::query_as::<_, NoteTemplate>(
+            "SELECT id, name, content, tags, category, color, created_at, updated_at FROM note_templates ORDER BY name"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(templates)
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn get_note_template(&self, id: &str) -> Result<NoteTemplate, Box<dyn std::error::Error>> {
+        let template = sqlx::query_as::<_, NoteTemplate>(
+            "SELECT id, name, content, tags, category, color, created_at, updated_at FROM note_templates WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| not_found_error("便笺模板", id))?;
+
+        Ok(template)
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn create_note_template(&self, mut request: CreateNoteTemplateRequest) -> Result<NoteTemplate, Box<dyn std::error::Error>> {
+        request.name = sanitize_title(&request.name, "模板名称")?;
+        let color = normalize_hex_color(&request.color)?;
+        if request.category.trim().is_empty() {
+            request.category = self.get_default_category("note").await?;
+        }
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let tags_json = if let Some(tags) = &request.tags {
+            Some(serde_json::to_string(tags)?)
+        } else {
+            None
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO note_templates (id, name, content, tags, category, color, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&id)
+        .bind(&request.name)
+        .bind(&request.content)
+        .bind(&tags_json)
+        .bind(&request.category)
+        .bind(&color)
+        .bind(now)
+        .bind(now)
+        .execute_with_retry(&self.pool)
+        .await
+        .map_err(map_constraint_error)?;
+
+        self.get_note_template(&id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %request.id), err(Display))]
+    pub async fn update_note_template(&self, mut request: UpdateNoteTemplateRequest) -> Result<NoteTemplate, Box<dyn std::error::Error>> {
+        request.name = sanitize_title(&request.name, "模板名称")?;
+        let color = normalize_hex_color(&request.color)?;
+        if request.category.trim().is_empty() {
+            request.category = self.get_default_category("note").await?;
+        }
+        let tags_json = if let Some(tags) = &request.tags {
+            Some(serde_json::to_string(tags)?)
+        } else {
+            None
+        };
+
+        let result = sqlx::query(
+            r#"
+            UPDATE note_templates SET
+                name = ?, content = ?, tags = ?, category = ?, color = ?, updated_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(&request.name)
+        .bind(&request.content)
+        .bind(&tags_json)
+        .bind(&request.category)
+        .bind(&color)
+        .bind(Utc::now())
+        .bind(&request.id)
+        .execute_with_retry(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(not_found_error("便笺模板", &request.id));
+        }
+        self.get_note_template(&request.id).await
+    }
+
+    // 返回被删除的模板，不存在时返回 None；已由模板创建的便笺不受影响
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn delete_note_template(&self, id: &str) -> Result<Option<NoteTemplate>, Box<dyn std::error::Error>> {
+        let template = sqlx::query_as::<_, NoteTemplate>(
+            "SELECT id, name, content, tags, category, color, created_at, updated_at FROM note_templates WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        sqlx::query("DELETE FROM note_templates WHERE id = ?")
+            .bind(id)
+            .execute_with_retry(&self.pool)
+            .await?;
+
+        Ok(template)
+    }
+
+    // 用模板的内容、标签、分类和颜色创建新便笺，标题和内容中的占位符会被替换
+    #[tracing::instrument(skip_all, fields(template_id = %template_id), err(Display))]
+    pub async fn create_note_from_template(&self, template_id: &str, overrides: NoteTemplateOverrides) -> Result<Note, Box<dyn std::error::Error>> {
+        let template = self.get_note_template(template_id).await?;
+        let date = match overrides.date {
+            Some(date) => {
+                parse_date(&date)?;
+                date
+            }
+            None => Local::now().date_naive().format("%Y-%m-%d").to_string(),
+        };
+        let title = render_note_template(overrides.title.as_deref().unwrap_or(&template.name), "", &date);
+        let content = render_note_template(&template.content, &title, &date);
+        let tags = template
+            .tags
+            .as_deref()
+            .and_then(|tags| serde_json::from_str::<Vec<String>>(tags).ok());
+
+        self.create_note(CreateNoteRequest {
+            title,
+            content,
+            tags,
+            category: template.category,
+            color: template.color,
+        })
+        .await
+    }
+
+    // 每日日志：标题为日期且带有 journal 标签的便笺，不存在时新建；
+    // 配置了日志模板时套用模板的内容、分类、颜色和标签，标题始终为日期以便下次查找
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_or_create_daily_note(&self, date: &str) -> Result<Note, Box<dyn std::error::Error>> {
+        parse_date(date)?;
+        let existing_note = sqlx::query_as::<_, Note>(
+            r#"
+            SELECT n.id, n.title, n.content, n.tags, n.category, n.color, n.is_pinned, n.is_archived, n.pin_order, n.created_at, n.updated_at
+            FROM notes n
+            WHERE TRIM(n.title) = ?
+                AND CASE WHEN json_valid(n.tags)
+                    THEN EXISTS (SELECT 1 FROM json_each(n.tags) t WHERE t.value = ?)
+                    ELSE 0 END
+            ORDER BY n.created_at
+            LIMIT 1
+            "#,
+        )
+        .bind(date)
+        .bind(JOURNAL_TAG)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(note) = existing_note {
+            return Ok(sanitize_note(note));
+        }
+
+        // 模板已被删除时按没有模板处理
+        let template = match self.get_journal_template_id().await? {
+            Some(template_id) => self.get_note_template(&template_id).await.ok(),
+            None => None,
+        };
+        let mut tags: Vec<String> = template
+            .as_ref()
+            .and_then(|template| template.tags.as_deref())
+            .and_then(|tags| serde_json::from_str(tags).ok())
+            .unwrap_or_default();
+        if !tags.iter().any(|tag| tag == JOURNAL_TAG) {
+            tags.push(JOURNAL_TAG.to_string());
+        }
+
+        let request = match template {
+            Some(template) => CreateNoteRequest {
+                title: date.to_string(),
+                content: render_note_template(&template.content, date, date),
+                tags: Some(tags),
+                category: template.category,
+                color: template.color,
+            },
+            None => CreateNoteRequest {
+                title: date.to_string(),
+                content: String::new(),
+                tags: Some(tags),
+                category: String::new(), // 留空，由 create_note 套用默认分类
+                color: DEFAULT_NOTE_COLORS[0].1.to_string(),
+            },
+        };
+        self.create_note(request).await
+    }
+
+    // 习惯相关方法
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn create_habit(&self, mut request: CreateHabitRequest) -> Result<Habit, Box<dyn std::error::Error>> {
+        request.name = sanitize_title(&request.name, "习惯名称")?;
+        validate_weekly_target(request.weekly_target)?;
+        parse_frequency(&request.frequency)?;
+        let color = if request.color.trim().is_empty() {
+            next_habit_color(&self.get_all_habits().await?).to_string()
+        } else {
+            normalize_hex_color(&request.color)?
+        };
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO habits (
+                id, name, description, category, color, target, unit, frequency, is_active, skip_weekends, weekly_target,
+                value_based, created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&id)
+        .bind(&request.name)
+        .bind(&request.description)
+        .bind(&request.category)
+        .bind(&color)
+        .bind(request.target)
+        .bind(&request.unit)
+        .bind(&request.frequency)
+        .bind(request.is_active)
+        .bind(request.skip_weekends)
+        .bind(request.weekly_target)
+        .bind(request.value_based)
+        .bind(now)
+        .bind(now)
+        .execute_with_retry(&self.pool)
+        .await
+        .map_err(map_constraint_error)?;
+
+        self.get_habit(&id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn get_habit(&self, id: &str) -> Result<Habit, Box<dyn std::error::Error>> {
+        self.refresh_stale_habit_streaks(Some(id)).await?;
+        let habit = sqlx::query_as::<_, Habit>(
+            "SELECT id, name, description, category, color, target, unit, frequency, is_active, skip_weekends, weekly_target, value_based, current_streak, longest_streak, created_at, updated_at FROM habits WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(habit)
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_all_habits(&self) -> Result<Vec<Habit>, Box<dyn std::error::Error>> {
+        self.refresh_stale_habit_streaks(None).await?;
+        let habits = sqlx::query_as::<_, Habit>(
+            "SELECT id, name, description, category, color, target, unit, frequency, is_active, skip_weekends, weekly_target, value_based, current_streak, longest_streak, created_at, updated_at FROM habits ORDER BY created_at"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(habits)
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_active_habits(&self) -> Result<Vec<Habit>, Box<dyn std::error::Error>> {
+        self.refresh_stale_habit_streaks(None).await?;
+        let habits = sqlx::query_as::<_, Habit>(
+            "SELECT id, name, description, category, color, target, unit, frequency, is_active, skip_weekends, weekly_target, value_based, current_streak, longest_streak, created_at, updated_at FROM habits WHERE is_active = TRUE ORDER BY created_at"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(habits)
+    }
+
+    // 当天还没打卡、也没有跳过的每日/自定义频率习惯，供提醒任务使用
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_unfinished_daily_habits(&self, date: &str) -> Result<Vec<Habit>, Box<dyn std::error::Error>> {
+        let day = parse_date(date)?;
+        self.refresh_stale_habit_streaks(None).await?;
+        let habits = sqlx::query_as::<_, Habit>(
+            r#"
+            SELECT id, name, description, category, color, target, unit, frequency, is_active, skip_weekends, weekly_target, value_based, current_streak, longest_streak, created_at, updated_at
+            FROM habits h
+            WHERE h.is_active = TRUE AND h.frequency != 'weekly'
+              AND NOT EXISTS (SELECT 1 FROM habit_records r WHERE r.habit_id = h.id AND r.date = ? AND r.completed = TRUE)
+              AND NOT EXISTS (SELECT 1 FROM habit_skips s WHERE s.habit_id = h.id AND s.date = ?)
+              AND NOT EXISTS (SELECT 1 FROM habit_pauses p WHERE p.habit_id = h.id AND p.start_date <= ? AND p.end_date >= ?)
+            ORDER BY h.created_at
+            "#
+        )
+        .bind(date)
+        .bind(date)
+        .bind(date)
+        .bind(date)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(habits
+            .into_iter()
+            .filter(|habit| !is_habit_day_skipped(habit, &HashSet::new(), day))
+            .collect())
+    }
+
+    // include_inactive 为 true 时包含已归档的习惯，供管理页面使用
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_habits_by_category(&self, category: &str, include_inactive: bool) -> Result<Vec<Habit>, Box<dyn std::error::Error>> {
+        self.refresh_stale_habit_streaks(None).await?;
+        let habits = sqlx::query_as::<_, Habit>(
+            "SELECT id, name, description, category, color, target, unit, frequency, is_active, skip_weekends, weekly_target, value_based, current_streak, longest_streak, created_at, updated_at FROM habits WHERE category = ? AND (? OR is_active = TRUE) ORDER BY created_at"
+        )
+        .bind(category)
+        .bind(include_inactive)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(habits)
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_habit_categories(&self) -> Result<Vec<HabitCategory>, Box<dyn std::error::Error>> {
+        let categories = sqlx::query_as::<_, HabitCategory>(
+            r#"
+            SELECT
+                category,
+                COUNT(*) AS habit_count,
+                COALESCE(SUM(CASE WHEN is_active THEN 1 ELSE 0 END), 0) AS active_count
+            FROM habits
+            GROUP BY category
+            ORDER BY category
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(categories)
+    }
+
+    // 归档只是停用，打卡记录会保留
+    async fn set_habit_active(&self, id: &str, is_active: bool) -> Result<Habit, Box<dyn std::error::Error>> {
+        sqlx::query("UPDATE habits SET is_active = ?, updated_at = ? WHERE id = ?")
+            .bind(is_active)
+            .bind(Utc::now())
+            .bind(id)
+            .execute_with_retry(&self.pool)
+            .await?;
+
+        self.get_habit(id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn archive_habit(&self, id: &str) -> Result<Habit, Box<dyn std::error::Error>> {
+        self.set_habit_active(id, false).await
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn unarchive_habit(&self, id: &str) -> Result<Habit, Box<dyn std::error::Error>> {
+        self.set_habit_active(id, true).await
+    }
+
+    // 今日打卡面板：只读取，不会为没有记录的习惯创建记录
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_today_habits(&self, date: &str) -> Result<Vec<HabitWithRecord>, Box<dyn std::error::Error>> {
+        self.refresh_stale_habit_streaks(None).await?;
+        let habits = sqlx::query_as::<_, HabitWithRecord>(
+            r#"
+            SELECT
+                h.id, h.name, h.description, h.category, h.color, h.target, h.unit, h.frequency, h.is_active, h.skip_weekends, h.weekly_target, h.value_based, h.current_streak, h.longest_streak, h.created_at, h.updated_at,
+                r.id AS record_id,
+                COALESCE(r.completed, FALSE) AS completed,
+                r.value,
+                r.note
+            FROM habits h
+            LEFT JOIN habit_records r ON r.habit_id = h.id AND r.date = ?
+            WHERE h.is_active = TRUE
+            ORDER BY h.created_at
+            "#,
+        )
+        .bind(date)
+        .fetch_all(&self.pool)
+        .await?;
+
+        // 自定义频率的习惯只在安排的星期几出现，暂停中的习惯也不显示
+        let day = parse_date(date)?;
+        let paused: HashSet<String> = sqlx::query("SELECT DISTINCT habit_id FROM habit_pauses WHERE start_date <= ? AND end_date >= ?")
+            .bind(date)
+            .bind(date)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| row.get::<String, _>("habit_id"))
+            .collect();
+
+        Ok(habits
+            .into_iter()
+            .filter(|habit| is_scheduled_on(&habit.habit.frequency, day) && !paused.contains(&habit.habit.id))
+            .collect())
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %request.id), err(Display))]
+    pub async fn update_habit(&self, mut request: UpdateHabitRequest) -> Result<Habit, Box<dyn std::error::Error>> {
+        request.name = sanitize_title(&request.name, "习惯名称")?;
+        validate_weekly_target(request.weekly_target)?;
+        parse_frequency(&request.frequency)?;
+        let color = normalize_hex_color(&request.color)?;
+        let previous = self.get_habit(&request.id).await?;
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            UPDATE habits SET 
+                name = ?, description = ?, category = ?, color = ?, target = ?, 
+                unit = ?, frequency = ?, is_active = ?, skip_weekends = COALESCE(?, skip_weekends),
+                weekly_target = CASE WHEN ? THEN NULL ELSE COALESCE(?, weekly_target) END,
+                value_based = COALESCE(?, value_based), updated_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(&request.name)
+        .bind(&request.description)
+        .bind(&request.category)
+        .bind(&color)
+        .bind(request.target)
+        .bind(&request.unit)
+        .bind(&request.frequency)
+        .bind(request.is_active)
+        .bind(request.skip_weekends)
+        .bind(request.clear_weekly_target)
+        .bind(request.weekly_target)
+        .bind(request.value_based)
+        .bind(now)
+        .bind(&request.id)
+        .execute_with_retry(&self.pool)
+        .await?;
+        self.record_undo("updated", UndoSnapshot::Habit { habit: previous, records: Vec::new(), skips: Vec::new(), pauses: Vec::new() });
+
+        // 频率、周末跳过和每周目标都会影响连续天数
+        self.recompute_habit_streak(&request.id).await?;
+        self.get_habit(&request.id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn delete_habit(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let habit = self.get_habit(id).await.ok();
+        let records = self.get_habit_records_by_habit(id, None, None).await?;
+        let skips = sqlx::query_as::<_, HabitSkip>("SELECT id, habit_id, date, created_at FROM habit_skips WHERE habit_id = ?")
+            .bind(id)
+            .fetch_all(&self.pool)
+            .await?;
+        let pauses = self.get_habit_pauses(id).await?;
+
+        sqlx::query("DELETE FROM habits WHERE id = ?")
+            .bind(id)
+            .execute_with_retry(&self.pool)
+            .await?;
+
+        if let Some(habit) = habit {
+            self.record_undo("deleted", UndoSnapshot::Habit { habit, records, skips, pauses });
+        }
+        Ok(())
+    }
+
+    // 习惯跳过日期相关方法
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn add_habit_skip(&self, habit_id: &str, date: &str) -> Result<HabitSkip, Box<dyn std::error::Error>> {
+        parse_date(date)?;
+        // 先确认习惯存在，避免外键错误以原始数据库错误的形式返回
+        let habit = sqlx::query("SELECT id FROM habits WHERE id = ?")
+            .bind(habit_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        if habit.is_none() {
+            return Err(not_found_error("习惯", habit_id));
+        }
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        sqlx::query(
+            "INSERT INTO habit_skips (id, habit_id, date, created_at) VALUES (?, ?, ?, ?) ON CONFLICT(habit_id, date) DO NOTHING"
+        )
+        .bind(&id)
+        .bind(habit_id)
+        .bind(date)
+        .bind(now)
+        .execute_with_retry(&self.pool)
+        .await?;
+
+        let skip = sqlx::query_as::<_, HabitSkip>(
+            "SELECT id, habit_id, date, created_at FROM habit_skips WHERE habit_id = ? AND date = ?"
+        )
+        .bind(habit_id)
+        .bind(date)
+        .fetch_one(&self.pool)
+        .await?;
+
+        self.recompute_habit_streak(habit_id).await?;
+        Ok(skip)
+    }
+
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn remove_habit_skip(&self, habit_id: &str, date: &str) -> Result<(), Box<dyn std::error::Error>> {
+        sqlx::query("DELETE FROM habit_skips WHERE habit_id = ? AND date = ?")
+            .bind(habit_id)
+            .bind(date)
+            .execute_with_retry(&self.pool)
+            .await?;
+
+        self.recompute_habit_streak(habit_id).await?;
+        Ok(())
+    }
+
+    // 习惯暂停区间相关方法
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn add_habit_pause(&self, habit_id: &str, start_date: &str, end_date: &str) -> Result<HabitPause, Box<dyn std::error::Error>> {
+        let start = parse_date(start_date)?;
+        let end = parse_date(end_date)?;
+        if end < start {
+            return Err(validation_error("暂停的结束日期不能早于开始日期"));
+        }
+
+        // 与已有区间重叠时直接拒绝，避免同一天被多个区间覆盖
+        let overlapping: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM habit_pauses WHERE habit_id = ? AND start_date <= ? AND end_date >= ?"
+        )
+        .bind(habit_id)
+        .bind(end_date)
+        .bind(start_date)
+        .fetch_one(&self.pool)
+        .await?;
+        if overlapping > 0 {
+            return Err(validation_error(format!("暂停区间 {} 至 {} 与已有的暂停重叠", start_date, end_date)));
+        }
+
+        let pause = HabitPause {
+            id: Uuid::new_v4().to_string(),
+            habit_id: habit_id.to_string(),
+            start_date: start_date.to_string(),
+            end_date: end_date.to_string(),
+            created_at: Utc::now(),
+        };
+
+        sqlx::query(
+            "INSERT INTO habit_pauses (id, habit_id, start_date, end_date, created_at) VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(&pause.id)
+        .bind(&pause.habit_id)
+        .bind(&pause.start_date)
+        .bind(&pause.end_date)
+        .bind(pause.created_at)
+        .execute_with_retry(&self.pool)
+        .await
+        .map_err(map_constraint_error)?;
+
+        self.recompute_habit_streak(habit_id).await?;
+        Ok(pause)
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn remove_habit_pause(&self, id: &str) -> Result<Option<HabitPause>, Box<dyn std::error::Error>> {
+        let pause = sqlx::query_as::<_, HabitPause>(
+            "SELECT id, habit_id, start_date, end_date, created_at FROM habit_pauses WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        sqlx::query("DELETE FROM habit_pauses WHERE id = ?")
+            .bind(id)
+            .execute_with_retry(&self.pool)
+            .await?;
+
+        if let Some(pause) = &pause {
+            self.recompute_habit_streak(&pause.habit_id).await?;
+        }
+        Ok(pause)
+    }
+
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn get_habit_pauses(&self, habit_id: &str) -> Result<Vec<HabitPause>, Box<dyn std::error::Error>> {
+        let pauses = sqlx::query_as::<_, HabitPause>(
+            "SELECT id, habit_id, start_date, end_date, created_at FROM habit_pauses WHERE habit_id = ? ORDER BY start_date"
+        )
+        .bind(habit_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(pauses)
+    }
+
+    async fn get_habit_skip_dates(&self, habit_id: &str) -> Result<HashSet<NaiveDate>, Box<dyn std::error::Error>> {
+        let mut conn = self.pool.acquire().await?;
+        Ok(load_habit_skip_dates(&mut conn, habit_id).await?)
+    }
+
+    async fn get_habit_completed_dates(&self, habit_id: &str) -> Result<HashSet<NaiveDate>, Box<dyn std::error::Error>> {
+        let mut conn = self.pool.acquire().await?;
+        Ok(load_habit_completed_dates(&mut conn, habit_id).await?)
+    }
+
+    // 已完成打卡按本地时间的小时分布，返回完整的 24 个小时
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn get_habit_checkin_hours(&self, habit_id: &str) -> Result<Vec<HabitCheckinHour>, Box<dyn std::error::Error>> {
+        let offset = self
+            .get_utc_offset_minutes()
+            .await?
+            .and_then(|minutes| FixedOffset::east_opt(minutes * 60));
+
+        let timestamps = sqlx::query("SELECT created_at FROM habit_records WHERE habit_id = ? AND completed = TRUE")
+            .bind(habit_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut counts = [0i64; 24];
+        for row in &timestamps {
+            let created_at = row.get::<chrono::DateTime<Utc>, _>("created_at");
+            // 未配置时区时逐条按系统时区换算，夏令时也能正确处理
+            let hour = match offset {
+                Some(offset) => created_at.with_timezone(&offset).hour(),
+                None => created_at.with_timezone(&Local).hour(),
+            };
+            counts[hour as usize] += 1;
+        }
+
+        Ok(counts
+            .iter()
+            .enumerate()
+            .map(|(hour, count)| HabitCheckinHour {
+                hour: hour as u32,
+                count: *count,
+            })
+            .collect())
+    }
+
+    // 习惯统计相关方法
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn get_habit_streak(&self, habit_id: &str, date: &str) -> Result<HabitStreak, Box<dyn std::error::Error>> {
+        let today = parse_date(date)?;
+        let mut conn = self.pool.acquire().await?;
+        compute_habit_streak(&mut conn, habit_id, today).await
+    }
+
+    // 按今天重新计算并写入 habits 上缓存的连续天数，列表页直接读取缓存。
+    // 读取和写入在同一个事务中，并发的打卡不会让旧的计算结果覆盖新的
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn recompute_habit_streak(&self, habit_id: &str) -> Result<HabitStreak, Box<dyn std::error::Error>> {
+        let today = Local::now().date_naive();
+        self.with_transaction(|conn| {
+            let habit_id = habit_id.to_string();
+            Box::pin(async move { store_habit_streak(conn, &habit_id, today).await })
+        })
+        .await
+    }
+
+    // 缓存的 current_streak 只对计算当天有效：跨天后（或从未计算过）读取前先重新计算。
+    // habit_id 为空时检查所有习惯
+    async fn refresh_stale_habit_streaks(&self, habit_id: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        let today = Local::now().date_naive().format("%Y-%m-%d").to_string();
+        let stale: Vec<String> = sqlx::query_scalar(
+            "SELECT id FROM habits WHERE (? IS NULL OR id = ?) AND (streak_computed_on IS NULL OR streak_computed_on != ?)"
+        )
+        .bind(habit_id)
+        .bind(habit_id)
+        .bind(&today)
+        .fetch_all(&self.pool)
+        .await?;
+
+        for id in stale {
+            self.recompute_habit_streak(&id).await?;
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn get_habit_week_progress(&self, habit_id: &str, week_start: &str) -> Result<HabitWeekProgress, Box<dyn std::error::Error>> {
+        let day = parse_date(week_start)?;
+        let start = week_start_of(day, self.get_week_start_weekday().await?);
+        let end = start + Duration::days(6);
+        let habit = self.get_habit(habit_id).await?;
+
+        let completed = sqlx::query(
+            "SELECT COUNT(DISTINCT date) AS count FROM habit_records WHERE habit_id = ? AND completed = TRUE AND date >= ? AND date <= ?"
+        )
+        .bind(habit_id)
+        .bind(start.format("%Y-%m-%d").to_string())
+        .bind(end.format("%Y-%m-%d").to_string())
+        .fetch_one(&self.pool)
+        .await?
+        .get::<i64, _>("count") as i32;
+
+        let target = habit.weekly_target.unwrap_or(1);
+        Ok(HabitWeekProgress {
+            habit_id: habit.id,
+            week_start: start.format("%Y-%m-%d").to_string(),
+            week_end: end.format("%Y-%m-%d").to_string(),
+            completed,
+            target,
+            achieved: completed >= target,
+        })
+    }
+
+    // 范围内记录的数值合计（如本月读了多少页），未完成但填写了数值的记录同样计入
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn get_habit_value_total(&self, habit_id: &str, start_date: &str, end_date: &str) -> Result<HabitValueTotal, Box<dyn std::error::Error>> {
+        parse_date(start_date)?;
+        parse_date(end_date)?;
+        let habit = self.get_habit(habit_id).await?;
+
+        let total = sqlx::query(
+            "SELECT COALESCE(SUM(COALESCE(value, 0)), 0) AS total FROM habit_records WHERE habit_id = ? AND date >= ? AND date <= ?"
+        )
+        .bind(habit_id)
+        .bind(start_date)
+        .bind(end_date)
+        .fetch_one(&self.pool)
+        .await?
+        .get::<i64, _>("total");
+
+        Ok(HabitValueTotal {
+            habit_id: habit.id,
+            start_date: start_date.to_string(),
+            end_date: end_date.to_string(),
+            total,
+            unit: habit.unit,
+        })
+    }
+
+    // 年度回顾：只扫描一次当年的打卡记录；统计从习惯创建（或更早的首次打卡）开始，到年底或今天为止
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn get_habit_year_summary(&self, habit_id: &str, year: i32) -> Result<HabitYearSummary, Box<dyn std::error::Error>> {
+        let year_start = NaiveDate::from_ymd_opt(year, 1, 1).ok_or_else(|| validation_error(format!("无效的年份: {}", year)))?;
+        let year_end = NaiveDate::from_ymd_opt(year, 12, 31).ok_or_else(|| validation_error(format!("无效的年份: {}", year)))?;
+        let habit = self.get_habit(habit_id).await?;
+        let skips = self.get_habit_skip_dates(habit_id).await?;
+
+        let records = sqlx::query("SELECT date, completed, value FROM habit_records WHERE habit_id = ? AND date >= ? AND date <= ?")
+            .bind(habit_id)
+            .bind(year_start.format("%Y-%m-%d").to_string())
+            .bind(year_end.format("%Y-%m-%d").to_string())
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut completed = HashSet::new();
+        let mut month_counts = [0i64; 12];
+        let mut total_value = 0i64;
+        for row in &records {
+            total_value += row.get::<Option<i64>, _>("value").unwrap_or(0);
+            let Ok(day) = parse_date(&row.get::<String, _>("date")) else {
+                continue;
+            };
+            if row.get::<bool, _>("completed") && completed.insert(day) {
+                month_counts[day.month0() as usize] += 1;
+            }
+        }
+
+        let first_day = completed
+            .iter()
+            .copied()
+            .chain(std::iter::once(habit.created_at.date_naive()))
+            .min()
+            .unwrap_or(year_start)
+            .max(year_start);
+        let last_day = year_end.min(Local::now().date_naive());
+
+        let mut due_days = 0;
+        let mut longest_streak = 0;
+        let mut run = 0;
+        for day in first_day.iter_days().take_while(|day| *day <= last_day) {
+            if completed.contains(&day) {
+                due_days += 1;
+                run += 1;
+                longest_streak = longest_streak.max(run);
+            } else if !is_habit_day_skipped(&habit, &skips, day) {
+                due_days += 1;
+                run = 0;
+            }
+        }
+        if habit_frequency(&habit.frequency) == HabitFrequency::Weekly && first_day <= last_day {
+            let week_start = self.get_week_start_weekday().await?;
+            longest_streak = weekly_streaks(&completed, habit.weekly_target.unwrap_or(1), first_day, last_day, week_start).1;
+        }
+
+        // 完成次数相同的月份取较早的一个
+        let best_month = month_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .max_by(|(a_month, a), (b_month, b)| a.cmp(b).then(b_month.cmp(a_month)))
+            .map(|(month, _)| month as u32 + 1);
+
+        Ok(HabitYearSummary {
+            habit_id: habit.id,
+            year,
+            total_completions: completed.len() as i64,
+            completion_rate: if due_days == 0 { 0.0 } else { completed.len() as f64 / due_days as f64 },
+            longest_streak,
+            best_month,
+            total_value,
+            unit: habit.unit,
+        })
+    }
+
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn get_habit_completion_rate(&self, habit_id: &str, start_date: &str, end_date: &str) -> Result<f64, Box<dyn std::error::Error>> {
+        let start = parse_date(start_date)?;
+        let end = parse_date(end_date)?;
+        let habit = self.get_habit(habit_id).await?;
+        let completed = self.get_habit_completed_dates(habit_id).await?;
+        let skips = self.get_habit_skip_dates(habit_id).await?;
+
+        let mut due_days = 0;
+        let mut completed_days = 0;
+        for day in start.iter_days().take_while(|day| *day <= end) {
+            if completed.contains(&day) {
+                due_days += 1;
+                completed_days += 1;
+            } else if !is_habit_day_skipped(&habit, &skips, day) {
+                due_days += 1;
+            }
+        }
+
+        if due_days == 0 {
+            return Ok(0.0);
+        }
+
+        Ok(completed_days as f64 / due_days as f64)
+    }
+
+    // 所有习惯的每日完成总数，范围内没有记录的日期也会返回 0
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_daily_habit_completions(&self, start_date: &str, end_date: &str) -> Result<Vec<DailyHabitCompletion>, Box<dyn std::error::Error>> {
+        let start = parse_date(start_date)?;
+        let end = parse_date(end_date)?;
+
+        let total_active = sqlx::query("SELECT COUNT(*) AS count FROM habits WHERE is_active = TRUE")
+            .fetch_one(&self.pool)
+            .await?
+            .get::<i64, _>("count");
+
+        let counts: HashMap<String, i64> = sqlx::query(
+            r#"
+            SELECT r.date, COUNT(DISTINCT r.habit_id) AS count
+            FROM habit_records r
+            JOIN habits h ON h.id = r.habit_id
+            WHERE r.completed = TRUE AND r.date >= ? AND r.date <= ?
+            GROUP BY r.date
+            "#,
+        )
+        .bind(start_date)
+        .bind(end_date)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.get::<String, _>("date"), row.get::<i64, _>("count")))
+        .collect();
+
+        Ok(start
+            .iter_days()
+            .take_while(|day| *day <= end)
+            .map(|day| {
+                let date = day.format("%Y-%m-%d").to_string();
+                DailyHabitCompletion {
+                    completed_count: counts.get(&date).copied().unwrap_or(0),
+                    date,
+                    total_active,
+                }
+            })
+            .collect())
+    }
+
+    // 习惯记录相关方法
+    #[tracing::instrument(skip_all, fields(habit_id = %request.habit_id), err(Display))]
+    pub async fn create_habit_record(&self, request: CreateHabitRecordRequest) -> Result<HabitRecord, Box<dyn std::error::Error>> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let today = Local::now().date_naive();
+
+        // 记录和连续天数缓存在同一个事务中写入，缓存更新失败时记录也会回滚
+        self.with_transaction(|conn| {
+            let id = id.clone();
+            let request = request.clone();
+            Box::pin(async move {
+                let completed = resolve_record_completion(conn, &request.habit_id, request.value, request.completed, request.completed_override).await?;
+                sqlx::query(
+                    r#"
+                    INSERT INTO habit_records (
+                        id, habit_id, date, completed, value, note, created_at
+                    ) VALUES (?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                )
+                .bind(&id)
+                .bind(&request.habit_id)
+                .bind(&request.date)
+                .bind(completed)
+                .bind(request.value)
+                .bind(&request.note)
+                .bind(now)
+                .execute(&mut *conn)
+                .await
+                .map_err(map_constraint_error)?;
+
+                store_habit_streak(conn, &request.habit_id, today).await?;
+                Ok::<_, Box<dyn std::error::Error>>(())
+            })
+        })
+        .await?;
+
+        self.get_habit_record(&id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn get_habit_record(&self, id: &str) -> Result<HabitRecord, Box<dyn std::error::Error>> {
+        let record = sqlx::query_as::<_, HabitRecord>(
+            "SELECT id, habit_id, date, completed, value, note, created_at FROM habit_records WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn get_habit_records_by_habit(&self, habit_id: &str, limit: Option<i64>, before_date: Option<&str>) -> Result<Vec<HabitRecord>, Box<dyn std::error::Error>> {
+        if limit.is_some_and(|limit| limit <= 0) {
+            return Err(validation_error("limit 必须大于 0"));
+        }
+        if let Some(before_date) = before_date {
+            parse_date(before_date)?;
+        }
+
+        // 按日期倒序的游标分页：下一页传入上一页最后一条的日期作为 before_date；LIMIT -1 表示不限制
+        let records = sqlx::query_as::<_, HabitRecord>(
+            "SELECT id, habit_id, date, completed, value, note, created_at FROM habit_records WHERE habit_id = ? AND (? IS NULL OR date < ?) ORDER BY date DESC LIMIT ?"
+        )
+        .bind(habit_id)
+        .bind(before_date)
+        .bind(before_date)
+        .bind(limit.unwrap_or(-1))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    // 一次查询多个习惯在日期范围内的记录，由前端按习惯分组
+    #[tracing::instrument(skip_all, fields(count = habit_ids.len()), err(Display))]
+    pub async fn get_habit_records_for_habits(&self, habit_ids: &[String], start_date: &str, end_date: &str) -> Result<Vec<HabitRecord>, Box<dyn std::error::Error>> {
+        if habit_ids.is_empty() || is_reversed_range(start_date, end_date) {
+            return Ok(Vec::new());
+        }
+
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            "SELECT id, habit_id, date, completed, value, note, created_at FROM habit_records WHERE habit_id IN "
+        );
+        push_id_list(&mut builder, habit_ids);
+        builder
+            .push(" AND date BETWEEN ")
+            .push_bind(start_date.to_string())
+            .push(" AND ")
+            .push_bind(end_date.to_string())
+            .push(" ORDER BY habit_id, date");
+
+        let records = builder.build_query_as::<HabitRecord>().fetch_all(&self.pool).await?;
+
+        Ok(records)
+    }
+
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn get_habit_records_by_date_range(&self, habit_id: &str, start_date: &str, end_date: &str) -> Result<Vec<HabitRecord>, Box<dyn std::error::Error>> {
+        if is_reversed_range(start_date, end_date) {
+            return Ok(Vec::new());
+        }
+
+        let records = sqlx::query_as::<_, HabitRecord>(
+            "SELECT id, habit_id, date, completed, value, note, created_at FROM habit_records WHERE habit_id = ? AND date >= ? AND date <= ? ORDER BY date DESC"
+        )
+        .bind(habit_id)
+        .bind(start_date)
+        .bind(end_date)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    // 导出范围内的日程为 Google 日历可导入的 CSV，重复日程按每次发生各占一行，返回写入的行数
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn export_events_google_csv(&self, start_date: &str, end_date: &str, path: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let events = self.get_events_by_date_range(start_date, end_date).await?;
+        let rows: Vec<Vec<String>> = events.iter().map(event_to_google_csv_row).collect();
+
+        tokio::fs::write(path, to_csv(&GOOGLE_CSV_HEADERS, &rows)).await?;
+
+        Ok(rows.len())
+    }
+
+    // 导出习惯记录为 CSV，返回写入的记录数
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn export_habit_records_csv(&self, habit_id: &str, path: &str, start_date: Option<&str>, end_date: Option<&str>) -> Result<usize, Box<dyn std::error::Error>> {
+        let records = sqlx::query_as::<_, HabitRecord>(
+            "SELECT id, habit_id, date, completed, value, note, created_at FROM habit_records WHERE habit_id = ? AND (? IS NULL OR date >= ?) AND (? IS NULL OR date <= ?) ORDER BY date"
+        )
+        .bind(habit_id)
+        .bind(start_date)
+        .bind(start_date)
+        .bind(end_date)
+        .bind(end_date)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let rows: Vec<Vec<String>> = records
+            .iter()
+            .map(|record| {
+                vec![
+                    record.date.clone(),
+                    record.completed.to_string(),
+                    record.value.map(|value| value.to_string()).unwrap_or_default(),
+                    record.note.clone().unwrap_or_default(),
+                ]
+            })
+            .collect();
+
+        tokio::fs::write(path, to_csv(&["date", "completed", "value", "note"], &rows)).await?;
+
+        Ok(rows.len())
+    }
+
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn get_habit_record_by_date(&self, habit_id: &str, date: &str) -> Result<Option<HabitRecord>, Box<dyn std::error::Error>> {
+        let record = sqlx::query_as::<_, HabitRecord>(
+            "SELECT id, habit_id, date, completed, value, note, created_at FROM habit_records WHERE habit_id = ? AND date = ?"
+        )
+        .bind(habit_id)
+        .bind(date)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id), err(Display))]
+    pub async fn update_habit_record(&self, id: &str, completed: bool, value: Option<i32>, note: Option<String>, completed_override: bool) -> Result<HabitRecord, Box<dyn std::error::Error>> {
+        let habit_id = self.get_habit_record(id).await?.habit_id;
+        let today = Local::now().date_naive();
+
+        self.with_transaction(|conn| {
+            let id = id.to_string();
+            let habit_id = habit_id.clone();
+            let note = note.clone();
+            Box::pin(async move {
+                let completed = resolve_record_completion(conn, &habit_id, value, completed, completed_override).await?;
+                sqlx::query(
+                    "UPDATE habit_records SET completed = ?, value = ?, note = ? WHERE id = ?"
+                )
+                .bind(completed)
+                .bind(value)
+                .bind(&note)
+                .bind(&id)
+                .execute(&mut *conn)
+                .await?;
+
+                store_habit_streak(conn, &habit_id, today).await?;
+                Ok::<_, Box<dyn std::error::Error>>(())
+            })
+        })
+        .await?;
+
+        self.get_habit_record(id).await
+    }
+
+    // 周视图批量打卡：按 (habit_id, date) 插入或更新，全部在一个事务中完成，按传入顺序返回结果
+    #[tracing::instrument(skip_all, fields(count = records.len()), err(Display))]
+    pub async fn upsert_habit_records_bulk(&self, records: Vec<CreateHabitRecordRequest>) -> Result<Vec<HabitRecord>, Box<dyn std::error::Error>> {
+        for record in &records {
+            parse_date(&record.date)?;
+        }
+
+        let today = Local::now().date_naive();
+        let saved = self.with_transaction(|conn| {
+            let records = records.clone();
+            Box::pin(async move {
+                let now = Utc::now();
+                let mut saved = Vec::with_capacity(records.len());
+                for record in &records {
+                    let completed = resolve_record_completion(conn, &record.habit_id, record.value, record.completed, record.completed_override).await?;
+                    sqlx::query(
+                        r#"
+                        INSERT INTO habit_records (id, habit_id, date, completed, value, note, created_at)
+                        VALUES (?, ?, ?, ?, ?, ?, ?)
+                        ON CONFLICT(habit_id, date) DO UPDATE SET
+                            completed = excluded.completed, value = excluded.value, note = excluded.note
+                        "#,
+                    )
+                    .bind(Uuid::new_v4().to_string())
+                    .bind(&record.habit_id)
+                    .bind(&record.date)
+                    .bind(completed)
+                    .bind(record.value)
+                    .bind(&record.note)
+                    .bind(now)
+                    .execute(&mut *conn)
+                    .await?;
+
+                    let row = sqlx::query_as::<_, HabitRecord>(
+                        "SELECT id, habit_id, date, completed, value, note, created_at FROM habit_records WHERE habit_id = ? AND date = ?"
+                    )
+                    .bind(&record.habit_id)
+                    .bind(&record.date)
+                    .fetch_one(&mut *conn)
+                    .await?;
+                    saved.push(row);
+                }
+
+                let habit_ids: HashSet<String> = saved.iter().map(|record| record.habit_id.clone()).collect();
+                for habit_id in &habit_ids {
+                    store_habit_streak(conn, habit_id, today).await?;
+                }
+                Ok::<_, Box<dyn std::error::Error>>(saved)
+            })
+        })
+        .await?;
+
+        Ok(saved)
+    }
+
+    // pub async fn delete_habit_record(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    //     sqlx::query("DELETE FROM habit_records WHERE id = ?")
+    //         .bind(id)
+    //         .execute(&self.pool)
+    //         .await?;
+
+    //     Ok(())
+    // }
+
+    #[tracing::instrument(skip_all, fields(habit_id = %habit_id), err(Display))]
+    pub async fn get_or_create_habit_record(&self, habit_id: &str, date: &str) -> Result<HabitRecord, Box<dyn std::error::Error>> {
+        // 首先尝试获取现有记录
+        let existing_record = sqlx::query_as::<_, HabitRecord>(
+            "SELECT id, habit_id, date, completed, value, note, created_at FROM habit_records WHERE habit_id = ? AND date = ?"
+        )
+        .bind(habit_id)
+        .bind(date)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(record) = existing_record {
+            Ok(record)
+        } else {
+            // 创建新记录
+            let request = CreateHabitRecordRequest {
+                habit_id: habit_id.to_string(),
+                date: date.to_string(),
+                completed: false,
+                value: None,
+                note: None,
+                completed_override: false,
+            };
+            self.create_habit_record(request).await
+        }
+    }
+
+    // 回顾报告：每一项单独查询后组装；include_archived 为 false 时不统计已归档的便笺
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_productivity_report(&self, start_date: &str, end_date: &str, include_archived: bool) -> Result<ProductivityReport, Box<dyn std::error::Error>> {
+        if parse_date(end_date)? < parse_date(start_date)? {
+            return Err(validation_error("结束日期不能早于开始日期"));
+        }
+
+        // 待办没有单独的完成时间，以最后更新时间近似
+        let todos_completed = sqlx::query(
+            "SELECT COUNT(*) AS count FROM todos WHERE completed = TRUE AND date(updated_at) >= ? AND date(updated_at) <= ?"
+        )
+        .bind(start_date)
+        .bind(end_date)
+        .fetch_one(&self.pool)
+        .await?
+        .get::<i64, _>("count");
+
+        let habits = self.get_active_habits().await?;
+        let mut rate_sum = 0.0;
+        for habit in &habits {
+            rate_sum += self.get_habit_completion_rate(&habit.id, start_date, end_date).await?;
+        }
+        let habit_completion_rate = if habits.is_empty() { 0.0 } else { rate_sum / habits.len() as f64 };
+
+        let focus_seconds = sqlx::query(
+            "SELECT COALESCE(SUM(duration), 0) AS total FROM pomodoro_sessions WHERE session_type = 'work' AND completed = TRUE AND date >= ? AND date <= ?"
+        )
+        .bind(start_date)
+        .bind(end_date)
+        .fetch_one(&self.pool)
+        .await?
+        .get::<i64, _>("total");
+
+        let events_attended = self.get_events_by_date_range(start_date, end_date).await?.len() as i64;
+
+        let notes_created = sqlx::query(
+            "SELECT COUNT(*) AS count FROM notes WHERE date(created_at) >= ? AND date(created_at) <= ? AND (? OR is_archived = FALSE)"
+        )
+        .bind(start_date)
+        .bind(end_date)
+        .bind(include_archived)
+        .fetch_one(&self.pool)
+        .await?
+        .get::<i64, _>("count");
+
+        Ok(ProductivityReport {
+            start_date: start_date.to_string(),
+            end_date: end_date.to_string(),
+            todos_completed,
+            habit_completion_rate,
+            focus_minutes: focus_seconds / 60,
+            events_attended,
+            notes_created,
+        })
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn export_report_markdown(&self, path: &str, start_date: &str, end_date: &str) -> Result<ProductivityReport, Box<dyn std::error::Error>> {
+        let report = self.get_productivity_report(start_date, end_date, false).await?;
+        tokio::fs::write(path, report_to_markdown(&report)).await?;
+
+        Ok(report)
+    }
+
+    // 备份相关方法
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn export_backup(&self, path: &str) -> Result<Vec<TableRowCount>, Box<dyn std::error::Error>> {
+        let backup = Backup {
+            schema_version: BACKUP_SCHEMA_VERSION,
+            exported_at: Utc::now(),
+            calendar_events: sqlx::query_as::<_, CalendarEvent>(
+                "SELECT id, title, description, date, end_date, start_time, end_time, event_type, priority, is_all_day, reminder, repeat_type, repeat_until, repeat_count, location, attendees, is_favorite, notification_enabled, created_at, updated_at FROM calendar_events ORDER BY created_at"
+            )
+            .fetch_all(&self.pool)
+            .await?,
+            event_exceptions: sqlx::query_as::<_, EventException>(
+                "SELECT id, event_id, occurrence_date, is_cancelled, override_data, created_at FROM event_exceptions ORDER BY created_at"
+            )
+            .fetch_all(&self.pool)
+            .await?,
+            habits: self.get_all_habits().await?,
+            habit_records: sqlx::query_as::<_, HabitRecord>(
+                "SELECT id, habit_id, date, completed, value, note, created_at FROM habit_records ORDER BY date"
+            )
+            .fetch_all(&self.pool)
+            .await?,
+            habit_skips: sqlx::query_as::<_, HabitSkip>("SELECT id, habit_id, date, created_at FROM habit_skips ORDER BY date")
+                .fetch_all(&self.pool)
+                .await?,
+            habit_pauses: sqlx::query_as::<_, HabitPause>(
+                "SELECT id, habit_id, start_date, end_date, created_at FROM habit_pauses ORDER BY start_date"
+            )
+            .fetch_all(&self.pool)
+            .await?,
+            todos: sqlx::query_as::<_, Todo>(
+                "SELECT id, title, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at FROM todos ORDER BY created_at"
+            )
+            .fetch_all(&self.pool)
+            .await?,
+            subtasks: sqlx::query_as::<_, Subtask>("SELECT id, todo_id, title, completed, created_at FROM subtasks ORDER BY created_at")
+                .fetch_all(&self.pool)
+                .await?,
+            pomodoro_sessions: sqlx::query_as::<_, PomodoroSession>(
+                "SELECT id, session_type, duration, completed, task_title, notes, date, started_at, ended_at, interrupted, interruption_reason, created_at FROM pomodoro_sessions ORDER BY date, created_at"
+            )
+            .fetch_all(&self.pool)
+            .await?,
+            notes: sqlx::query_as::<_, Note>(
+                "SELECT id, title, content, tags, category, color, is_pinned, is_archived, pin_order, created_at, updated_at FROM notes ORDER BY created_at"
+            )
+            .fetch_all(&self.pool)
+            .await?,
+            note_templates: sqlx::query_as::<_, NoteTemplate>(
+                "SELECT id, name, content, tags, category, color, created_at, updated_at FROM note_templates ORDER BY created_at"
+            )
+            .fetch_all(&self.pool)
+            .await?,
+            entity_links: sqlx::query_as::<_, EntityLink>(
+                "SELECT id, source_type, source_id, target_type, target_id, created_at FROM entity_links ORDER BY created_at"
+            )
+            .fetch_all(&self.pool)
+            .await?,
+        };
+
+        tokio::fs::write(path, serde_json::to_string_pretty(&backup)?).await?;
+
+        Ok(backup_table_counts(&backup))
+    }
+
+    // 试运行：只校验备份文件，不写入任何数据
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn validate_backup(&self, path: &str) -> Result<BackupValidation, Box<dyn std::error::Error>> {
+        let raw = tokio::fs::read_to_string(path).await?;
+        Ok(validate_backup_json(&raw).0)
+    }
+
+    // 导入前执行与 validate_backup 相同的校验，任何错误都会拒绝导入
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn import_backup(&self, path: &str, mode: ImportMode) -> Result<BackupValidation, Box<dyn std::error::Error>> {
+        let raw = tokio::fs::read_to_string(path).await?;
+        let (validation, backup) = validate_backup_json(&raw);
+        let Some(backup) = backup else {
+            return Err(validation_error(format!("备份文件无效: {}", validation.errors.join("; "))));
+        };
+
+        // 重试时共享同一份备份数据，避免整体复制
+        let backup = Arc::new(backup);
+        self.with_transaction(|conn| {
+            let backup = Arc::clone(&backup);
+            Box::pin(async move {
+                if let ImportMode::Replace = mode {
+                    for table in BACKUP_TABLES.iter().rev() {
+                        // 表名来自常量列表，拼接是安全的
+                        sqlx::query(&format!("DELETE FROM {}", table)).execute(&mut *conn).await?;
+                    }
+                }
+
+                for event in &backup.calendar_events {
+                    upsert_event(conn, event).await?;
+                }
+                for exception in &backup.event_exceptions {
+                    insert_event_exception(conn, exception).await?;
+                }
+                for habit in &backup.habits {
+                    upsert_habit(conn, habit).await?;
+                }
+                for record in &backup.habit_records {
+                    insert_habit_record(conn, record).await?;
+                }
+                for skip in &backup.habit_skips {
+                    insert_habit_skip(conn, skip).await?;
+                }
+                for pause in &backup.habit_pauses {
+                    insert_habit_pause(conn, pause).await?;
+                }
+                for todo in &backup.todos {
+                    upsert_todo(conn, todo).await?;
+                }
+                for subtask in &backup.subtasks {
+                    insert_subtask(conn, subtask).await?;
+                }
+                for session in &backup.pomodoro_sessions {
+                    insert_pomodoro_session(conn, session).await?;
+                }
+                for note in &backup.notes {
+                    upsert_note(conn, note).await?;
+                }
+                for template in &backup.note_templates {
+                    upsert_note_template(conn, template).await?;
+                }
+                for link in &backup.entity_links {
+                    insert_entity_link(conn, link).await?;
+                }
+
+                Ok::<_, Box<dyn std::error::Error>>(())
+            })
+        })
+        .await?;
+
+        // 导入后旧快照已不可靠
+        self.undo_log.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).entries.clear();
+
+        for habit in self.get_all_habits().await? {
+            self.recompute_habit_streak(&habit.id).await?;
+        }
+
+        Ok(validation)
+    }
+
+    // 实体关联相关方法
+    #[tracing::instrument(skip_all, fields(source_type = %source_type, source_id = %source_id, target_type = %target_type, target_id = %target_id), err(Display))]
+    pub async fn link_entities(&self, source_type: &str, source_id: &str, target_type: &str, target_id: &str) -> Result<EntityLink, Box<dyn std::error::Error>> {
+        let ((source_type, source_id), (target_type, target_id)) =
+            normalize_link((source_type, source_id), (target_type, target_id))?;
+        for (entity_type, id) in [(source_type, source_id), (target_type, target_id)] {
+            let (table, entity) = match entity_type {
+                "note" => ("notes", "便笺"),
+                "todo" => ("todos", "待办"),
+                _ => ("calendar_events", "日程"),
+            };
+            // 表名来自上面的固定分支，拼接是安全的
+            let exists = sqlx::query(&format!("SELECT 1 FROM {} WHERE id = ?", table))
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?
+                .is_some();
+            if !exists {
+                return Err(not_found_error(entity, id));
+            }
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO entity_links (id, source_type, source_id, target_type, target_id, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(source_type, source_id, target_type, target_id) DO NOTHING
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(source_type)
+        .bind(source_id)
+        .bind(target_type)
+        .bind(target_id)
+        .bind(Utc::now())
+        .execute_with_retry(&self.pool)
+        .await
+        .map_err(map_constraint_error)?;
+
+        let link = sqlx::query_as::<_, EntityLink>(
+            "SELECT id, source_type, source_id, target_type, target_id, created_at FROM entity_links WHERE source_type = ? AND source_id = ? AND target_type = ? AND target_id = ?"
+        )
+        .bind(source_type)
+        .bind(source_id)
+        .bind(target_type)
+        .bind(target_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(link)
+    }
+
+    #[tracing::instrument(skip_all, fields(source_type = %source_type, source_id = %source_id, target_type = %target_type, target_id = %target_id), err(Display))]
+    pub async fn unlink_entities(&self, source_type: &str, source_id: &str, target_type: &str, target_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let ((source_type, source_id), (target_type, target_id)) =
+            normalize_link((source_type, source_id), (target_type, target_id))?;
+
+        sqlx::query("DELETE FROM entity_links WHERE source_type = ? AND source_id = ? AND target_type = ? AND target_id = ?")
+            .bind(source_type)
+            .bind(source_id)
+            .bind(target_type)
+            .bind(target_id)
+            .execute_with_retry(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // 返回与该实体关联的便笺、待办和日程本身，而不只是 id
+    #[tracing::instrument(skip_all, fields(entity_type = %entity_type, id = %id), err(Display))]
+    pub async fn get_links(&self, entity_type: &str, id: &str) -> Result<LinkedEntities, Box<dyn std::error::Error>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT target_type AS linked_type, target_id AS linked_id FROM entity_links WHERE source_type = ? AND source_id = ?
+            UNION
+            SELECT source_type, source_id FROM entity_links WHERE target_type = ? AND target_id = ?
+            "#,
+        )
+        .bind(entity_type)
+        .bind(id)
+        .bind(entity_type)
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut ids: HashMap<String, Vec<String>> = HashMap::new();
+        for row in &rows {
+            ids.entry(row.get::<String, _>("linked_type"))
+                .or_default()
+                .push(row.get::<String, _>("linked_id"));
+        }
+
+        let mut linked = LinkedEntities::default();
+        if let Some(note_ids) = ids.get("note") {
+            let mut builder = QueryBuilder::<Sqlite>::new(
+                "SELECT id, title, content, tags, category, color, is_pinned, is_archived, pin_order, created_at, updated_at FROM notes WHERE id IN "
+            );
+            push_id_list(&mut builder, note_ids);
+            builder.push(" ORDER BY updated_at DESC");
+            let notes = builder.build_query_as::<Note>().fetch_all(&self.pool).await?;
+            linked.notes = notes.into_iter().map(sanitize_note).collect();
+        }
+        if let Some(todo_ids) = ids.get("todo") {
+            let mut builder = QueryBuilder::<Sqlite>::new(
+                "SELECT id, title, description, completed, priority, tags, due_date, category, auto_complete_on_subtasks, is_archived, created_at, updated_at FROM todos WHERE id IN "
+            );
+            push_id_list(&mut builder, todo_ids);
+            builder.push(" ORDER BY created_at");
+            let todos = builder.build_query_as::<Todo>().fetch_all(&self.pool).await?;
+            linked.todos = todos.into_iter().map(sanitize_todo).collect();
+        }
+        if let Some(event_ids) = ids.get("event") {
+            let mut builder = QueryBuilder::<Sqlite>::new(
+                "SELECT id, title, description, date, end_date, start_time, end_time, event_type, priority, is_all_day, reminder, repeat_type, repeat_until, repeat_count, location, attendees, is_favorite, notification_enabled, created_at, updated_at FROM calendar_events WHERE id IN "
+            );
+            push_id_list(&mut builder, event_ids);
+            builder.push(" ORDER BY date, start_time");
+            let events = builder.build_query_as::<CalendarEvent>().fetch_all(&self.pool).await?;
+            linked.events = events.into_iter().map(sanitize_event).collect();
+        }
+
+        Ok(linked)
+    }
+
+    // 数据库诊断相关方法
+    // 只执行两条很轻的查询，前端可以定时轮询；查询失败时返回 db_ok = false 而不是报错
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn healthcheck(&self) -> Result<HealthCheck, Box<dyn std::error::Error>> {
+        let db_ok = sqlx::query("SELECT 1").fetch_one(&self.pool).await.is_ok();
+        let schema_version = if db_ok {
+            sqlx::query("PRAGMA user_version")
+                .fetch_one(&self.pool)
+                .await
+                .map(|row| row.get::<i64, _>(0))
+                .unwrap_or(0)
+        } else {
+            0
+        };
+
+        Ok(HealthCheck {
+            db_ok,
+            schema_version,
+            pending_migrations: (SCHEMA_VERSION - schema_version).max(0) as usize,
+        })
+    }
+
+    // 高级用户的自定义报表：在单独的只读连接上执行校验过的 SELECT，最多返回 READONLY_QUERY_MAX_ROWS 行，
+    // 超时后由 SQLite 中断执行；每一行转换为以列名为键的 JSON 对象
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn run_readonly_query(&self, sql: &str) -> Result<Vec<serde_json::Value>, Box<dyn std::error::Error>> {
+        let sql = validate_readonly_sql(sql)?;
+        // 换行避免语句末尾的单行注释吞掉右括号
+        let limited = format!("SELECT * FROM (\n{}\n) LIMIT {}", sql, READONLY_QUERY_MAX_ROWS);
+
+        let options = SqliteConnectOptions::new().filename(&self.path).read_only(true);
+        let mut conn = SqliteConnection::connect_with(&options).await?;
+        // 只放弃等待的话，语句仍会在 SQLite 的工作线程里继续执行；进度回调返回 false 时 SQLite 中断当前语句
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(READONLY_QUERY_TIMEOUT_SECS);
+        conn.lock_handle()
+            .await?
+            .set_progress_handler(READONLY_QUERY_PROGRESS_OPS, move || std::time::Instant::now() < deadline);
+        let rows = match sqlx::query(&limited).fetch_all(&mut conn).await {
+            Ok(rows) => Ok(rows),
+            Err(_) if std::time::Instant::now() >= deadline => Err(format!("查询超过 {} 秒未完成", READONLY_QUERY_TIMEOUT_SECS)),
+            Err(e) => Err(e.to_string()),
+        };
+        let _ = conn.close().await;
+        let rows = rows.map_err(validation_error)?;
+
+        let mut results = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let mut object = serde_json::Map::new();
+            for column in row.columns() {
+                object.insert(column.name().to_string(), sqlite_value_to_json(row, column.ordinal())?);
+            }
+            results.push(serde_json::Value::Object(object));
+        }
+        Ok(results)
+    }
+
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn get_db_stats(&self) -> Result<DbStats, Box<dyn std::error::Error>> {
+        let page_count = sqlx::query("PRAGMA page_count")
+            .fetch_one(&self.pool)
+            .await?
+            .get::<i64, _>(0);
+        let page_size = sqlx::query("PRAGMA page_size")
+            .fetch_one(&self.pool)
+            .await?
+            .get::<i64, _>(0);
+        let journal_mode = sqlx::query("PRAGMA journal_mode")
+            .fetch_one(&self.pool)
+            .await?
+            .get::<String, _>(0);
+
+        let mut tables = Vec::with_capacity(TABLES.len());
+        for table in TABLES {
+            // 表名来自常量列表，拼接是安全的
+            let rows = sqlx::query(&format!("SELECT COUNT(*) AS count FROM {}", table))
+                .fetch_one(&self.pool)
+                .await?
+                .get::<i64, _>("count");
+            tables.push(TableRowCount {
+                table: table.to_string(),
+                rows,
+            });
+        }
+
+        Ok(DbStats {
+            file_size: page_count * page_size,
+            page_count,
+            page_size,
+            wal_enabled: journal_mode.eq_ignore_ascii_case("wal"),
+            journal_mode,
+            tables,
+        })
+    }
+
+    async fn get_db_file_size(&self) -> Result<i64, Box<dyn std::error::Error>> {
+        let size = sqlx::query("SELECT page_count * page_size AS size FROM pragma_page_count(), pragma_page_size()")
+            .fetch_one(&self.pool)
+            .await?
+            .get::<i64, _>("size");
+
+        Ok(size)
+    }
+
+    // 压缩数据库：数据量大时 VACUUM 可能较慢，只应由用户手动触发。
+    // 调用方持有 DatabaseState 的锁，期间不会有其他命令写入。
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn compact_database(&self) -> Result<CompactResult, Box<dyn std::error::Error>> {
+        let size_before = self.get_db_file_size().await?;
+
+        let mut conn = self.pool.acquire().await?;
+        let journal_mode = sqlx::query("PRAGMA journal_mode")
+            .fetch_one(&mut *conn)
+            .await?
+            .get::<String, _>(0);
+        if journal_mode.eq_ignore_ascii_case("wal") {
+            sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+                .execute(&mut *conn)
+                .await?;
+        }
+        sqlx::query("VACUUM").execute(&mut *conn).await?;
+        drop(conn);
+
+        let size_after = self.get_db_file_size().await?;
+
+        Ok(CompactResult {
+            size_before,
+            size_after,
+        })
+    }
+
+    // 外键约束未启用时删除父记录会留下孤立的子任务和打卡记录
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn find_orphans(&self) -> Result<OrphanReport, Box<dyn std::error::Error>> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM subtasks WHERE todo_id NOT IN (SELECT id FROM todos)) AS orphaned_subtasks,
+                (SELECT COUNT(*) FROM habit_records WHERE habit_id NOT IN (SELECT id FROM habits)) AS orphaned_habit_records
             "#,
         )
-        .bind(&id)
-        .bind(&request.title)
-        .bind(&request.description)
-        .bind(&request.date)
-        .bind(&request.start_time)
-        .bind(&request.end_time)
-        .bind(&request.event_type)
-        .bind(&request.priority)
-        .bind(request.is_all_day)
-        .bind(request.reminder)
-        .bind(&request.repeat_type)
-        .bind(&request.location)
-        .bind(&attendees_json)
-        .bind(now)
-        .bind(now)
-        .execute(&self.pool)
+        .fetch_one(&self.pool)
         .await?;
 
-        self.get_event(&id).await
+        Ok(OrphanReport {
+            orphaned_subtasks: row.get("orphaned_subtasks"),
+            orphaned_habit_records: row.get("orphaned_habit_records"),
+        })
+    }
+
+    // 在一个事务中删除所有孤立行，返回各表实际删除的数量
+    #[tracing::instrument(skip_all, err(Display))]
+    pub async fn repair_orphans(&self) -> Result<OrphanReport, Box<dyn std::error::Error>> {
+        self.with_transaction(|conn| {
+            Box::pin(async move {
+                let subtasks = sqlx::query("DELETE FROM subtasks WHERE todo_id NOT IN (SELECT id FROM todos)")
+                    .execute(&mut *conn)
+                    .await?;
+                let habit_records = sqlx::query("DELETE FROM habit_records WHERE habit_id NOT IN (SELECT id FROM habits)")
+                    .execute(&mut *conn)
+                    .await?;
+
+                Ok::<_, Box<dyn std::error::Error>>(OrphanReport {
+                    orphaned_subtasks: subtasks.rows_affected() as i64,
+                    orphaned_habit_records: habit_records.rows_affected() as i64,
+                })
+            })
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 每个测试使用独立的临时数据库文件，互不影响
+    fn test_db_path() -> String {
+        std::env::temp_dir()
+            .join(format!("productimate-test-{}.db", Uuid::new_v4()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    async fn test_db() -> DatabaseService {
+        DatabaseService::open(&test_db_path()).await.unwrap()
+    }
+
+    fn settings_request(work_time: i32) -> UpdatePomodoroSettingsRequest {
+        UpdatePomodoroSettingsRequest {
+            work_time,
+            short_break: 300,
+            long_break: 900,
+            long_break_interval: 4,
+            auto_start_breaks: false,
+            auto_start_work: false,
+            notification_enabled: true,
+            daily_session_goal: None,
+        }
+    }
+
+    fn habit_request(name: &str) -> CreateHabitRequest {
+        CreateHabitRequest {
+            name: name.to_string(),
+            description: None,
+            category: "health".to_string(),
+            color: String::new(),
+            target: 1,
+            unit: "次".to_string(),
+            frequency: "daily".to_string(),
+            is_active: true,
+            skip_weekends: false,
+            weekly_target: None,
+            value_based: false,
+        }
+    }
+
+    // 模拟前端编辑表单：只带基础字段，可选字段全部缺省
+    fn habit_edit(habit: &Habit) -> UpdateHabitRequest {
+        UpdateHabitRequest {
+            id: habit.id.clone(),
+            name: habit.name.clone(),
+            description: habit.description.clone(),
+            category: habit.category.clone(),
+            color: habit.color.clone(),
+            target: habit.target,
+            unit: habit.unit.clone(),
+            frequency: habit.frequency.clone(),
+            is_active: habit.is_active,
+            skip_weekends: None,
+            weekly_target: None,
+            clear_weekly_target: false,
+            value_based: None,
+        }
+    }
+
+    fn day(date: &str) -> NaiveDate {
+        parse_date(date).unwrap()
+    }
+
+    async fn complete_habit_on(db: &DatabaseService, habit_id: &str, date: &str) {
+        db.create_habit_record(CreateHabitRecordRequest {
+            habit_id: habit_id.to_string(),
+            date: date.to_string(),
+            completed: true,
+            value: None,
+            note: None,
+            completed_override: false,
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn habit_week_progress_reports_partial_week() {
+        let db = test_db().await;
+        let habit = db
+            .create_habit(CreateHabitRequest {
+                frequency: "weekly".to_string(),
+                weekly_target: Some(3),
+                ..habit_request("健身")
+            })
+            .await
+            .unwrap();
+        complete_habit_on(&db, &habit.id, "2026-01-06").await;
+        complete_habit_on(&db, &habit.id, "2026-01-07").await;
+
+        let progress = db.get_habit_week_progress(&habit.id, "2026-01-07").await.unwrap();
+        assert_eq!(progress.completed, 2);
+        assert_eq!(progress.target, 3);
+        assert!(!progress.achieved);
+    }
+
+    #[test]
+    fn weekly_streaks_keep_run_through_unfinished_current_week() {
+        let completed: HashSet<NaiveDate> = ["2026-01-05", "2026-01-06", "2026-01-07", "2026-01-12", "2026-01-13", "2026-01-14", "2026-01-19"]
+            .into_iter()
+            .map(day)
+            .collect();
+
+        let (current, longest) = weekly_streaks(&completed, 3, day("2026-01-05"), day("2026-01-21"), Weekday::Mon);
+        assert_eq!((current, longest), (2, 2));
+    }
+
+    #[test]
+    fn weekly_streaks_reset_after_partial_past_week() {
+        let completed: HashSet<NaiveDate> = ["2026-01-05", "2026-01-06", "2026-01-07", "2026-01-12", "2026-01-19", "2026-01-20", "2026-01-21"]
+            .into_iter()
+            .map(day)
+            .collect();
+
+        let (current, longest) = weekly_streaks(&completed, 3, day("2026-01-05"), day("2026-01-25"), Weekday::Mon);
+        assert_eq!((current, longest), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn update_habit_keeps_weekly_target_unless_cleared() {
+        let db = test_db().await;
+        let habit = db
+            .create_habit(CreateHabitRequest {
+                frequency: "weekly".to_string(),
+                weekly_target: Some(3),
+                ..habit_request("游泳")
+            })
+            .await
+            .unwrap();
+
+        let updated = db.update_habit(habit_edit(&habit)).await.unwrap();
+        assert_eq!(updated.weekly_target, Some(3));
+
+        let updated = db.update_habit(UpdateHabitRequest { weekly_target: Some(5), ..habit_edit(&habit) }).await.unwrap();
+        assert_eq!(updated.weekly_target, Some(5));
+
+        let updated = db.update_habit(UpdateHabitRequest { clear_weekly_target: true, ..habit_edit(&habit) }).await.unwrap();
+        assert_eq!(updated.weekly_target, None);
+    }
+
+    #[tokio::test]
+    async fn update_habit_keeps_skip_weekends_when_absent() {
+        let db = test_db().await;
+        let habit = db
+            .create_habit(CreateHabitRequest { skip_weekends: true, ..habit_request("晨跑") })
+            .await
+            .unwrap();
+
+        let updated = db.update_habit(UpdateHabitRequest { name: "夜跑".to_string(), ..habit_edit(&habit) }).await.unwrap();
+        assert_eq!(updated.name, "夜跑");
+        assert!(updated.skip_weekends);
+
+        let updated = db.update_habit(UpdateHabitRequest { skip_weekends: Some(false), ..habit_edit(&habit) }).await.unwrap();
+        assert!(!updated.skip_weekends);
+    }
+
+    #[tokio::test]
+    async fn add_habit_skip_rejects_unknown_habit() {
+        let db = test_db().await;
+
+        let error = db.add_habit_skip("missing", "2026-01-05").await.unwrap_err();
+        assert!(error.downcast_ref::<crate::error::NotFoundError>().is_some());
+    }
+
+    fn todo_request(title: &str) -> CreateTodoRequest {
+        CreateTodoRequest {
+            title: title.to_string(),
+            description: None,
+            priority: "medium".to_string(),
+            tags: None,
+            due_date: None,
+            category: "general".to_string(),
+            auto_complete_on_subtasks: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn search_todos_tolerates_one_character_typo() {
+        let db = test_db().await;
+        let groceries = db.create_todo(todo_request("Buy groceries")).await.unwrap();
+        db.create_todo(todo_request("Call mom")).await.unwrap();
+
+        let results = db.search_todos("grocries").await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, groceries.id);
+
+        let results = db.search_todos("gruceries").await.unwrap();
+        assert_eq!(results.iter().map(|todo| todo.id.as_str()).collect::<Vec<_>>(), vec![groceries.id.as_str()]);
+    }
+
+    async fn insert_test_setting(conn: &mut SqliteConnection, key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO app_settings (key, value, updated_at) VALUES (?, '1', ?)")
+            .bind(key)
+            .bind(Utc::now())
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn with_transaction_rolls_back_on_error() {
+        let db = test_db().await;
+
+        let result: Result<(), _> = db
+            .with_transaction(|conn| {
+                Box::pin(async move {
+                    insert_test_setting(conn, "tx_first").await?;
+                    insert_test_setting(conn, "tx_second").await?;
+                    Err::<(), _>(validation_error("中途失败"))
+                })
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(db.get_app_setting("tx_first").await.unwrap(), None);
+        assert_eq!(db.get_app_setting("tx_second").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn with_transaction_commits_on_success() {
+        let db = test_db().await;
+
+        db.with_transaction(|conn| {
+            Box::pin(async move {
+                insert_test_setting(conn, "tx_committed").await?;
+                Ok::<_, Box<dyn std::error::Error>>(())
+            })
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(db.get_app_setting("tx_committed").await.unwrap(), Some("1".to_string()));
+    }
+
+    fn event_request(title: &str, date: &str) -> CreateEventRequest {
+        CreateEventRequest {
+            title: title.to_string(),
+            description: None,
+            date: date.to_string(),
+            end_date: None,
+            start_time: Some("09:00".to_string()),
+            end_time: Some("10:00".to_string()),
+            event_type: "work".to_string(),
+            priority: "medium".to_string(),
+            is_all_day: false,
+            reminder: None,
+            repeat_type: None,
+            repeat_until: None,
+            repeat_count: None,
+            location: None,
+            attendees: None,
+            notification_enabled: true,
+        }
+    }
+
+    // 模拟前端编辑表单：不带结束日期和重复限制
+    fn event_edit(event: &CalendarEvent) -> UpdateEventRequest {
+        UpdateEventRequest {
+            id: event.id.clone(),
+            title: event.title.clone(),
+            description: event.description.clone(),
+            date: event.date.clone(),
+            end_date: None,
+            clear_end_date: false,
+            start_time: event.start_time.clone(),
+            end_time: event.end_time.clone(),
+            event_type: event.event_type.clone(),
+            priority: event.priority.clone(),
+            is_all_day: event.is_all_day,
+            reminder: event.reminder,
+            repeat_type: event.repeat_type.clone(),
+            repeat_until: None,
+            repeat_count: None,
+            clear_repeat_limit: false,
+            location: event.location.clone(),
+            attendees: None,
+            notification_enabled: None,
+            expected_updated_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn update_event_keeps_multi_day_span_when_end_date_absent() {
+        let db = test_db().await;
+        let event = db
+            .create_event(CreateEventRequest { end_date: Some("2026-01-07".to_string()), ..event_request("年会", "2026-01-05") })
+            .await
+            .unwrap();
+
+        let updated = db.update_event(UpdateEventRequest { title: "年度大会".to_string(), ..event_edit(&event) }).await.unwrap();
+        assert_eq!(updated.end_date.as_deref(), Some("2026-01-07"));
+
+        let moved = db.update_event(UpdateEventRequest { date: "2026-01-10".to_string(), ..event_edit(&event) }).await.unwrap();
+        assert_eq!(moved.end_date.as_deref(), Some("2026-01-12"));
+
+        let cleared = db.update_event(UpdateEventRequest { clear_end_date: true, ..event_edit(&moved) }).await.unwrap();
+        assert_eq!(cleared.end_date, None);
+    }
+
+    #[tokio::test]
+    async fn list_queries_survive_garbage_json() {
+        let db = test_db().await;
+        let todo = db.create_todo(todo_request("坏标签")).await.unwrap();
+        db.create_todo(todo_request("正常待办")).await.unwrap();
+        let event = db.create_event(event_request("坏参与人", "2026-01-05")).await.unwrap();
+
+        sqlx::query("UPDATE todos SET tags = '{not json' WHERE id = ?")
+            .bind(&todo.id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE calendar_events SET attendees = '[1, 2' WHERE id = ?")
+            .bind(&event.id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let todos = db.get_all_todos(None).await.unwrap();
+        assert_eq!(todos.len(), 2);
+        let broken = todos.iter().find(|t| t.id == todo.id).unwrap();
+        assert_eq!(broken.tags.as_deref(), Some("[]"));
+
+        let events = db.get_all_events().await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].attendees.as_deref(), Some("[]"));
+    }
+
+    #[tokio::test]
+    async fn create_subtask_rejects_unknown_todo() {
+        let db = test_db().await;
+
+        let error = db
+            .create_subtask(CreateSubtaskRequest { todo_id: "missing".to_string(), title: "孤立的子任务".to_string() })
+            .await
+            .unwrap_err();
+        let not_found = error.downcast_ref::<crate::error::NotFoundError>().unwrap();
+        assert_eq!(not_found.id, "missing");
+
+        let count: i64 = sqlx::query("SELECT COUNT(*) AS count FROM subtasks").fetch_one(&db.pool).await.unwrap().get("count");
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn upgrade_attendee_entries_keeps_unrecognized_entries() {
+        let entries = vec![
+            serde_json::json!("张三"),
+            serde_json::json!({ "name": "李四", "email": "li@example.com", "status": "accepted" }),
+            serde_json::json!(42),
+            serde_json::json!({ "nickname": "王五" }),
+        ];
+
+        let upgraded = upgrade_attendee_entries(entries, "event");
+        assert_eq!(upgraded.len(), 4);
+        assert_eq!(upgraded[0], serde_json::json!({ "name": "张三", "email": null, "status": "unknown" }));
+        assert_eq!(upgraded[1]["name"], "李四");
+        assert_eq!(upgraded[2], serde_json::json!(42));
+        assert_eq!(upgraded[3], serde_json::json!({ "nickname": "王五" }));
+    }
+
+    fn habit_with_color(color: &str) -> Habit {
+        Habit {
+            id: Uuid::new_v4().to_string(),
+            name: "习惯".to_string(),
+            description: None,
+            category: "health".to_string(),
+            color: color.to_string(),
+            target: 1,
+            unit: "次".to_string(),
+            frequency: "daily".to_string(),
+            is_active: true,
+            skip_weekends: false,
+            weekly_target: None,
+            value_based: false,
+            current_streak: 0,
+            longest_streak: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn next_habit_color_picks_least_used() {
+        assert_eq!(next_habit_color(&[]), HABIT_COLOR_PALETTE[0]);
+
+        let existing = [habit_with_color(HABIT_COLOR_PALETTE[0]), habit_with_color(HABIT_COLOR_PALETTE[1])];
+        assert_eq!(next_habit_color(&existing), HABIT_COLOR_PALETTE[2]);
+
+        // 每种颜色都用过一次，第一种用过两次时取第二种
+        let mut existing: Vec<Habit> = HABIT_COLOR_PALETTE.iter().map(|color| habit_with_color(color)).collect();
+        existing.push(habit_with_color(HABIT_COLOR_PALETTE[0]));
+        assert_eq!(next_habit_color(&existing), HABIT_COLOR_PALETTE[1]);
+    }
+
+    #[test]
+    fn next_habit_color_ignores_case_and_custom_colors() {
+        let existing = [habit_with_color(&HABIT_COLOR_PALETTE[0].to_uppercase()), habit_with_color("#123456")];
+        assert_eq!(next_habit_color(&existing), HABIT_COLOR_PALETTE[1]);
+    }
+
+    #[tokio::test]
+    async fn create_habit_assigns_palette_color_when_empty() {
+        let db = test_db().await;
+
+        let first = db.create_habit(habit_request("阅读")).await.unwrap();
+        let second = db.create_habit(habit_request("冥想")).await.unwrap();
+        assert_eq!(first.color, HABIT_COLOR_PALETTE[0]);
+        assert_eq!(second.color, HABIT_COLOR_PALETTE[1]);
+    }
+
+    // 另开一个连接持有写锁；busy_timeout 为 0 的连接池遇到锁会立即返回 SQLITE_BUSY，而不是在 SQLite 内部等待
+    async fn lock_database(path: &str) -> (SqliteConnection, SqlitePool) {
+        let mut locker = SqliteConnection::connect(&format!("sqlite://{}", path)).await.unwrap();
+        sqlx::query("BEGIN IMMEDIATE").execute(&mut locker).await.unwrap();
+        let impatient = SqlitePool::connect_with(SqliteConnectOptions::new().filename(path).busy_timeout(std::time::Duration::ZERO))
+            .await
+            .unwrap();
+        (locker, impatient)
+    }
+
+    #[tokio::test]
+    async fn locked_database_is_reported_as_busy() {
+        let path = test_db_path();
+        let _db = DatabaseService::open(&path).await.unwrap();
+        let (_locker, impatient) = lock_database(&path).await;
+
+        let error = sqlx::query("INSERT INTO app_settings (key, value, updated_at) VALUES ('busy', '1', '')")
+            .execute(&impatient)
+            .await
+            .unwrap_err();
+        let error: Box<dyn std::error::Error> = Box::new(error);
+        assert!(is_busy_error(error.as_ref()));
+    }
+
+    #[tokio::test]
+    async fn busy_writes_succeed_once_lock_is_released() {
+        let path = test_db_path();
+        let db = DatabaseService::open(&path).await.unwrap();
+        let (mut locker, impatient) = lock_database(&path).await;
+
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(120)).await;
+            sqlx::query("COMMIT").execute(&mut locker).await.unwrap();
+        });
+
+        let result = sqlx::query("INSERT INTO app_settings (key, value, updated_at) VALUES ('contended', '1', '')")
+            .execute_with_retry(&impatient)
+            .await;
+        release.await.unwrap();
+
+        assert_eq!(result.unwrap().rows_affected(), 1);
+        assert_eq!(db.get_app_setting("contended").await.unwrap(), Some("1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn busy_writes_give_up_with_clear_error() {
+        let path = test_db_path();
+        let _db = DatabaseService::open(&path).await.unwrap();
+        let (_locker, impatient) = lock_database(&path).await;
+
+        let error = sqlx::query("INSERT INTO app_settings (key, value, updated_at) VALUES ('never', '1', '')")
+            .execute_with_retry(&impatient)
+            .await
+            .unwrap_err();
+        assert!(error.is::<crate::error::DatabaseBusyError>());
+    }
+
+    async fn keys_matching(db: &DatabaseService, operator: &str, keys: &[String]) -> Vec<String> {
+        let mut builder = QueryBuilder::<Sqlite>::new(format!("SELECT key FROM app_settings WHERE key LIKE 'id\\_%' ESCAPE '\\' AND key {} ", operator));
+        push_id_list(&mut builder, keys);
+        builder.push(" ORDER BY key");
+        builder
+            .build()
+            .fetch_all(&db.pool)
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get::<String, _>("key"))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn push_id_list_binds_ids_containing_quotes() {
+        let db = test_db().await;
+        for key in ["id_o'brien", "id_plain"] {
+            db.set_app_setting(key, "1").await.unwrap();
+        }
+
+        let keys = vec!["id_o'brien".to_string(), "x') OR ('1'='1".to_string()];
+        assert_eq!(keys_matching(&db, "IN", &keys).await, vec!["id_o'brien"]);
+        assert_eq!(keys_matching(&db, "NOT IN", &keys).await, vec!["id_plain"]);
+    }
+
+    #[tokio::test]
+    async fn push_id_list_handles_empty_list() {
+        let db = test_db().await;
+        db.set_app_setting("id_plain", "1").await.unwrap();
+
+        assert!(keys_matching(&db, "IN", &[]).await.is_empty());
+        assert_eq!(keys_matching(&db, "NOT IN", &[]).await, vec!["id_plain"]);
+    }
+
+    fn note_request(title: &str) -> CreateNoteRequest {
+        CreateNoteRequest {
+            title: title.to_string(),
+            content: "内容".to_string(),
+            tags: Some(vec!["工作".to_string()]),
+            category: "general".to_string(),
+            color: "#ffffff".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn delete_note_returns_removed_note() {
+        let db = test_db().await;
+        let note = db.create_note(note_request("会议纪要")).await.unwrap();
+
+        let deleted = db.delete_note(&note.id).await.unwrap().unwrap();
+        assert_eq!(deleted.id, note.id);
+        assert_eq!(deleted.title, "会议纪要");
+        assert_eq!(deleted.content, note.content);
+        assert_eq!(deleted.tags, note.tags);
+        assert_eq!(deleted.created_at, note.created_at);
+
+        assert!(db.get_note(&note.id).await.is_err());
+        assert!(db.delete_note(&note.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn delete_todo_returns_removed_todo() {
+        let db = test_db().await;
+        let todo = db
+            .create_todo(CreateTodoRequest { description: Some("描述".to_string()), ..todo_request("写周报") })
+            .await
+            .unwrap();
+
+        let deleted = db.delete_todo(&todo.id).await.unwrap().unwrap();
+        assert_eq!(deleted.id, todo.id);
+        assert_eq!(deleted.title, "写周报");
+        assert_eq!(deleted.description.as_deref(), Some("描述"));
+        assert_eq!(deleted.priority, todo.priority);
+
+        assert!(db.get_todo(&todo.id).await.is_err());
+        assert!(db.delete_todo(&todo.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn delete_event_returns_removed_event() {
+        let db = test_db().await;
+        let event = db.create_event(event_request("评审会", "2026-01-05")).await.unwrap();
+
+        let deleted = db.delete_event(&event.id).await.unwrap().unwrap();
+        assert_eq!(deleted.id, event.id);
+        assert_eq!(deleted.title, "评审会");
+        assert_eq!(deleted.date, "2026-01-05");
+        assert_eq!(deleted.start_time, event.start_time);
+
+        assert!(db.get_event(&event.id).await.is_err());
+        assert!(db.delete_event(&event.id).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn parse_frequency_accepts_builtin_strings() {
+        assert_eq!(parse_frequency("daily").unwrap(), HabitFrequency::Daily);
+        assert_eq!(parse_frequency(" weekly ").unwrap(), HabitFrequency::Weekly);
+    }
+
+    #[test]
+    fn parse_frequency_accepts_custom_days() {
+        let frequency = parse_frequency(r#"{"type":"custom","days":[5,1,3,1]}"#).unwrap();
+        assert_eq!(frequency, HabitFrequency::Custom(vec![Weekday::Mon, Weekday::Wed, Weekday::Fri]));
+
+        let sunday = parse_frequency(r#"{"type":"custom","days":[7]}"#).unwrap();
+        assert_eq!(sunday, HabitFrequency::Custom(vec![Weekday::Sun]));
+    }
+
+    #[test]
+    fn parse_frequency_rejects_invalid_specs() {
+        for frequency in [
+            "monthly",
+            "",
+            r#"{"type":"custom","days":[]}"#,
+            r#"{"type":"custom","days":[0]}"#,
+            r#"{"type":"custom","days":[8]}"#,
+            r#"{"type":"every","days":[1]}"#,
+            r#"{"type":"custom"}"#,
+            "{not json",
+        ] {
+            let error = parse_frequency(frequency).unwrap_err();
+            assert!(error.is::<crate::error::ValidationError>(), "{}", frequency);
+        }
+    }
+
+    #[test]
+    fn unscheduled_days_are_skipped() {
+        let frequency = r#"{"type":"custom","days":[1,3,5]}"#;
+        // 2026-01-05 是周一
+        assert!(is_scheduled_on(frequency, day("2026-01-05")));
+        assert!(!is_scheduled_on(frequency, day("2026-01-06")));
+        assert!(is_scheduled_on(frequency, day("2026-01-09")));
+        assert!(!is_scheduled_on(frequency, day("2026-01-11")));
+
+        // 读取时无法识别的频率按每日处理
+        assert!(is_scheduled_on("garbage", day("2026-01-06")));
+    }
+
+    #[tokio::test]
+    async fn search_todos_ignores_accents_and_case() {
+        let db = test_db().await;
+        let cafe = db.create_todo(todo_request("Meet at the Café")).await.unwrap();
+        let plain = db.create_todo(todo_request("naive plan")).await.unwrap();
+
+        for query in ["cafe", "CAFE", "Café", "cAfÉ"] {
+            let ids: Vec<String> = db.search_todos(query).await.unwrap().into_iter().map(|todo| todo.id).collect();
+            assert_eq!(ids, vec![cafe.id.clone()], "{}", query);
+        }
+
+        // 查询带重音、数据不带时同样命中
+        let ids: Vec<String> = db.search_todos("Naïve").await.unwrap().into_iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![plain.id.clone()]);
+    }
+
+    #[tokio::test]
+    async fn search_notes_ignores_accents_and_case() {
+        let db = test_db().await;
+        let resume = db.create_note(note_request("Résumé Draft")).await.unwrap();
+        db.create_note(note_request("Groceries")).await.unwrap();
+
+        for query in ["resume", "RESUME", "résumé", "Resume draft"] {
+            let ids: Vec<String> = db.search_notes(query).await.unwrap().into_iter().map(|note| note.id).collect();
+            assert_eq!(ids, vec![resume.id.clone()], "{}", query);
+        }
+    }
+
+    #[tokio::test]
+    async fn upsert_habit_records_bulk_mixes_new_and_existing_dates() {
+        let db = test_db().await;
+        let habit = db.create_habit(habit_request("Read")).await.unwrap();
+        complete_habit_on(&db, &habit.id, "2026-01-05").await;
+        let existing = db.get_habit_records_by_date_range(&habit.id, "2026-01-05", "2026-01-05").await.unwrap().remove(0);
+
+        let cell = |date: &str, completed: bool, note: Option<&str>| CreateHabitRecordRequest {
+            habit_id: habit.id.clone(),
+            date: date.to_string(),
+            completed,
+            value: None,
+            note: note.map(str::to_string),
+            completed_override: true,
+        };
+        let saved = db
+            .upsert_habit_records_bulk(vec![
+                cell("2026-01-06", true, None),
+                cell("2026-01-05", false, Some("生病")),
+                cell("2026-01-07", true, None),
+            ])
+            .await
+            .unwrap();
+
+        // 按传入顺序返回，已有日期沿用原记录 id
+        let dates: Vec<&str> = saved.iter().map(|record| record.date.as_str()).collect();
+        assert_eq!(dates, vec!["2026-01-06", "2026-01-05", "2026-01-07"]);
+        assert_eq!(saved[1].id, existing.id);
+        assert!(!saved[1].completed);
+        assert_eq!(saved[1].note.as_deref(), Some("生病"));
+
+        let stored = db.get_habit_records_by_date_range(&habit.id, "2026-01-01", "2026-01-31").await.unwrap();
+        assert_eq!(stored.len(), 3);
+        let completed: Vec<&str> = stored.iter().filter(|record| record.completed).map(|record| record.date.as_str()).collect();
+        assert_eq!(completed.len(), 2);
+        assert!(!completed.contains(&"2026-01-05"));
+    }
+
+    #[tokio::test]
+    async fn duplicate_habit_record_maps_to_duplicate_error() {
+        let db = test_db().await;
+        let habit = db.create_habit(habit_request("Read")).await.unwrap();
+        complete_habit_on(&db, &habit.id, "2026-01-05").await;
+
+        let error = db
+            .create_habit_record(CreateHabitRecordRequest {
+                habit_id: habit.id.clone(),
+                date: "2026-01-05".to_string(),
+                completed: false,
+                value: None,
+                note: None,
+                completed_override: false,
+            })
+            .await
+            .unwrap_err();
+
+        let duplicate = error.downcast_ref::<crate::error::DuplicateError>().expect("应映射为 DuplicateError");
+        assert_eq!(duplicate.fields, vec!["habit_id".to_string(), "date".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn add_habit_pause_rejects_overlapping_ranges() {
+        let db = test_db().await;
+        let habit = db.create_habit(habit_request("Run")).await.unwrap();
+        db.add_habit_pause(&habit.id, "2026-01-10", "2026-01-15").await.unwrap();
+
+        for (start, end) in [
+            ("2026-01-08", "2026-01-10"), // 结束日与已有开始日重合
+            ("2026-01-15", "2026-01-20"), // 开始日与已有结束日重合
+            ("2026-01-12", "2026-01-13"), // 完全包含在已有区间内
+            ("2026-01-01", "2026-01-31"), // 完全覆盖已有区间
+        ] {
+            let error = db.add_habit_pause(&habit.id, start, end).await.unwrap_err();
+            assert!(error.is::<crate::error::ValidationError>(), "{} ~ {}", start, end);
+        }
+
+        // 紧邻的区间和其他习惯的区间不算重叠
+        db.add_habit_pause(&habit.id, "2026-01-16", "2026-01-18").await.unwrap();
+        let other = db.create_habit(habit_request("Swim")).await.unwrap();
+        db.add_habit_pause(&other.id, "2026-01-12", "2026-01-13").await.unwrap();
+
+        let pauses: Vec<(String, String)> = db
+            .get_habit_pauses(&habit.id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|pause| (pause.start_date, pause.end_date))
+            .collect();
+        assert_eq!(
+            pauses,
+            vec![
+                ("2026-01-10".to_string(), "2026-01-15".to_string()),
+                ("2026-01-16".to_string(), "2026-01-18".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn undo_delete_habit_restores_pauses() {
+        let db = test_db().await;
+        let habit = db.create_habit(habit_request("Run")).await.unwrap();
+        let pause = db.add_habit_pause(&habit.id, "2026-01-10", "2026-01-15").await.unwrap();
+
+        db.delete_habit(&habit.id).await.unwrap();
+        assert!(db.get_habit_pauses(&habit.id).await.unwrap().is_empty());
+
+        db.undo_last().await.unwrap().unwrap();
+        let pauses = db.get_habit_pauses(&habit.id).await.unwrap();
+        assert_eq!(pauses.len(), 1);
+        assert_eq!(pauses[0].id, pause.id);
+    }
+
+    #[tokio::test]
+    async fn backup_round_trips_habit_pauses() {
+        let db = test_db().await;
+        let habit = db.create_habit(habit_request("Run")).await.unwrap();
+        db.add_habit_pause(&habit.id, "2026-01-10", "2026-01-15").await.unwrap();
+        let path = test_db_path().replace(".db", ".json");
+
+        db.export_backup(&path).await.unwrap();
+        db.import_backup(&path, ImportMode::Replace).await.unwrap();
+
+        let pauses = db.get_habit_pauses(&habit.id).await.unwrap();
+        assert_eq!(pauses.len(), 1);
+        assert_eq!((pauses[0].start_date.as_str(), pauses[0].end_date.as_str()), ("2026-01-10", "2026-01-15"));
+    }
+
+    #[test]
+    fn version_one_backup_without_new_tables_is_valid() {
+        let mut backup = serde_json::json!({ "schema_version": 1, "exported_at": Utc::now() });
+        for table in BACKUP_TABLES {
+            if backup_table_required(table, 1) {
+                backup[table] = serde_json::json!([]);
+            }
+        }
+
+        let (validation, parsed) = validate_backup_json(&backup.to_string());
+        assert!(validation.valid, "{:?}", validation.errors);
+        let parsed = parsed.unwrap();
+        assert!(parsed.habit_pauses.is_empty());
+        assert!(parsed.note_templates.is_empty());
+        assert!(parsed.entity_links.is_empty());
+
+        // 当前版本的备份必须包含新增的表
+        backup["schema_version"] = serde_json::json!(BACKUP_SCHEMA_VERSION);
+        let (validation, _) = validate_backup_json(&backup.to_string());
+        for (table, _) in BACKUP_TABLES_SINCE {
+            assert!(validation.errors.iter().any(|error| error.contains(table)), "{}", table);
+        }
+    }
+
+    // 依次记录一个会话并按 minute 设置结束时间，保证排序确定
+    async fn finish_session(db: &DatabaseService, session_type: &str, interrupted: bool, minute: i64) -> PomodoroSession {
+        let session = db
+            .create_pomodoro_session(CreatePomodoroSessionRequest {
+                session_type: session_type.to_string(),
+                duration: 1500,
+                task_title: None,
+                notes: None,
+                date: "2026-01-05".to_string(),
+            })
+            .await
+            .unwrap();
+        let ended_at = "2026-01-05T08:00:00Z".parse::<DateTime<Utc>>().unwrap() + Duration::minutes(minute);
+        db.update_pomodoro_session(UpdatePomodoroSessionRequest {
+            id: session.id,
+            completed: !interrupted,
+            task_title: None,
+            notes: None,
+            ended_at: Some(ended_at),
+            interrupted,
+            interruption_reason: None,
+            session_type: None,
+            duration: None,
+        })
+        .await
+        .unwrap()
+    }
+
+    async fn next_session(db: &DatabaseService) -> (String, i64) {
+        let next = db.get_next_pomodoro_session("2026-01-05").await.unwrap();
+        (next.session_type, next.completed_work_sessions)
+    }
+
+    #[tokio::test]
+    async fn next_pomodoro_session_after_interrupted_work_restarts_work() {
+        let db = test_db().await;
+        assert_eq!(next_session(&db).await, ("work".to_string(), 0));
+
+        finish_session(&db, "work", false, 0).await;
+        assert_eq!(next_session(&db).await, ("short_break".to_string(), 1));
+
+        // 中断的工作番茄不计数，也不会触发休息
+        finish_session(&db, "short_break", false, 5).await;
+        finish_session(&db, "work", true, 10).await;
+        assert_eq!(next_session(&db).await, ("work".to_string(), 1));
+    }
+
+    #[tokio::test]
+    async fn interrupted_sessions_do_not_advance_long_break_cycle() {
+        let db = test_db().await;
+        let mut minute = 0;
+        for interrupted in [false, true, false, true, false] {
+            finish_session(&db, "work", interrupted, minute).await;
+            minute += 5;
+        }
+        // 五个工作会话中只有三个完成，还没到默认的 4 个，只需短休息
+        assert_eq!(next_session(&db).await, ("short_break".to_string(), 3));
+
+        finish_session(&db, "work", false, minute).await;
+        assert_eq!(next_session(&db).await, ("long_break".to_string(), 4));
+
+        // 刚好满 4 个之后又中断一次，应重新开始工作而不是长休息
+        finish_session(&db, "work", true, minute + 5).await;
+        assert_eq!(next_session(&db).await, ("work".to_string(), 4));
     }
 
-    pub async fn get_event(&self, id: &str) -> Result<CalendarEvent, Box<dyn std::error::Error>> {
-        let event = sqlx::query_as::<_, CalendarEvent>(
-            "SELECT id, title, description, date, start_time, end_time, event_type, priority, is_all_day, reminder, repeat_type, location, attendees, created_at, updated_at FROM calendar_events WHERE id = ?"
-        )
-        .bind(id)
-        .fetch_one(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn interrupted_break_is_followed_by_work() {
+        let db = test_db().await;
+        finish_session(&db, "work", false, 0).await;
+        finish_session(&db, "short_break", true, 5).await;
+        assert_eq!(next_session(&db).await, ("work".to_string(), 1));
+    }
 
-        Ok(event)
+    #[tokio::test]
+    async fn cached_streak_matches_computed_streak() {
+        let db = test_db().await;
+        let habit = db.create_habit(habit_request("Read")).await.unwrap();
+        let today = Local::now().date_naive();
+        for offset in 0..3 {
+            complete_habit_on(&db, &habit.id, &(today - Duration::days(offset)).format("%Y-%m-%d").to_string()).await;
+        }
+
+        let cached = db.get_habit(&habit.id).await.unwrap();
+        let computed = db.get_habit_streak(&habit.id, &today.format("%Y-%m-%d").to_string()).await.unwrap();
+        assert_eq!((cached.current_streak, cached.longest_streak), (computed.current_streak, computed.longest_streak));
+        assert_eq!(cached.current_streak, 3);
     }
 
-    pub async fn get_all_events(&self) -> Result<Vec<CalendarEvent>, Box<dyn std::error::Error>> {
-        let events = sqlx::query_as::<_, CalendarEvent>(
-            "SELECT id, title, description, date, start_time, end_time, event_type, priority, is_all_day, reminder, repeat_type, location, attendees, created_at, updated_at FROM calendar_events ORDER BY date, start_time"
-        )
-        .fetch_all(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn stale_streak_cache_is_refreshed_on_read() {
+        let db = test_db().await;
+        let habit = db.create_habit(habit_request("Read")).await.unwrap();
+        let today = Local::now().date_naive();
+        complete_habit_on(&db, &habit.id, &today.format("%Y-%m-%d").to_string()).await;
 
-        Ok(events)
+        // 模拟昨天算出的缓存：跨天后不应继续返回
+        let yesterday = (today - Duration::days(1)).format("%Y-%m-%d").to_string();
+        sqlx::query("UPDATE habits SET current_streak = 99, streak_computed_on = ? WHERE id = ?")
+            .bind(&yesterday)
+            .bind(&habit.id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let habits = db.get_all_habits().await.unwrap();
+        assert_eq!(habits[0].current_streak, 1);
     }
 
-    pub async fn get_events_by_date_range(&self, start_date: &str, end_date: &str) -> Result<Vec<CalendarEvent>, Box<dyn std::error::Error>> {
-        let events = sqlx::query_as::<_, CalendarEvent>(
-            "SELECT id, title, description, date, start_time, end_time, event_type, priority, is_all_day, reminder, repeat_type, location, attendees, created_at, updated_at FROM calendar_events WHERE date >= ? AND date <= ? ORDER BY date, start_time"
-        )
-        .bind(start_date)
-        .bind(end_date)
-        .fetch_all(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn update_habit_recomputes_streak_cache() {
+        let db = test_db().await;
+        let habit = db.create_habit(habit_request("Read")).await.unwrap();
+        complete_habit_on(&db, &habit.id, &Local::now().date_naive().format("%Y-%m-%d").to_string()).await;
 
-        Ok(events)
+        // 缓存是今天算的但数值错误，只有更新时重新计算才能修正
+        sqlx::query("UPDATE habits SET current_streak = 99 WHERE id = ?")
+            .bind(&habit.id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let updated = db.update_habit(habit_edit(&habit)).await.unwrap();
+        assert_eq!(updated.current_streak, 1);
     }
 
-    pub async fn update_event(&self, request: UpdateEventRequest) -> Result<CalendarEvent, Box<dyn std::error::Error>> {
-        let now = Utc::now();
-        let attendees_json = if let Some(attendees) = &request.attendees {
-            Some(serde_json::to_string(attendees)?)
-        } else {
-            None
-        };
+    #[tokio::test]
+    async fn habit_record_rolls_back_when_streak_cache_update_fails() {
+        let db = test_db().await;
+        let habit = db.create_habit(habit_request("Read")).await.unwrap();
+        let today = Local::now().date_naive().format("%Y-%m-%d").to_string();
 
-        sqlx::query(
-            r#"
-            UPDATE calendar_events SET 
-                title = ?, description = ?, date = ?, start_time = ?, end_time = ?, 
-                event_type = ?, priority = ?, is_all_day = ?, reminder = ?, 
-                repeat_type = ?, location = ?, attendees = ?, updated_at = ?
-            WHERE id = ?
-            "#,
-        )
-        .bind(&request.title)
-        .bind(&request.description)
-        .bind(&request.date)
-        .bind(&request.start_time)
-        .bind(&request.end_time)
-        .bind(&request.event_type)
-        .bind(&request.priority)
-        .bind(request.is_all_day)
-        .bind(request.reminder)
-        .bind(&request.repeat_type)
-        .bind(&request.location)
-        .bind(&attendees_json)
-        .bind(now)
-        .bind(&request.id)
-        .execute(&self.pool)
-        .await?;
+        // 让写回缓存失败：打卡记录不应单独保存下来
+        sqlx::query("CREATE TRIGGER fail_streak_cache BEFORE UPDATE OF current_streak ON habits BEGIN SELECT RAISE(ABORT, 'streak cache failed'); END")
+            .execute(&db.pool)
+            .await
+            .unwrap();
 
-        self.get_event(&request.id).await
+        let result = db.create_habit_record(CreateHabitRecordRequest {
+            habit_id: habit.id.clone(),
+            date: today.clone(),
+            completed: true,
+            value: None,
+            note: None,
+            completed_override: false,
+        })
+        .await;
+        assert!(result.is_err());
+        assert!(db.get_habit_record_by_date(&habit.id, &today).await.unwrap().is_none());
     }
 
-    pub async fn delete_event(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
-        sqlx::query("DELETE FROM calendar_events WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+    #[tokio::test]
+    async fn fresh_database_is_at_current_schema_version() {
+        let db = test_db().await;
+        let health = db.healthcheck().await.unwrap();
+        assert!(health.db_ok);
+        assert_eq!(health.schema_version, SCHEMA_VERSION);
+        assert_eq!(health.pending_migrations, 0);
+    }
 
-        Ok(())
+    #[tokio::test]
+    async fn reopening_old_schema_runs_pending_migrations() {
+        let path = test_db_path();
+        let db = DatabaseService::open(&path).await.unwrap();
+
+        // 模拟版本 1 的数据库：还没有 streak_computed_on 和 todos.is_archived 列
+        sqlx::query("ALTER TABLE habits DROP COLUMN streak_computed_on").execute(&db.pool).await.unwrap();
+        sqlx::query("ALTER TABLE todos DROP COLUMN is_archived").execute(&db.pool).await.unwrap();
+        sqlx::query("PRAGMA user_version = 1").execute(&db.pool).await.unwrap();
+        assert_eq!(db.healthcheck().await.unwrap().pending_migrations, (SCHEMA_VERSION - 1) as usize);
+        db.pool.close().await;
+
+        let db = DatabaseService::open(&path).await.unwrap();
+        let health = db.healthcheck().await.unwrap();
+        assert_eq!((health.schema_version, health.pending_migrations), (SCHEMA_VERSION, 0));
+        let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info('habits')")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert!(columns.contains(&"streak_computed_on".to_string()));
+        let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info('todos')")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert!(columns.contains(&"is_archived".to_string()));
     }
 
-    // 待办事项相关方法
-    pub async fn create_todo(&self, request: CreateTodoRequest) -> Result<Todo, Box<dyn std::error::Error>> {
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
-        let tags_json = if let Some(tags) = &request.tags {
-            Some(serde_json::to_string(tags)?)
-        } else {
-            None
-        };
+    #[tokio::test]
+    async fn migration_dedupes_habit_records_before_adding_unique_index() {
+        let path = test_db_path();
+        let db = DatabaseService::open(&path).await.unwrap();
+        let habit = db.create_habit(habit_request("Run")).await.unwrap();
 
+        // 模拟版本 2 的数据库：habit_records 还没有唯一约束，同一天有两条记录；todos 还没有 is_archived 列
+        sqlx::query("ALTER TABLE todos DROP COLUMN is_archived").execute(&db.pool).await.unwrap();
+        sqlx::query("DROP TABLE habit_records").execute(&db.pool).await.unwrap();
         sqlx::query(
-            r#"
-            INSERT INTO todos (
-                id, title, description, completed, priority, tags, due_date, category, created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
+            "CREATE TABLE habit_records (id TEXT PRIMARY KEY, habit_id TEXT NOT NULL, date TEXT NOT NULL, completed BOOLEAN NOT NULL, value INTEGER, note TEXT, created_at DATETIME NOT NULL)"
         )
-        .bind(&id)
-        .bind(&request.title)
-        .bind(&request.description)
-        .bind(false)
-        .bind(&request.priority)
-        .bind(&tags_json)
-        .bind(&request.due_date)
-        .bind(&request.category)
-        .bind(now)
-        .bind(now)
-        .execute(&self.pool)
-        .await?;
+        .execute(&db.pool)
+        .await
+        .unwrap();
+        for (id, completed, created_at) in [("old", false, "2026-01-05T08:00:00Z"), ("new", true, "2026-01-05T09:00:00Z")] {
+            sqlx::query("INSERT INTO habit_records (id, habit_id, date, completed, created_at) VALUES (?, ?, '2026-01-05', ?, ?)")
+                .bind(id)
+                .bind(&habit.id)
+                .bind(completed)
+                .bind(created_at)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        sqlx::query("PRAGMA user_version = 2").execute(&db.pool).await.unwrap();
+        db.pool.close().await;
 
-        self.get_todo(&id).await
+        let db = DatabaseService::open(&path).await.unwrap();
+        let ids: Vec<String> = sqlx::query_scalar("SELECT id FROM habit_records WHERE habit_id = ?")
+            .bind(&habit.id)
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(ids, vec!["new"]);
+        let inserted = sqlx::query("INSERT INTO habit_records (id, habit_id, date, completed, created_at) VALUES ('dup', ?, '2026-01-05', TRUE, '2026-01-06T00:00:00Z')")
+            .bind(&habit.id)
+            .execute(&db.pool)
+            .await;
+        assert!(inserted.is_err());
     }
 
-    pub async fn get_todo(&self, id: &str) -> Result<Todo, Box<dyn std::error::Error>> {
-        let todo = sqlx::query_as::<_, Todo>(
-            "SELECT id, title, description, completed, priority, tags, due_date, category, created_at, updated_at FROM todos WHERE id = ?"
-        )
-        .bind(id)
-        .fetch_one(&self.pool)
-        .await?;
-
-        Ok(todo)
+    fn note_patch(id: &str, title: &str) -> PatchNoteRequest {
+        PatchNoteRequest {
+            id: id.to_string(),
+            title: Some(title.to_string()),
+            content: None,
+            tags: None,
+            category: None,
+            color: None,
+            is_pinned: None,
+            is_archived: None,
+            expected_updated_at: None,
+        }
     }
 
-    pub async fn get_all_todos(&self) -> Result<Vec<Todo>, Box<dyn std::error::Error>> {
-        let todos = sqlx::query_as::<_, Todo>(
-            "SELECT id, title, description, completed, priority, tags, due_date, category, created_at, updated_at FROM todos ORDER BY created_at DESC"
-        )
-        .fetch_all(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn patch_note_pin_appends_pin_order() {
+        let db = test_db().await;
+        let first = db.create_note(note_request("第一条")).await.unwrap();
+        let second = db.create_note(note_request("第二条")).await.unwrap();
+        db.toggle_note_pin(&first.id).await.unwrap();
 
-        Ok(todos)
+        let pin = PatchNoteRequest { title: None, is_pinned: Some(true), ..note_patch(&second.id, "") };
+        let pinned = db.patch_note(pin).await.unwrap();
+        assert!(pinned.is_pinned);
+        assert_eq!(pinned.pin_order, Some(db.get_note(&first.id).await.unwrap().pin_order.unwrap() + 1));
+
+        let unpin = PatchNoteRequest { title: None, is_pinned: Some(false), ..note_patch(&second.id, "") };
+        assert_eq!(db.patch_note(unpin).await.unwrap().pin_order, None);
     }
 
-    pub async fn update_todo(&self, request: UpdateTodoRequest) -> Result<Todo, Box<dyn std::error::Error>> {
-        let now = Utc::now();
-        let tags_json = if let Some(tags) = &request.tags {
-            Some(serde_json::to_string(tags)?)
-        } else {
-            None
-        };
+    #[tokio::test]
+    async fn patch_note_rejects_stale_expected_updated_at() {
+        let db = test_db().await;
+        let note = db.create_note(note_request("草稿")).await.unwrap();
+        let edited = db.patch_note(note_patch(&note.id, "第一次修改")).await.unwrap();
 
-        sqlx::query(
-            r#"
-            UPDATE todos SET 
-                title = ?, description = ?, completed = ?, priority = ?, 
-                tags = ?, due_date = ?, category = ?, updated_at = ?
-            WHERE id = ?
-            "#,
-        )
-        .bind(&request.title)
-        .bind(&request.description)
-        .bind(request.completed)
-        .bind(&request.priority)
-        .bind(&tags_json)
-        .bind(&request.due_date)
-        .bind(&request.category)
-        .bind(now)
-        .bind(&request.id)
-        .execute(&self.pool)
-        .await?;
+        let error = db
+            .patch_note(PatchNoteRequest { expected_updated_at: Some(note.updated_at), ..note_patch(&note.id, "过期的修改") })
+            .await
+            .unwrap_err();
+        assert!(error.is::<crate::error::ConflictError>());
+        assert_eq!(db.get_note(&note.id).await.unwrap().title, "第一次修改");
 
-        self.get_todo(&request.id).await
+        let patched = db
+            .patch_note(PatchNoteRequest { expected_updated_at: Some(edited.updated_at), ..note_patch(&note.id, "基于最新的修改") })
+            .await
+            .unwrap();
+        assert_eq!(patched.title, "基于最新的修改");
     }
 
-    pub async fn delete_todo(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
-        sqlx::query("DELETE FROM todos WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+    #[tokio::test]
+    async fn patch_note_rejects_whitespace_only_title() {
+        let db = test_db().await;
+        let note = db.create_note(note_request("Plan")).await.unwrap();
 
-        Ok(())
+        let error = db.patch_note(note_patch(&note.id, " \t\n ")).await.unwrap_err();
+        assert!(error.is::<crate::error::ValidationError>());
+        assert_eq!(db.get_note(&note.id).await.unwrap().title, "Plan");
     }
 
-    pub async fn toggle_todo_completion(&self, id: &str) -> Result<Todo, Box<dyn std::error::Error>> {
-        let now = Utc::now();
-        sqlx::query("UPDATE todos SET completed = NOT completed, updated_at = ? WHERE id = ?")
-            .bind(now)
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+    #[tokio::test]
+    async fn patch_note_trims_and_collapses_title() {
+        let db = test_db().await;
+        let note = db.create_note(note_request("Plan")).await.unwrap();
 
-        self.get_todo(id).await
+        let patched = db.patch_note(note_patch(&note.id, "  Weekly   Review \n")).await.unwrap();
+        assert_eq!(patched.title, "Weekly Review");
+        // 规范化后的标题同样用于搜索
+        let found: Vec<String> = db.search_notes("weekly review").await.unwrap().into_iter().map(|note| note.id).collect();
+        assert_eq!(found, vec![note.id.clone()]);
     }
 
-    // 子任务相关方法
-    pub async fn create_subtask(&self, request: CreateSubtaskRequest) -> Result<Subtask, Box<dyn std::error::Error>> {
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
+    #[tokio::test]
+    async fn undo_delete_restores_entity_links() {
+        let db = test_db().await;
+        let note = db.create_note(note_request("Specs")).await.unwrap();
+        let todo = db.create_todo(todo_request("Build it")).await.unwrap();
+        let event = db.create_event(event_request("Review", "2026-01-05")).await.unwrap();
+        db.link_entities("note", &note.id, "todo", &todo.id).await.unwrap();
+        db.link_entities("event", &event.id, "note", &note.id).await.unwrap();
 
-        sqlx::query(
-            "INSERT INTO subtasks (id, todo_id, title, completed, created_at) VALUES (?, ?, ?, ?, ?)"
-        )
-        .bind(&id)
-        .bind(&request.todo_id)
-        .bind(&request.title)
-        .bind(false)
-        .bind(now)
-        .execute(&self.pool)
-        .await?;
+        db.delete_todo(&todo.id).await.unwrap();
+        db.undo_last().await.unwrap().unwrap();
+        assert_eq!(db.get_links("todo", &todo.id).await.unwrap().notes.len(), 1);
 
-        let subtask = sqlx::query_as::<_, Subtask>(
-            "SELECT id, todo_id, title, completed, created_at FROM subtasks WHERE id = ?"
-        )
-        .bind(&id)
-        .fetch_one(&self.pool)
-        .await?;
+        db.delete_event(&event.id).await.unwrap();
+        db.undo_last().await.unwrap().unwrap();
+        assert_eq!(db.get_links("event", &event.id).await.unwrap().notes.len(), 1);
 
-        Ok(subtask)
+        db.delete_note(&note.id).await.unwrap();
+        assert!(db.get_links("todo", &todo.id).await.unwrap().notes.is_empty());
+        db.undo_last().await.unwrap().unwrap();
+        let links = db.get_links("note", &note.id).await.unwrap();
+        assert_eq!((links.todos.len(), links.events.len()), (1, 1));
     }
 
-    pub async fn get_subtasks_by_todo(&self, todo_id: &str) -> Result<Vec<Subtask>, Box<dyn std::error::Error>> {
-        let subtasks = sqlx::query_as::<_, Subtask>(
-            "SELECT id, todo_id, title, completed, created_at FROM subtasks WHERE todo_id = ? ORDER BY created_at"
-        )
-        .bind(todo_id)
-        .fetch_all(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn undo_skips_links_to_entities_deleted_since() {
+        let db = test_db().await;
+        let note = db.create_note(note_request("Specs")).await.unwrap();
+        let todo = db.create_todo(todo_request("Build it")).await.unwrap();
+        db.link_entities("note", &note.id, "todo", &todo.id).await.unwrap();
 
-        Ok(subtasks)
+        db.delete_todo(&todo.id).await.unwrap();
+        // 绕过撤销日志删除便笺，撤销待办删除时关联的另一端已不存在
+        sqlx::query("DELETE FROM notes WHERE id = ?").bind(&note.id).execute(&db.pool).await.unwrap();
+        db.undo_last().await.unwrap().unwrap();
+
+        assert!(db.get_todo(&todo.id).await.is_ok());
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM entity_links").fetch_one(&db.pool).await.unwrap();
+        assert_eq!(count, 0);
     }
 
-    pub async fn toggle_subtask_completion(&self, id: &str) -> Result<Subtask, Box<dyn std::error::Error>> {
-        sqlx::query("UPDATE subtasks SET completed = NOT completed WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+    #[tokio::test]
+    async fn backup_replace_round_trips_entity_links_without_dangling_rows() {
+        let db = test_db().await;
+        let note = db.create_note(note_request("Specs")).await.unwrap();
+        let todo = db.create_todo(todo_request("Build it")).await.unwrap();
+        db.link_entities("note", &note.id, "todo", &todo.id).await.unwrap();
+        let path = test_db_path().replace(".db", ".json");
+        db.export_backup(&path).await.unwrap();
 
-        let subtask = sqlx::query_as::<_, Subtask>(
-            "SELECT id, todo_id, title, completed, created_at FROM subtasks WHERE id = ?"
-        )
-        .bind(id)
-        .fetch_one(&self.pool)
-        .await?;
+        // 备份之后新建的实体和关联在 Replace 导入后都应消失
+        let extra = db.create_todo(todo_request("Later")).await.unwrap();
+        db.link_entities("note", &note.id, "todo", &extra.id).await.unwrap();
+        db.import_backup(&path, ImportMode::Replace).await.unwrap();
 
-        Ok(subtask)
+        let links: Vec<(String, String)> = sqlx::query_as("SELECT source_id, target_id FROM entity_links")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(links, vec![(note.id.clone(), todo.id.clone())]);
     }
 
-    pub async fn delete_subtask(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
-        sqlx::query("DELETE FROM subtasks WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+    #[test]
+    fn parse_event_time_accepts_minutes_and_legacy_seconds() {
+        assert_eq!(parse_event_time("09:30").unwrap(), NaiveTime::from_hms_opt(9, 30, 0).unwrap());
+        assert_eq!(parse_event_time("23:59:30").unwrap(), NaiveTime::from_hms_opt(23, 59, 30).unwrap());
+    }
 
-        Ok(())
+    #[test]
+    fn parse_event_time_rejects_malformed_input() {
+        for time in ["", "noon", "24:00", "12:60", "12-30", "12:30pm", "12:30:00:00"] {
+            let error = parse_event_time(time).unwrap_err();
+            assert!(error.is::<crate::error::ValidationError>(), "{}", time);
+        }
     }
 
-    // 番茄钟会话相关方法
-    pub async fn create_pomodoro_session(&self, request: CreatePomodoroSessionRequest) -> Result<PomodoroSession, Box<dyn std::error::Error>> {
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
+    #[test]
+    fn time_span_minutes_wraps_past_midnight() {
+        assert_eq!(time_span_minutes("09:00", "10:30").unwrap(), 90);
+        assert_eq!(time_span_minutes("09:00", "09:00").unwrap(), 0);
+        assert_eq!(time_span_minutes("22:00", "01:00").unwrap(), 180);
+        assert_eq!(time_span_minutes("23:59", "00:00").unwrap(), 1);
+        assert!(time_span_minutes("22:00", "1am").is_err());
+    }
 
-        sqlx::query(
-            r#"
-            INSERT INTO pomodoro_sessions (
-                id, session_type, duration, completed, task_title, notes, date, created_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(&id)
-        .bind(&request.session_type)
-        .bind(request.duration)
-        .bind(false)
-        .bind(&request.task_title)
-        .bind(&request.notes)
-        .bind(&request.date)
-        .bind(now)
-        .execute(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn event_duration_handles_midnight_and_multi_day_spans() {
+        let db = test_db().await;
 
-        let session = sqlx::query_as::<_, PomodoroSession>(
-            "SELECT id, session_type, duration, completed, task_title, notes, date, started_at, ended_at, created_at FROM pomodoro_sessions WHERE id = ?"
-        )
-        .bind(&id)
-        .fetch_one(&self.pool)
-        .await?;
+        let mut request = event_request("Night shift", "2026-01-05");
+        request.start_time = Some("23:00".to_string());
+        request.end_time = Some("01:00".to_string());
+        assert_eq!(db.create_event(request).await.unwrap().duration_minutes, Some(120));
 
-        Ok(session)
+        // 多日日程按结束日期计算，不再额外加 24 小时
+        let mut request = event_request("Trip", "2026-01-05");
+        request.end_date = Some("2026-01-06".to_string());
+        request.start_time = Some("22:00".to_string());
+        request.end_time = Some("02:00".to_string());
+        assert_eq!(db.create_event(request).await.unwrap().duration_minutes, Some(240));
+
+        let mut request = event_request("Holiday", "2026-01-05");
+        request.is_all_day = true;
+        assert_eq!(db.create_event(request).await.unwrap().duration_minutes, None);
     }
 
-    pub async fn update_pomodoro_session(&self, request: UpdatePomodoroSessionRequest) -> Result<PomodoroSession, Box<dyn std::error::Error>> {
-        sqlx::query(
-            r#"
-            UPDATE pomodoro_sessions SET 
-                completed = ?, task_title = ?, notes = ?, ended_at = ?
-            WHERE id = ?
-            "#,
-        )
-        .bind(request.completed)
-        .bind(&request.task_title)
-        .bind(&request.notes)
-        .bind(&request.ended_at)
-        .bind(&request.id)
-        .execute(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn create_event_rejects_malformed_time() {
+        let db = test_db().await;
+        let mut request = event_request("Standup", "2026-01-05");
+        request.start_time = Some("9h30".to_string());
 
-        let session = sqlx::query_as::<_, PomodoroSession>(
-            "SELECT id, session_type, duration, completed, task_title, notes, date, started_at, ended_at, created_at FROM pomodoro_sessions WHERE id = ?"
-        )
-        .bind(&request.id)
-        .fetch_one(&self.pool)
-        .await?;
+        let error = db.create_event(request).await.unwrap_err();
+        assert!(error.is::<crate::error::ValidationError>());
+    }
 
-        Ok(session)
+    // 两个 general 便笺和一个 ideas 便笺，其中 ideas 已归档
+    async fn notes_with_one_archived(db: &DatabaseService) {
+        db.create_note(note_request("Plan")).await.unwrap();
+        db.create_note(note_request("Review")).await.unwrap();
+        let mut request = note_request("Someday");
+        request.category = "ideas".to_string();
+        let archived = db.create_note(request).await.unwrap();
+        let mut patch = note_patch(&archived.id, "Someday");
+        patch.is_archived = Some(true);
+        db.patch_note(patch).await.unwrap();
     }
 
-    pub async fn get_pomodoro_sessions_by_date(&self, date: &str) -> Result<Vec<PomodoroSession>, Box<dyn std::error::Error>> {
-        let sessions = sqlx::query_as::<_, PomodoroSession>(
-            "SELECT id, session_type, duration, completed, task_title, notes, date, started_at, ended_at, created_at FROM pomodoro_sessions WHERE date = ? ORDER BY created_at"
-        )
-        .bind(date)
-        .fetch_all(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn note_categories_respect_include_archived() {
+        let db = test_db().await;
+        notes_with_one_archived(&db).await;
 
-        Ok(sessions)
+        let counts = |categories: Vec<CategoryCount>| -> Vec<(String, i64)> {
+            categories.into_iter().map(|category| (category.category, category.count)).collect()
+        };
+        assert_eq!(counts(db.get_note_categories(false).await.unwrap()), vec![("general".to_string(), 2)]);
+        assert_eq!(
+            counts(db.get_note_categories(true).await.unwrap()),
+            vec![("general".to_string(), 2), ("ideas".to_string(), 1)]
+        );
     }
 
-    pub async fn get_pomodoro_sessions_by_date_range(&self, start_date: &str, end_date: &str) -> Result<Vec<PomodoroSession>, Box<dyn std::error::Error>> {
-        let sessions = sqlx::query_as::<_, PomodoroSession>(
-            "SELECT id, session_type, duration, completed, task_title, notes, date, started_at, ended_at, created_at FROM pomodoro_sessions WHERE date >= ? AND date <= ? ORDER BY date, created_at"
-        )
-        .bind(start_date)
-        .bind(end_date)
-        .fetch_all(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn note_aggregates_respect_include_archived() {
+        let db = test_db().await;
+        notes_with_one_archived(&db).await;
 
-        Ok(sessions)
+        let today = Local::now().date_naive().format("%Y-%m-%d").to_string();
+        let total = |points: Vec<HistogramPoint>| points.iter().map(|point| point.count).sum::<i64>();
+        assert_eq!(total(db.get_notes_created_histogram(&today, &today, Bucket::Day, false).await.unwrap()), 2);
+        assert_eq!(total(db.get_notes_created_histogram(&today, &today, Bucket::Day, true).await.unwrap()), 3);
+
+        // 报告按 UTC 日期统计，放宽到前后各一天
+        let utc_today = Utc::now().date_naive();
+        let start = (utc_today - Duration::days(1)).format("%Y-%m-%d").to_string();
+        let end = (utc_today + Duration::days(1)).format("%Y-%m-%d").to_string();
+        assert_eq!(db.get_productivity_report(&start, &end, false).await.unwrap().notes_created, 2);
+        assert_eq!(db.get_productivity_report(&start, &end, true).await.unwrap().notes_created, 3);
     }
 
-    // 番茄钟设置相关方法
-    pub async fn get_pomodoro_settings(&self) -> Result<PomodoroSettings, Box<dyn std::error::Error>> {
-        let settings = sqlx::query_as::<_, PomodoroSettings>(
-            "SELECT id, work_time, short_break, long_break, long_break_interval, auto_start_breaks, auto_start_work, notification_enabled, created_at, updated_at FROM pomodoro_settings LIMIT 1"
-        )
-        .fetch_one(&self.pool)
-        .await?;
+    #[test]
+    fn should_take_long_break_at_interval_boundaries() {
+        assert!(!should_take_long_break(0, 4));
+        assert!(!should_take_long_break(3, 4)); // 差一个
+        assert!(should_take_long_break(4, 4)); // 正好达到
+        assert!(!should_take_long_break(5, 4));
+        assert!(!should_take_long_break(7, 4));
+        assert!(should_take_long_break(8, 4)); // 整数倍
+        assert!(should_take_long_break(12, 4));
+    }
 
-        Ok(settings)
+    #[test]
+    fn should_take_long_break_with_degenerate_intervals() {
+        // 间隔为 1 时每个番茄后都长休息；非正数按 1 处理
+        assert!(should_take_long_break(1, 1));
+        assert!(should_take_long_break(3, 0));
+        assert!(should_take_long_break(2, -5));
+        assert!(!should_take_long_break(0, 1));
     }
 
-    pub async fn update_pomodoro_settings(&self, request: UpdatePomodoroSettingsRequest) -> Result<PomodoroSettings, Box<dyn std::error::Error>> {
-        let now = Utc::now();
-        
-        sqlx::query(
-            r#"
-            UPDATE pomodoro_settings SET 
-                work_time = ?, short_break = ?, long_break = ?, long_break_interval = ?,
-                auto_start_breaks = ?, auto_start_work = ?, notification_enabled = ?, updated_at = ?
-            "#,
-        )
-        .bind(request.work_time)
-        .bind(request.short_break)
-        .bind(request.long_break)
-        .bind(request.long_break_interval)
-        .bind(request.auto_start_breaks)
-        .bind(request.auto_start_work)
-        .bind(request.notification_enabled)
-        .bind(now)
-        .execute(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn should_take_long_break_reads_settings_and_today_sessions() {
+        let db = test_db().await;
+        for minute in [0, 5, 10] {
+            finish_session(&db, "work", false, minute).await;
+        }
+        assert!(!db.should_take_long_break("2026-01-05").await.unwrap());
 
-        self.get_pomodoro_settings().await
+        // 中断的番茄不计数
+        finish_session(&db, "work", true, 15).await;
+        assert!(!db.should_take_long_break("2026-01-05").await.unwrap());
+
+        finish_session(&db, "work", false, 20).await;
+        assert!(db.should_take_long_break("2026-01-05").await.unwrap());
+        assert!(!db.should_take_long_break("2026-01-06").await.unwrap());
     }
 
-    // 便笺相关方法
-    pub async fn create_note(&self, request: CreateNoteRequest) -> Result<Note, Box<dyn std::error::Error>> {
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
-        let tags_json = if let Some(tags) = &request.tags {
-            Some(serde_json::to_string(tags)?)
-        } else {
-            None
-        };
+    #[tokio::test]
+    async fn corrupted_file_is_quarantined_and_replaced() {
+        let path = test_db_path();
+        let garbage = "this is not a sqlite database ".repeat(200);
+        tokio::fs::write(&path, &garbage).await.unwrap();
 
-        sqlx::query(
-            r#"
-            INSERT INTO notes (
-                id, title, content, tags, category, color, is_pinned, is_archived, created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(&id)
-        .bind(&request.title)
-        .bind(&request.content)
-        .bind(&tags_json)
-        .bind(&request.category)
-        .bind(&request.color)
-        .bind(false)
-        .bind(false)
-        .bind(now)
-        .bind(now)
-        .execute(&self.pool)
-        .await?;
+        let db = DatabaseService::open(&path).await.unwrap();
+        let status = db.startup_status();
+        assert!(status.recovered_from_corruption);
+        assert!(!status.open_failed);
+        assert!(status.error.is_some());
 
-        self.get_note(&id).await
+        // 原文件原样保留在备份路径，新数据库可以正常使用
+        let backup_path = status.corrupt_backup_path.unwrap();
+        assert_eq!(tokio::fs::read_to_string(&backup_path).await.unwrap(), garbage);
+        assert!(db.healthcheck().await.unwrap().db_ok);
+        db.create_todo(todo_request("After recovery")).await.unwrap();
     }
 
-    pub async fn get_note(&self, id: &str) -> Result<Note, Box<dyn std::error::Error>> {
-        let note = sqlx::query_as::<_, Note>(
-            "SELECT id, title, content, tags, category, color, is_pinned, is_archived, created_at, updated_at FROM notes WHERE id = ?"
-        )
-        .bind(id)
-        .fetch_one(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn non_corruption_open_failure_returns_error() {
+        let path = std::env::temp_dir()
+            .join(format!("productimate-missing-{}", Uuid::new_v4()))
+            .join("toolbox.db");
+        let path = path.to_string_lossy().into_owned();
 
-        Ok(note)
+        // 目录不存在属于普通错误：返回 Err，不当作损坏处理
+        assert!(DatabaseService::open(&path).await.is_err());
+        assert!(!std::path::Path::new(&format!("{}.corrupt", path)).exists());
     }
 
-    pub async fn get_all_notes(&self) -> Result<Vec<Note>, Box<dyn std::error::Error>> {
-        let notes = sqlx::query_as::<_, Note>(
-            "SELECT id, title, content, tags, category, color, is_pinned, is_archived, created_at, updated_at FROM notes WHERE is_archived = FALSE ORDER BY is_pinned DESC, updated_at DESC"
-        )
-        .fetch_all(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn delete_pomodoro_session_removes_it_from_the_day() {
+        let db = test_db().await;
+        let kept = finish_session(&db, "work", false, 0).await;
+        let removed = finish_session(&db, "work", false, 30).await;
+
+        assert!(db.delete_pomodoro_session(&removed.id).await.unwrap());
+        let ids: Vec<String> = db
+            .get_pomodoro_sessions_by_date("2026-01-05")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|session| session.id)
+            .collect();
+        assert_eq!(ids, vec![kept.id]);
 
-        Ok(notes)
+        // 再次删除时没有行被删除
+        assert!(!db.delete_pomodoro_session(&removed.id).await.unwrap());
+        assert_eq!(db.get_daily_pomodoro_progress("2026-01-05").await.unwrap().completed_sessions, 1);
     }
 
-    pub async fn update_note(&self, request: UpdateNoteRequest) -> Result<Note, Box<dyn std::error::Error>> {
-        let now = Utc::now();
-        let tags_json = if let Some(tags) = &request.tags {
-            Some(serde_json::to_string(tags)?)
-        } else {
-            None
-        };
+    #[test]
+    fn is_reversed_range_compares_dates() {
+        assert!(is_reversed_range("2026-01-06", "2026-01-05"));
+        assert!(!is_reversed_range("2026-01-05", "2026-01-05"));
+        assert!(!is_reversed_range("2025-12-31", "2026-01-01"));
+    }
 
-        sqlx::query(
-            r#"
-            UPDATE notes SET 
-                title = ?, content = ?, tags = ?, category = ?, color = ?, 
-                is_pinned = ?, is_archived = ?, updated_at = ?
-            WHERE id = ?
-            "#,
-        )
-        .bind(&request.title)
-        .bind(&request.content)
-        .bind(&tags_json)
-        .bind(&request.category)
-        .bind(&request.color)
-        .bind(request.is_pinned)
-        .bind(request.is_archived)
-        .bind(now)
-        .bind(&request.id)
-        .execute(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn range_queries_return_empty_for_reversed_ranges() {
+        let db = test_db().await;
+        db.create_event(event_request("Standup", "2026-01-05")).await.unwrap();
+        finish_session(&db, "work", false, 0).await;
+        let habit = db.create_habit(habit_request("Read")).await.unwrap();
+        complete_habit_on(&db, &habit.id, "2026-01-05").await;
 
-        self.get_note(&request.id).await
+        // 数据都在 2026-01-05，反向范围覆盖了这一天也应返回空列表而不是错误
+        let (start, end) = ("2026-01-10", "2026-01-01");
+        assert!(db.get_events_by_date_range(start, end).await.unwrap().is_empty());
+        assert!(db.get_pomodoro_sessions_by_date_range(start, end).await.unwrap().is_empty());
+        assert!(db.get_habit_records_by_date_range(&habit.id, start, end).await.unwrap().is_empty());
+        assert!(db.get_habit_records_for_habits(&[habit.id.clone()], start, end).await.unwrap().is_empty());
     }
 
-    pub async fn delete_note(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
-        sqlx::query("DELETE FROM notes WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+    #[tokio::test]
+    async fn range_queries_return_empty_for_ranges_without_data() {
+        let db = test_db().await;
+        db.create_event(event_request("Standup", "2026-01-05")).await.unwrap();
+        finish_session(&db, "work", false, 0).await;
+        let habit = db.create_habit(habit_request("Read")).await.unwrap();
+        complete_habit_on(&db, &habit.id, "2026-01-05").await;
 
-        Ok(())
+        let (start, end) = ("2026-02-01", "2026-02-28");
+        assert!(db.get_events_by_date_range(start, end).await.unwrap().is_empty());
+        assert!(db.get_pomodoro_sessions_by_date_range(start, end).await.unwrap().is_empty());
+        assert!(db.get_habit_records_by_date_range(&habit.id, start, end).await.unwrap().is_empty());
+        assert!(db.get_habit_records_for_habits(&[habit.id.clone()], start, end).await.unwrap().is_empty());
+        assert!(db.get_habit_records_for_habits(&[], "2026-01-01", "2026-01-31").await.unwrap().is_empty());
+
+        // 同样的范围在有数据时确实能查到，说明上面的空结果不是查询本身出错
+        let (start, end) = ("2026-01-01", "2026-01-31");
+        assert_eq!(db.get_events_by_date_range(start, end).await.unwrap().len(), 1);
+        assert_eq!(db.get_habit_records_by_date_range(&habit.id, start, end).await.unwrap().len(), 1);
     }
 
-    pub async fn toggle_note_pin(&self, id: &str) -> Result<Note, Box<dyn std::error::Error>> {
-        let now = Utc::now();
-        sqlx::query("UPDATE notes SET is_pinned = NOT is_pinned, updated_at = ? WHERE id = ?")
-            .bind(now)
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+    async fn occurrence_dates_of(db: &DatabaseService, start: &str, end: &str) -> Vec<String> {
+        db.get_events_by_date_range(start, end).await.unwrap().into_iter().map(|event| event.date).collect()
+    }
 
-        self.get_note(id).await
+    #[tokio::test]
+    async fn repeat_count_caps_expansion() {
+        let db = test_db().await;
+        db.create_event(CreateEventRequest {
+            repeat_type: Some("weekly".to_string()),
+            repeat_count: Some(3),
+            ..event_request("周会", "2026-01-05")
+        })
+        .await
+        .unwrap();
+
+        let dates = occurrence_dates_of(&db, "2026-01-01", "2026-12-31").await;
+        assert_eq!(dates, vec!["2026-01-05", "2026-01-12", "2026-01-19"]);
     }
 
-    // 习惯相关方法
-    pub async fn create_habit(&self, request: CreateHabitRequest) -> Result<Habit, Box<dyn std::error::Error>> {
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
+    #[tokio::test]
+    async fn repeat_until_caps_expansion() {
+        let db = test_db().await;
+        db.create_event(CreateEventRequest {
+            repeat_type: Some("daily".to_string()),
+            repeat_until: Some("2026-01-07".to_string()),
+            ..event_request("晨跑", "2026-01-05")
+        })
+        .await
+        .unwrap();
 
-        sqlx::query(
-            r#"
-            INSERT INTO habits (
-                id, name, description, category, color, target, unit, frequency, is_active, created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(&id)
-        .bind(&request.name)
-        .bind(&request.description)
-        .bind(&request.category)
-        .bind(&request.color)
-        .bind(request.target)
-        .bind(&request.unit)
-        .bind(&request.frequency)
-        .bind(request.is_active)
-        .bind(now)
-        .bind(now)
-        .execute(&self.pool)
-        .await?;
+        let dates = occurrence_dates_of(&db, "2026-01-01", "2026-01-31").await;
+        assert_eq!(dates, vec!["2026-01-05", "2026-01-06", "2026-01-07"]);
+    }
 
-        self.get_habit(&id).await
+    #[tokio::test]
+    async fn earlier_repeat_limit_wins() {
+        let db = test_db().await;
+        // 次数先到
+        db.create_event(CreateEventRequest {
+            repeat_type: Some("weekly".to_string()),
+            repeat_until: Some("2026-03-01".to_string()),
+            repeat_count: Some(2),
+            ..event_request("周会", "2026-01-05")
+        })
+        .await
+        .unwrap();
+        // 结束日期先到
+        db.create_event(CreateEventRequest {
+            repeat_type: Some("weekly".to_string()),
+            repeat_until: Some("2026-01-13".to_string()),
+            repeat_count: Some(10),
+            ..event_request("周会", "2026-01-06")
+        })
+        .await
+        .unwrap();
+
+        let dates = occurrence_dates_of(&db, "2026-01-01", "2026-12-31").await;
+        assert_eq!(dates, vec!["2026-01-05", "2026-01-06", "2026-01-12", "2026-01-13"]);
     }
 
-    pub async fn get_habit(&self, id: &str) -> Result<Habit, Box<dyn std::error::Error>> {
-        let habit = sqlx::query_as::<_, Habit>(
-            "SELECT id, name, description, category, color, target, unit, frequency, is_active, created_at, updated_at FROM habits WHERE id = ?"
-        )
-        .bind(id)
-        .fetch_one(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn update_event_keeps_repeat_limits_unless_cleared() {
+        let db = test_db().await;
+        let event = db
+            .create_event(CreateEventRequest {
+                repeat_type: Some("daily".to_string()),
+                repeat_until: Some("2026-01-20".to_string()),
+                repeat_count: Some(5),
+                ..event_request("晨跑", "2026-01-05")
+            })
+            .await
+            .unwrap();
 
-        Ok(habit)
+        let updated = db.update_event(UpdateEventRequest { title: "夜跑".to_string(), ..event_edit(&event) }).await.unwrap();
+        assert_eq!(updated.repeat_until.as_deref(), Some("2026-01-20"));
+        assert_eq!(updated.repeat_count, Some(5));
+
+        let cleared = db.update_event(UpdateEventRequest { clear_repeat_limit: true, ..event_edit(&updated) }).await.unwrap();
+        assert_eq!(cleared.repeat_until, None);
+        assert_eq!(cleared.repeat_count, None);
     }
 
-    pub async fn get_all_habits(&self) -> Result<Vec<Habit>, Box<dyn std::error::Error>> {
-        let habits = sqlx::query_as::<_, Habit>(
-            "SELECT id, name, description, category, color, target, unit, frequency, is_active, created_at, updated_at FROM habits ORDER BY created_at"
-        )
-        .fetch_all(&self.pool)
-        .await?;
+    #[test]
+    fn render_note_template_substitutes_placeholders() {
+        assert_eq!(render_note_template("站会 {{date}}", "", "2026-01-05"), "站会 2026-01-05");
+        assert_eq!(
+            render_note_template("# {{title}}\n{{date}} / {{date}}", "站会", "2026-01-05"),
+            "# 站会\n2026-01-05 / 2026-01-05"
+        );
+        // 未知占位符和普通花括号原样保留
+        assert_eq!(render_note_template("{{author}} {x}", "站会", "2026-01-05"), "{{author}} {x}");
+        assert_eq!(render_note_template("{{ date }}", "站会", "2026-01-05"), "{{ date }}");
+    }
 
-        Ok(habits)
+    #[tokio::test]
+    async fn backup_round_trips_note_templates() {
+        let db = test_db().await;
+        let template = db
+            .create_note_template(CreateNoteTemplateRequest {
+                name: "站会 {{date}}".to_string(),
+                content: "# {{title}}".to_string(),
+                tags: Some(vec!["站会".to_string()]),
+                category: "general".to_string(),
+                color: "#ffffff".to_string(),
+            })
+            .await
+            .unwrap();
+        let path = test_db_path().replace(".db", ".json");
+
+        let counts = db.export_backup(&path).await.unwrap();
+        assert!(counts.iter().any(|count| count.table == "note_templates" && count.rows == 1));
+        db.import_backup(&path, ImportMode::Replace).await.unwrap();
+
+        let restored = db.get_note_template(&template.id).await.unwrap();
+        assert_eq!((restored.name, restored.content, restored.tags), (template.name, template.content, template.tags));
     }
 
-    pub async fn update_habit(&self, request: UpdateHabitRequest) -> Result<Habit, Box<dyn std::error::Error>> {
-        let now = Utc::now();
+    async fn todo_with_subtasks(db: &DatabaseService, auto_complete: bool) -> (Todo, Vec<Subtask>) {
+        let todo = db
+            .create_todo(CreateTodoRequest { auto_complete_on_subtasks: auto_complete, ..todo_request("发布") })
+            .await
+            .unwrap();
+        let mut subtasks = Vec::new();
+        for title in ["写说明", "打标签"] {
+            subtasks.push(
+                db.create_subtask(CreateSubtaskRequest { todo_id: todo.id.clone(), title: title.to_string() })
+                    .await
+                    .unwrap(),
+            );
+        }
+        (todo, subtasks)
+    }
 
-        sqlx::query(
-            r#"
-            UPDATE habits SET 
-                name = ?, description = ?, category = ?, color = ?, target = ?, 
-                unit = ?, frequency = ?, is_active = ?, updated_at = ?
-            WHERE id = ?
-            "#,
-        )
-        .bind(&request.name)
-        .bind(&request.description)
-        .bind(&request.category)
-        .bind(&request.color)
-        .bind(request.target)
-        .bind(&request.unit)
-        .bind(&request.frequency)
-        .bind(request.is_active)
-        .bind(now)
-        .bind(&request.id)
-        .execute(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn completing_last_subtask_completes_parent() {
+        let db = test_db().await;
+        let (todo, subtasks) = todo_with_subtasks(&db, true).await;
 
-        self.get_habit(&request.id).await
+        let first = db.toggle_subtask_completion(&subtasks[0].id).await.unwrap();
+        assert!(first.subtask.completed);
+        assert!(first.todo.is_none());
+        assert!(!db.get_todo(&todo.id).await.unwrap().completed);
+
+        let last = db.toggle_subtask_completion(&subtasks[1].id).await.unwrap();
+        assert!(last.todo.unwrap().completed);
+        assert!(db.get_todo(&todo.id).await.unwrap().completed);
     }
 
-    pub async fn delete_habit(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
-        sqlx::query("DELETE FROM habits WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+    #[tokio::test]
+    async fn uncompleting_subtask_reopens_parent() {
+        let db = test_db().await;
+        let (todo, subtasks) = todo_with_subtasks(&db, true).await;
+        for subtask in &subtasks {
+            db.toggle_subtask_completion(&subtask.id).await.unwrap();
+        }
 
-        Ok(())
+        let reopened = db.toggle_subtask_completion(&subtasks[0].id).await.unwrap();
+        assert!(!reopened.subtask.completed);
+        assert!(!reopened.todo.unwrap().completed);
+        assert!(!db.get_todo(&todo.id).await.unwrap().completed);
     }
 
-    // 习惯记录相关方法
-    pub async fn create_habit_record(&self, request: CreateHabitRecordRequest) -> Result<HabitRecord, Box<dyn std::error::Error>> {
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
+    #[tokio::test]
+    async fn subtasks_leave_parent_alone_without_auto_complete() {
+        let db = test_db().await;
+        let (todo, subtasks) = todo_with_subtasks(&db, false).await;
+        for subtask in &subtasks {
+            assert!(db.toggle_subtask_completion(&subtask.id).await.unwrap().todo.is_none());
+        }
+        assert!(!db.get_todo(&todo.id).await.unwrap().completed);
+    }
 
-        sqlx::query(
-            r#"
-            INSERT INTO habit_records (
-                id, habit_id, date, completed, value, note, created_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(&id)
-        .bind(&request.habit_id)
-        .bind(&request.date)
-        .bind(request.completed)
-        .bind(request.value)
-        .bind(&request.note)
-        .bind(now)
-        .execute(&self.pool)
-        .await?;
+    #[test]
+    fn derive_record_completion_compares_value_with_target() {
+        // 低于、等于、高于目标
+        assert!(!derive_record_completion(true, 5, Some(4), true, false));
+        assert!(derive_record_completion(true, 5, Some(5), false, false));
+        assert!(derive_record_completion(true, 5, Some(6), false, false));
+        // 显式覆盖、没有 value 或非数值型习惯时保持传入的 completed
+        assert!(derive_record_completion(true, 5, Some(4), true, true));
+        assert!(!derive_record_completion(true, 5, Some(6), false, true));
+        assert!(derive_record_completion(true, 5, None, true, false));
+        assert!(!derive_record_completion(false, 5, Some(6), false, false));
+    }
 
-        self.get_habit_record(&id).await
+    #[tokio::test]
+    async fn create_and_update_habit_record_derive_completion() {
+        let db = test_db().await;
+        let habit = db
+            .create_habit(CreateHabitRequest { target: 5, value_based: true, ..habit_request("喝水") })
+            .await
+            .unwrap();
+        let record = |date: &str, value: i32| CreateHabitRecordRequest {
+            habit_id: habit.id.clone(),
+            date: date.to_string(),
+            completed: false,
+            value: Some(value),
+            note: None,
+            completed_override: false,
+        };
+
+        assert!(!db.create_habit_record(record("2026-01-05", 4)).await.unwrap().completed);
+        assert!(db.create_habit_record(record("2026-01-06", 5)).await.unwrap().completed);
+        let above = db.create_habit_record(record("2026-01-07", 6)).await.unwrap();
+        assert!(above.completed);
+
+        // 更新时同样按新值推导，显式覆盖时保持传入的 completed
+        let below = db.update_habit_record(&above.id, true, Some(3), None, false).await.unwrap();
+        assert!(!below.completed);
+        let overridden = db.update_habit_record(&above.id, true, Some(3), None, true).await.unwrap();
+        assert!(overridden.completed);
     }
 
-    pub async fn get_habit_record(&self, id: &str) -> Result<HabitRecord, Box<dyn std::error::Error>> {
-        let record = sqlx::query_as::<_, HabitRecord>(
-            "SELECT id, habit_id, date, completed, value, note, created_at FROM habit_records WHERE id = ?"
-        )
-        .bind(id)
-        .fetch_one(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn value_based_habit_records_derive_completion() {
+        let db = test_db().await;
+        let habit = db
+            .create_habit(CreateHabitRequest { target: 5, value_based: true, ..habit_request("喝水") })
+            .await
+            .unwrap();
+        let record = |date: &str, value: i32, completed_override: bool| CreateHabitRecordRequest {
+            habit_id: habit.id.clone(),
+            date: date.to_string(),
+            completed: completed_override,
+            value: Some(value),
+            note: None,
+            completed_override,
+        };
 
-        Ok(record)
+        let records = db
+            .upsert_habit_records_bulk(vec![
+                record("2026-01-05", 4, false),
+                record("2026-01-06", 5, false),
+                record("2026-01-07", 6, false),
+                record("2026-01-08", 4, true),
+            ])
+            .await
+            .unwrap();
+        let completed: Vec<bool> = records.iter().map(|record| record.completed).collect();
+        assert_eq!(completed, vec![false, true, true, true]);
+
+        // 更新已有记录的值时重新推导
+        let records = db.upsert_habit_records_bulk(vec![record("2026-01-07", 3, false)]).await.unwrap();
+        assert!(!records[0].completed);
     }
 
-    pub async fn get_habit_records_by_habit(&self, habit_id: &str) -> Result<Vec<HabitRecord>, Box<dyn std::error::Error>> {
-        let records = sqlx::query_as::<_, HabitRecord>(
-            "SELECT id, habit_id, date, completed, value, note, created_at FROM habit_records WHERE habit_id = ? ORDER BY date DESC"
-        )
-        .bind(habit_id)
-        .fetch_all(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn run_readonly_query_reads_service_database_and_limits_rows() {
+        let db = test_db().await;
+        db.create_todo(todo_request("只读查询")).await.unwrap();
 
-        Ok(records)
+        let rows = db.run_readonly_query("SELECT title, completed FROM todos").await.unwrap();
+        assert_eq!(rows, vec![serde_json::json!({ "title": "只读查询", "completed": 0 })]);
+
+        let rows = db
+            .run_readonly_query("WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n WHERE x < 1500) SELECT x FROM n")
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), READONLY_QUERY_MAX_ROWS);
+        assert_eq!(rows.last().unwrap()["x"], READONLY_QUERY_MAX_ROWS as i64);
     }
 
-    pub async fn get_habit_records_by_date_range(&self, habit_id: &str, start_date: &str, end_date: &str) -> Result<Vec<HabitRecord>, Box<dyn std::error::Error>> {
-        let records = sqlx::query_as::<_, HabitRecord>(
-            "SELECT id, habit_id, date, completed, value, note, created_at FROM habit_records WHERE habit_id = ? AND date >= ? AND date <= ? ORDER BY date DESC"
-        )
-        .bind(habit_id)
-        .bind(start_date)
-        .bind(end_date)
-        .fetch_all(&self.pool)
-        .await?;
+    #[test]
+    fn validate_readonly_sql_accepts_single_select() {
+        assert_eq!(validate_readonly_sql("SELECT * FROM todos;  ").unwrap(), "SELECT * FROM todos");
+        assert!(validate_readonly_sql("WITH t AS (SELECT 1) SELECT * FROM t").is_ok());
+        // 关键字出现在字符串、标识符和注释里不算
+        assert!(validate_readonly_sql("SELECT 'DELETE' AS \"drop\" FROM notes -- UPDATE").is_ok());
+    }
 
-        Ok(records)
+    #[test]
+    fn validate_readonly_sql_rejects_writes_ddl_pragma_and_attach() {
+        for sql in [
+            "INSERT INTO todos (id) VALUES ('x')",
+            "UPDATE todos SET completed = TRUE",
+            "DELETE FROM notes",
+            "DROP TABLE notes",
+            "CREATE TABLE t (id TEXT)",
+            "ALTER TABLE notes ADD COLUMN x TEXT",
+            "PRAGMA writable_schema = ON",
+            "ATTACH DATABASE 'other.db' AS other",
+            "SELECT 1; DELETE FROM notes",
+            "WITH t AS (DELETE FROM notes RETURNING id) SELECT * FROM t",
+            "",
+        ] {
+            assert!(validate_readonly_sql(sql).is_err(), "{}", sql);
+        }
     }
 
-    pub async fn get_habit_record_by_date(&self, habit_id: &str, date: &str) -> Result<Option<HabitRecord>, Box<dyn std::error::Error>> {
-        let record = sqlx::query_as::<_, HabitRecord>(
-            "SELECT id, habit_id, date, completed, value, note, created_at FROM habit_records WHERE habit_id = ? AND date = ?"
+    #[test]
+    fn parse_retention_policy_accepts_valid_rules() {
+        let policy = parse_retention_policy(
+            r#"{"rules": [
+                {"entity": "todo", "action": "archive", "older_than_days": 30},
+                {"entity": "todo", "action": "purge", "older_than_days": 365},
+                {"entity": "note", "action": "archive", "older_than_days": 90},
+                {"entity": "note", "action": "purge", "older_than_days": 365},
+                {"entity": "pomodoro_session", "action": "purge", "older_than_days": 180}
+            ], "auto_apply": true}"#,
         )
-        .bind(habit_id)
-        .bind(date)
-        .fetch_optional(&self.pool)
-        .await?;
+        .unwrap();
+        assert_eq!(policy.rules.len(), 5);
+        assert!(policy.auto_apply);
 
-        Ok(record)
+        let empty = parse_retention_policy("{}").unwrap();
+        assert!(empty.rules.is_empty());
+        assert!(!empty.auto_apply);
     }
 
-    pub async fn update_habit_record(&self, id: &str, completed: bool, value: Option<i32>, note: Option<String>) -> Result<HabitRecord, Box<dyn std::error::Error>> {
-        sqlx::query(
-            "UPDATE habit_records SET completed = ?, value = ?, note = ? WHERE id = ?"
-        )
-        .bind(completed)
-        .bind(value)
-        .bind(&note)
-        .bind(id)
-        .execute(&self.pool)
-        .await?;
+    #[test]
+    fn parse_retention_policy_rejects_invalid_rules() {
+        for raw in [
+            "not json",
+            r#"{"rules": [], "extra": 1}"#,
+            r#"{"rules": [{"entity": "habit", "action": "purge", "older_than_days": 30}]}"#,
+            r#"{"rules": [{"entity": "todo", "action": "delete", "older_than_days": 30}]}"#,
+            r#"{"rules": [{"entity": "todo", "action": "purge", "older_than_days": -1}]}"#,
+            r#"{"rules": [{"entity": "todo", "action": "purge", "older_than_days": 0}]}"#,
+            r#"{"rules": [{"entity": "pomodoro_session", "action": "archive", "older_than_days": 30}]}"#,
+            r#"{"rules": [
+                {"entity": "note", "action": "archive", "older_than_days": 30},
+                {"entity": "note", "action": "archive", "older_than_days": 60}
+            ]}"#,
+        ] {
+            assert!(parse_retention_policy(raw).is_err(), "{}", raw);
+        }
+    }
 
-        self.get_habit_record(id).await
+    #[tokio::test]
+    async fn retention_archives_old_completed_todos_without_touching_completion_time() {
+        let db = test_db().await;
+        let completed_at = Utc::now() - Duration::days(60);
+        let mut ids = Vec::new();
+        for (title, completed, updated_at) in [("旧的已完成", true, completed_at), ("旧的未完成", false, completed_at), ("新的已完成", true, Utc::now())] {
+            let todo = db.create_todo(todo_request(title)).await.unwrap();
+            sqlx::query("UPDATE todos SET completed = ?, updated_at = ? WHERE id = ?")
+                .bind(completed)
+                .bind(updated_at)
+                .bind(&todo.id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            ids.push(todo.id);
+        }
+        db.set_retention_policy(r#"{"rules": [{"entity": "todo", "action": "archive", "older_than_days": 30}]}"#)
+            .await
+            .unwrap();
+
+        let report = db.apply_retention_now().await.unwrap();
+        assert_eq!(report.results[0].affected, 1);
+
+        let visible: Vec<String> = db.get_all_todos(None).await.unwrap().into_iter().map(|todo| todo.id).collect();
+        assert!(!visible.contains(&ids[0]));
+        assert_eq!(visible.len(), 2);
+        let archived = db.get_archived_todos().await.unwrap();
+        assert_eq!(archived.len(), 1);
+        assert!(archived[0].is_archived);
+        assert_eq!(archived[0].updated_at.timestamp(), completed_at.timestamp());
+
+        let restored = db.unarchive_todo(&ids[0]).await.unwrap();
+        assert!(!restored.is_archived);
+        assert_eq!(db.get_all_todos(None).await.unwrap().len(), 3);
     }
 
-    // pub async fn delete_habit_record(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
-    //     sqlx::query("DELETE FROM habit_records WHERE id = ?")
-    //         .bind(id)
-    //         .execute(&self.pool)
-    //         .await?;
+    #[tokio::test]
+    async fn retention_runs_on_startup_only_with_auto_apply() {
+        let path = test_db_path();
+        let db = DatabaseService::open(&path).await.unwrap();
+        finish_session(&db, "work", false, 0).await;
+        let purge = r#"{"entity": "pomodoro_session", "action": "purge", "older_than_days": 1}"#;
 
-    //     Ok(())
-    // }
+        db.set_retention_policy(&format!(r#"{{"rules": [{}]}}"#, purge)).await.unwrap();
+        db.pool.close().await;
+        let db = DatabaseService::open(&path).await.unwrap();
+        assert_eq!(db.get_pomodoro_sessions_by_date("2026-01-05").await.unwrap().len(), 1);
 
-    pub async fn get_or_create_habit_record(&self, habit_id: &str, date: &str) -> Result<HabitRecord, Box<dyn std::error::Error>> {
-        // 首先尝试获取现有记录
-        let existing_record = sqlx::query_as::<_, HabitRecord>(
-            "SELECT id, habit_id, date, completed, value, note, created_at FROM habit_records WHERE habit_id = ? AND date = ?"
-        )
-        .bind(habit_id)
-        .bind(date)
-        .fetch_optional(&self.pool)
-        .await?;
+        db.set_retention_policy(&format!(r#"{{"rules": [{}], "auto_apply": true}}"#, purge)).await.unwrap();
+        db.pool.close().await;
+        let db = DatabaseService::open(&path).await.unwrap();
+        assert!(db.get_pomodoro_sessions_by_date("2026-01-05").await.unwrap().is_empty());
+    }
 
-        if let Some(record) = existing_record {
-            Ok(record)
-        } else {
-            // 创建新记录
-            let request = CreateHabitRecordRequest {
-                habit_id: habit_id.to_string(),
-                date: date.to_string(),
-                completed: false,
-                value: None,
-                note: None,
-            };
-            self.create_habit_record(request).await
+    #[tokio::test]
+    async fn month_overview_counts_todos_due_with_time() {
+        let db = test_db().await;
+        for due_date in ["2026-01-31", "2026-01-31 18:00:00", "2026-02-01 09:00:00"] {
+            db.create_todo(CreateTodoRequest { due_date: Some(due_date.to_string()), ..todo_request("交报告") })
+                .await
+                .unwrap();
         }
+
+        let days = db.get_month_overview(2026, 1).await.unwrap();
+        let last = days.iter().find(|day| day.date == "2026-01-31").unwrap();
+        assert_eq!(last.todos_due, 2);
+        assert_eq!(days.iter().map(|day| day.todos_due).sum::<i64>(), 2);
+    }
+
+    #[tokio::test]
+    async fn create_pomodoro_session_rejects_unknown_type() {
+        let db = test_db().await;
+        let error = db
+            .create_pomodoro_session(CreatePomodoroSessionRequest {
+                session_type: "nap".to_string(),
+                duration: 1500,
+                task_title: None,
+                notes: None,
+                date: "2026-01-05".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert!(error.is::<crate::error::ValidationError>());
+        assert!(db.get_pomodoro_sessions_by_date("2026-01-05").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn pomodoro_settings_rejects_second_row() {
+        let db = test_db().await;
+        let now = Utc::now();
+
+        let inserted = sqlx::query("INSERT INTO pomodoro_settings (id, created_at, updated_at) VALUES ('extra', ?, ?)")
+            .bind(now)
+            .bind(now)
+            .execute(&db.pool)
+            .await;
+
+        assert!(inserted.is_err());
+        // 触发器拒绝不应被当作唯一约束冲突
+        let error = map_constraint_error(inserted.unwrap_err());
+        assert!(!error.is::<crate::error::DuplicateError>());
+    }
+
+    #[tokio::test]
+    async fn update_pomodoro_settings_only_changes_canonical_row() {
+        let db = test_db().await;
+        let canonical = db.get_pomodoro_settings().await.unwrap();
+
+        // 模拟触发器出现之前导入的多余行
+        sqlx::query("DROP TRIGGER pomodoro_settings_singleton").execute(&db.pool).await.unwrap();
+        let later = canonical.created_at + Duration::seconds(1);
+        sqlx::query("INSERT INTO pomodoro_settings (id, work_time, created_at, updated_at) VALUES ('extra', 600, ?, ?)")
+            .bind(later)
+            .bind(later)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let updated = db.update_pomodoro_settings(settings_request(1800)).await.unwrap();
+        assert_eq!(updated.id, canonical.id);
+        assert_eq!(updated.work_time, 1800);
+
+        let extra_work_time: i64 = sqlx::query("SELECT work_time FROM pomodoro_settings WHERE id = 'extra'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap()
+            .get("work_time");
+        assert_eq!(extra_work_time, 600);
     }
-}
\ No newline at end of file
+}
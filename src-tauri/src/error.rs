@@ -0,0 +1,180 @@
+use std::fmt;
+
+// 输入校验失败，消息会原样返回给前端
+#[derive(Debug)]
+pub struct ValidationError(pub String);
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "校验失败: {}", self.0)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+pub fn validation_error(message: impl Into<String>) -> Box<dyn std::error::Error> {
+    Box::new(ValidationError(message.into()))
+}
+
+// 引用的记录不存在
+#[derive(Debug)]
+pub struct NotFoundError {
+    pub entity: String,
+    pub id: String,
+}
+
+impl fmt::Display for NotFoundError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "未找到: {} {}", self.entity, self.id)
+    }
+}
+
+impl std::error::Error for NotFoundError {}
+
+pub fn not_found_error(entity: &str, id: &str) -> Box<dyn std::error::Error> {
+    Box::new(NotFoundError {
+        entity: entity.to_string(),
+        id: id.to_string(),
+    })
+}
+
+// 数据库被其他写操作锁定，重试多次后仍未成功
+#[derive(Debug)]
+pub struct DatabaseBusyError;
+
+impl fmt::Display for DatabaseBusyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "数据库繁忙，请稍后重试")
+    }
+}
+
+impl std::error::Error for DatabaseBusyError {}
+
+pub fn database_busy_error() -> Box<dyn std::error::Error> {
+    Box::new(DatabaseBusyError)
+}
+
+// 数据库文件损坏：无法识别为 SQLite 文件，或 PRAGMA quick_check 未通过
+#[derive(Debug)]
+pub struct CorruptDatabaseError(pub String);
+
+impl fmt::Display for CorruptDatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "数据库文件已损坏: {}", self.0)
+    }
+}
+
+impl std::error::Error for CorruptDatabaseError {}
+
+// 违反唯一约束，fields 为能从数据库错误中识别出的列名
+#[derive(Debug)]
+pub struct DuplicateError {
+    pub fields: Vec<String>,
+    pub detail: String,
+}
+
+impl fmt::Display for DuplicateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.fields.is_empty() {
+            write!(f, "重复: {}", self.detail)
+        } else {
+            write!(f, "重复: 已存在相同 {} 的记录", self.fields.join(", "))
+        }
+    }
+}
+
+impl std::error::Error for DuplicateError {}
+
+// SQLITE_CONSTRAINT_PRIMARYKEY / SQLITE_CONSTRAINT_UNIQUE 的扩展错误码。
+// 触发器 RAISE 的 SQLITE_CONSTRAINT_TRIGGER 不算重复，保留原始错误信息
+const DUPLICATE_CONSTRAINT_CODES: [i32; 2] = [1555, 2067];
+
+// 把唯一约束错误转为 DuplicateError，其他错误原样返回；同时接受 sqlx::Error 和重试后返回的 Box<dyn Error>。
+// SQLite 的信息形如 "UNIQUE constraint failed: habit_records.habit_id, habit_records.date"
+pub fn map_constraint_error(error: impl Into<Box<dyn std::error::Error>>) -> Box<dyn std::error::Error> {
+    let error = error.into();
+    if let Some(sqlx::Error::Database(db_error)) = error.downcast_ref::<sqlx::Error>() {
+        let code = db_error.code().and_then(|code| code.parse::<i32>().ok());
+        if code.is_some_and(|code| DUPLICATE_CONSTRAINT_CODES.contains(&code)) {
+            let message = db_error.message();
+            let fields = message
+                .split_once("constraint failed:")
+                .map(|(_, columns)| {
+                    columns
+                        .split(',')
+                        .filter_map(|column| column.trim().rsplit('.').next())
+                        .filter(|column| !column.is_empty())
+                        .map(|column| column.to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+            return Box::new(DuplicateError {
+                fields,
+                detail: message.to_string(),
+            });
+        }
+    }
+
+    error
+}
+
+// 乐观锁冲突，携带数据库中的当前数据供前端合并
+#[derive(Debug)]
+pub struct ConflictError {
+    pub current: serde_json::Value,
+}
+
+impl fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "冲突: 数据已被修改，当前数据: {}", self.current)
+    }
+}
+
+impl std::error::Error for ConflictError {}
+
+pub fn conflict_error<T: serde::Serialize>(current: &T) -> Box<dyn std::error::Error> {
+    Box::new(ConflictError {
+        current: serde_json::to_value(current).unwrap_or(serde_json::Value::Null),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_constraint_error_passes_through_other_errors() {
+        let mapped = map_constraint_error(validation_error("标题不能为空"));
+        assert!(mapped.is::<ValidationError>());
+
+        let mapped = map_constraint_error(sqlx::Error::RowNotFound);
+        assert!(!mapped.is::<DuplicateError>());
+    }
+
+    // 用内存数据库触发真实的唯一约束冲突，检查错误码和字段解析
+    #[tokio::test]
+    async fn unique_violation_maps_to_duplicate_error() {
+        use sqlx::Connection;
+
+        let mut conn = sqlx::SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE habit_records (id TEXT PRIMARY KEY, habit_id TEXT NOT NULL, date TEXT NOT NULL, UNIQUE (habit_id, date))")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        let insert = "INSERT INTO habit_records (id, habit_id, date) VALUES (?, 'h1', '2026-01-05')";
+        sqlx::query(insert).bind("r1").execute(&mut conn).await.unwrap();
+
+        let error = sqlx::query(insert).bind("r2").execute(&mut conn).await.unwrap_err();
+        let mapped = map_constraint_error(error);
+        let duplicate = mapped.downcast_ref::<DuplicateError>().expect("应映射为 DuplicateError");
+        assert_eq!(duplicate.fields, vec!["habit_id".to_string(), "date".to_string()]);
+        assert_eq!(duplicate.to_string(), "重复: 已存在相同 habit_id, date 的记录");
+
+        // 主键冲突同样是重复
+        let error = sqlx::query("INSERT INTO habit_records (id, habit_id, date) VALUES ('r1', 'h2', '2026-01-06')")
+            .execute(&mut conn)
+            .await
+            .unwrap_err();
+        assert!(map_constraint_error(error).is::<DuplicateError>());
+    }
+}
@@ -0,0 +1,420 @@
+// 导入导出相关的通用工具
+
+use crate::models::{CalendarEvent, Note, ProductivityReport};
+use chrono::{NaiveDate, NaiveTime};
+
+const MAX_SLUG_CHARS: usize = 60;
+
+// Google 日历导入时要求的表头，顺序和拼写都不能改
+pub const GOOGLE_CSV_HEADERS: [&str; 8] = [
+    "Subject",
+    "Start Date",
+    "Start Time",
+    "End Date",
+    "End Time",
+    "All Day Event",
+    "Description",
+    "Location",
+];
+
+// 按 RFC 4180 转义单个字段：包含逗号、引号或换行时用引号包裹，内部引号加倍
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn to_csv(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut csv = String::new();
+    let header_line: Vec<String> = headers.iter().map(|h| escape_csv_field(h)).collect();
+    csv.push_str(&header_line.join(","));
+    csv.push_str("\r\n");
+
+    for row in rows {
+        let line: Vec<String> = row.iter().map(|field| escape_csv_field(field)).collect();
+        csv.push_str(&line.join(","));
+        csv.push_str("\r\n");
+    }
+
+    csv
+}
+
+// YYYY-MM-DD 转为 Google 使用的 MM/DD/YYYY，无法解析时原样保留
+fn google_date(date: &str) -> String {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|date| date.format("%m/%d/%Y").to_string())
+        .unwrap_or_else(|_| date.to_string())
+}
+
+// HH:MM 转为 hh:mm AM/PM，无法解析时原样保留
+fn google_time(time: &str) -> String {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .map(|time| time.format("%I:%M %p").to_string())
+        .unwrap_or_else(|_| time.to_string())
+}
+
+// 全天日程不填写时间，单日日程的结束日期与开始日期相同
+pub fn event_to_google_csv_row(event: &CalendarEvent) -> Vec<String> {
+    let time = |value: &Option<String>| {
+        if event.is_all_day {
+            String::new()
+        } else {
+            value.as_deref().map(google_time).unwrap_or_default()
+        }
+    };
+
+    vec![
+        event.title.clone(),
+        google_date(&event.date),
+        time(&event.start_time),
+        google_date(event.end_date.as_deref().unwrap_or(&event.date)),
+        time(&event.end_time),
+        if event.is_all_day { "True" } else { "False" }.to_string(),
+        event.description.clone().unwrap_or_default(),
+        event.location.clone().unwrap_or_default(),
+    ]
+}
+
+pub fn report_to_markdown(report: &ProductivityReport) -> String {
+    let mut markdown = format!("# 效率回顾 {} ~ {}\n\n", report.start_date, report.end_date);
+    markdown.push_str("| 项目 | 数值 |\n| --- | --- |\n");
+    markdown.push_str(&format!("| 完成的待办 | {} |\n", report.todos_completed));
+    markdown.push_str(&format!("| 习惯完成率 | {:.1}% |\n", report.habit_completion_rate * 100.0));
+    markdown.push_str(&format!("| 专注时长 | {} 小时 {} 分钟 |\n", report.focus_minutes / 60, report.focus_minutes % 60));
+    markdown.push_str(&format!("| 日程 | {} |\n", report.events_attended));
+    markdown.push_str(&format!("| 新建便笺 | {} |\n", report.notes_created));
+    markdown
+}
+
+// 由标题生成文件名：保留字母、数字和中日韩文字，其余连续字符（含全角标点）合并为一个 '-'
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug: String = slug.chars().take(MAX_SLUG_CHARS).collect();
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "note".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+// YAML frontmatter 中的字符串用 JSON 转义，JSON 字符串和数组本身就是合法的 YAML
+pub fn note_to_markdown(note: &Note) -> String {
+    let tags: Vec<String> = note
+        .tags
+        .as_deref()
+        .and_then(|tags| serde_json::from_str(tags).ok())
+        .unwrap_or_default();
+    let quote = |value: &str| serde_json::to_string(value).unwrap_or_default();
+
+    let mut markdown = String::from("---\n");
+    markdown.push_str(&format!("title: {}\n", quote(&note.title)));
+    markdown.push_str(&format!("tags: {}\n", serde_json::to_string(&tags).unwrap_or_default()));
+    markdown.push_str(&format!("category: {}\n", quote(&note.category)));
+    markdown.push_str(&format!("created_at: {}\n", quote(&note.created_at.to_rfc3339())));
+    markdown.push_str("---\n\n");
+    markdown.push_str(&note.content);
+    if !note.content.ends_with('\n') {
+        markdown.push('\n');
+    }
+    markdown
+}
+
+// 从 Markdown 文件解析出的便笺，frontmatter 中缺失的字段为空
+#[derive(Debug, Default)]
+pub struct ParsedNote {
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    pub content: String,
+}
+
+// 去掉 YAML 标量两侧的引号，双引号按 JSON 规则反转义
+fn unquote_yaml_scalar(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.starts_with('"') {
+        serde_json::from_str(value).map_err(|e| format!("无效的字符串 {}: {}", value, e))
+    } else if let Some(inner) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        Ok(inner.replace("''", "'"))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+// 按逗号拆分行内列表，引号内的逗号不是分隔符；双引号内按 JSON 规则跳过转义字符
+fn split_inline_list(inner: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (index, c) in inner.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == ',' => {
+                items.push(&inner[start..index]);
+                start = index + 1;
+            }
+            None => {}
+        }
+    }
+    items.push(&inner[start..]);
+    items
+}
+
+// 只支持便笺用到的 YAML 子集：key: value、[a, b] 行内列表和 "- item" 块列表
+pub fn parse_note_markdown(raw: &str) -> Result<ParsedNote, String> {
+    let raw = raw.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let mut parsed = ParsedNote::default();
+
+    let body = match raw.strip_prefix("---\n") {
+        Some(rest) => {
+            let (frontmatter, body) = if let Some(body) = rest.strip_prefix("---\n") {
+                ("", body)
+            } else {
+                match rest.find("\n---\n") {
+                    Some(end) => (&rest[..end], &rest[end + 5..]),
+                    None => match rest.strip_suffix("\n---") {
+                        Some(frontmatter) => (frontmatter, ""),
+                        None => return Err("frontmatter 缺少结束标记 ---".to_string()),
+                    },
+                }
+            };
+
+            let mut current_list: Option<&str> = None;
+            for (index, line) in frontmatter.lines().enumerate() {
+                if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                    continue;
+                }
+                if let Some(item) = line.trim_start().strip_prefix("- ") {
+                    match current_list {
+                        Some("tags") => parsed.tags.push(unquote_yaml_scalar(item)?),
+                        Some(_) => {}
+                        None => return Err(format!("frontmatter 第 {} 行的列表项没有所属字段", index + 1)),
+                    }
+                    continue;
+                }
+
+                let Some((key, value)) = line.split_once(':') else {
+                    return Err(format!("无法解析 frontmatter 第 {} 行: {}", index + 1, line));
+                };
+                let key = key.trim();
+                let value = value.trim();
+                current_list = if value.is_empty() { Some(key) } else { None };
+
+                match key {
+                    "title" if !value.is_empty() => parsed.title = Some(unquote_yaml_scalar(value)?),
+                    "category" if !value.is_empty() => parsed.category = Some(unquote_yaml_scalar(value)?),
+                    "tags" if value.starts_with('[') => {
+                        let inner = value
+                            .strip_prefix('[')
+                            .and_then(|v| v.strip_suffix(']'))
+                            .ok_or_else(|| format!("frontmatter 第 {} 行的列表未闭合", index + 1))?;
+                        for tag in split_inline_list(inner).into_iter().filter(|tag| !tag.trim().is_empty()) {
+                            parsed.tags.push(unquote_yaml_scalar(tag)?);
+                        }
+                    }
+                    "tags" if !value.is_empty() => parsed.tags.push(unquote_yaml_scalar(value)?),
+                    _ => {}
+                }
+            }
+
+            body
+        }
+        None => raw.as_str(),
+    };
+
+    parsed.content = body.trim_start_matches('\n').to_string();
+    parsed.tags.retain(|tag| !tag.trim().is_empty());
+    parsed.title = parsed.title.filter(|title| !title.trim().is_empty());
+    if parsed.title.is_none() {
+        parsed.title = parsed
+            .content
+            .lines()
+            .find_map(|line| line.strip_prefix("# "))
+            .map(|heading| heading.trim().to_string());
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn event(date: &str, start_time: Option<&str>, end_time: Option<&str>, is_all_day: bool) -> CalendarEvent {
+        CalendarEvent {
+            id: "event-1".to_string(),
+            title: "Standup, daily".to_string(),
+            description: Some("say \"hi\"".to_string()),
+            date: date.to_string(),
+            end_date: None,
+            start_time: start_time.map(str::to_string),
+            end_time: end_time.map(str::to_string),
+            event_type: "work".to_string(),
+            priority: "medium".to_string(),
+            is_all_day,
+            reminder: None,
+            repeat_type: None,
+            repeat_until: None,
+            repeat_count: None,
+            location: None,
+            attendees: None,
+            is_favorite: false,
+            notification_enabled: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            duration_minutes: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn google_time_uses_twelve_hour_clock() {
+        assert_eq!(google_time("00:00"), "12:00 AM");
+        assert_eq!(google_time("09:05"), "09:05 AM");
+        assert_eq!(google_time("12:00"), "12:00 PM");
+        assert_eq!(google_time("12:30"), "12:30 PM");
+        assert_eq!(google_time("23:59"), "11:59 PM");
+    }
+
+    #[test]
+    fn google_helpers_keep_unparseable_input() {
+        assert_eq!(google_time("24:00"), "24:00");
+        assert_eq!(google_time("9am"), "9am");
+        assert_eq!(google_time(""), "");
+        assert_eq!(google_date("2026-02-30"), "2026-02-30");
+        assert_eq!(google_date("2026-03-07"), "03/07/2026");
+    }
+
+    #[test]
+    fn google_csv_row_formats_timed_and_all_day_events() {
+        let timed = event_to_google_csv_row(&event("2026-03-07", Some("13:15"), Some("14:00"), false));
+        assert_eq!(timed[1..6], ["03/07/2026", "01:15 PM", "03/07/2026", "02:00 PM", "False"]);
+
+        let all_day = event_to_google_csv_row(&event("2026-03-07", Some("13:15"), None, true));
+        assert_eq!(all_day[2], "");
+        assert_eq!(all_day[4], "");
+        assert_eq!(all_day[5], "True");
+    }
+
+    #[test]
+    fn to_csv_escapes_commas_and_quotes() {
+        let row = event_to_google_csv_row(&event("2026-03-07", None, None, false));
+        let csv = to_csv(&GOOGLE_CSV_HEADERS, &[row]);
+        let line = csv.lines().nth(1).unwrap();
+        assert!(line.starts_with("\"Standup, daily\",03/07/2026,"));
+        assert!(line.contains("\"say \"\"hi\"\"\""));
+    }
+
+    #[test]
+    fn slugify_keeps_cjk_and_collapses_punctuation() {
+        assert_eq!(slugify("会议记录：第一季度"), "会议记录-第一季度");
+        assert_eq!(slugify("日本語のノート"), "日本語のノート");
+        assert_eq!(slugify("한국어 메모"), "한국어-메모");
+        assert_eq!(slugify("周报（草稿）——待审"), "周报-草稿-待审");
+    }
+
+    #[test]
+    fn slugify_ascii_punctuation_and_case() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  --Leading and trailing--  "), "leading-and-trailing");
+        assert_eq!(slugify("a/b\\c:d*e?f"), "a-b-c-d-e-f");
+        assert_eq!(slugify("Café Über"), "café-über");
+    }
+
+    #[test]
+    fn slugify_falls_back_when_nothing_is_left() {
+        assert_eq!(slugify(""), "note");
+        assert_eq!(slugify("!!! ？？？"), "note");
+    }
+
+    #[test]
+    fn slugify_truncates_without_trailing_separator() {
+        assert_eq!(slugify(&"a".repeat(100)).chars().count(), MAX_SLUG_CHARS);
+
+        let title = format!("{} b", "a".repeat(MAX_SLUG_CHARS - 1));
+        assert_eq!(slugify(&title), "a".repeat(MAX_SLUG_CHARS - 1));
+    }
+
+    fn note(title: &str, tags: &[&str], content: &str) -> Note {
+        Note {
+            id: "note-1".to_string(),
+            title: title.to_string(),
+            content: content.to_string(),
+            tags: Some(serde_json::to_string(tags).unwrap()),
+            category: "work".to_string(),
+            color: "#ffffff".to_string(),
+            is_pinned: false,
+            is_archived: false,
+            pin_order: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn parse_note_markdown_reads_frontmatter() {
+        let parsed = parse_note_markdown("---\ntitle: \"站会\"\ncategory: work\n# 注释\ncreated_at: x\n---\n\n正文\n").unwrap();
+        assert_eq!(parsed.title.as_deref(), Some("站会"));
+        assert_eq!(parsed.category.as_deref(), Some("work"));
+        assert!(parsed.tags.is_empty());
+        assert_eq!(parsed.content, "正文\n");
+    }
+
+    #[test]
+    fn parse_note_markdown_reads_block_and_inline_lists() {
+        let block = parse_note_markdown("---\ntags:\n  - a\n  - \"b c\"\n  - 'it''s'\n---\n").unwrap();
+        assert_eq!(block.tags, vec!["a", "b c", "it's"]);
+
+        let inline = parse_note_markdown("---\ntags: [a, \"b, c\", 'd, e', \"say \\\"x, y\\\"\"]\n---\n").unwrap();
+        assert_eq!(inline.tags, vec!["a", "b, c", "d, e", "say \"x, y\""]);
+
+        let single = parse_note_markdown("---\ntags: solo\n---\n").unwrap();
+        assert_eq!(single.tags, vec!["solo"]);
+    }
+
+    #[test]
+    fn note_markdown_round_trips_tags_with_commas() {
+        let markdown = note_to_markdown(&note("站会, 周一", &["a,b", "c"], "# 标题\n内容"));
+        let parsed = parse_note_markdown(&markdown).unwrap();
+        assert_eq!(parsed.title.as_deref(), Some("站会, 周一"));
+        assert_eq!(parsed.tags, vec!["a,b", "c"]);
+        assert_eq!(parsed.category.as_deref(), Some("work"));
+        assert_eq!(parsed.content, "# 标题\n内容\n");
+    }
+
+    #[test]
+    fn parse_note_markdown_rejects_missing_end_marker() {
+        assert!(parse_note_markdown("---\ntitle: a\n正文\n").is_err());
+        assert!(parse_note_markdown("---\ntags: [a, b\n---\n").is_err());
+        assert!(parse_note_markdown("---\n- orphan\n---\n").is_err());
+    }
+
+    #[test]
+    fn parse_note_markdown_falls_back_to_first_heading() {
+        let parsed = parse_note_markdown("前言\n# 周报\n## 细节\n").unwrap();
+        assert_eq!(parsed.title.as_deref(), Some("周报"));
+        assert_eq!(parsed.content, "前言\n# 周报\n## 细节\n");
+
+        let blank_title = parse_note_markdown("---\ntitle: \"\"\n---\n# 标题\n").unwrap();
+        assert_eq!(blank_title.title.as_deref(), Some("标题"));
+
+        assert_eq!(parse_note_markdown("没有标题").unwrap().title, None);
+    }
+}
@@ -2,17 +2,41 @@
 
 mod models;
 mod database;
+mod error;
+mod export;
+mod search;
+mod logging;
+mod quickadd;
+mod validation;
+mod reminders;
 
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tauri::State;
+use tauri::{AppHandle, Emitter, Manager, State};
 use database::DatabaseService;
+use logging::LogLevelHandle;
 use models::*;
 
 type DatabaseState = Arc<Mutex<DatabaseService>>;
 
+// 数据库无法打开时托管这个状态代替 DatabaseState，应用照常启动，由前端显示错误页
+struct DatabaseStartupError(String);
+
+// 数据变更后通知所有窗口刷新，通知失败不影响本次操作
+fn emit_data_changed(app: &AppHandle, entity: &str, action: &str, id: Option<&str>) {
+    let event = DataChangedEvent {
+        entity: entity.to_string(),
+        action: action.to_string(),
+        id: id.map(|id| id.to_string()),
+    };
+    if let Err(e) = app.emit("data-changed", event) {
+        tracing::warn!(entity, action, error = %e, "data-changed 事件发送失败");
+    }
+}
+
 // 日程事件相关命令
 #[tauri::command]
+#[tracing::instrument(skip_all)]
 async fn get_all_events(
     db: State<'_, DatabaseState>,
 ) -> Result<Vec<CalendarEvent>, String> {
@@ -21,6 +45,7 @@ async fn get_all_events(
 }
 
 #[tauri::command]
+#[tracing::instrument(skip_all)]
 async fn get_events_by_date_range(
     start_date: String,
     end_date: String,
@@ -33,380 +58,2076 @@ async fn get_events_by_date_range(
 }
 
 #[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn check_event_buffer(
+    event_id: String,
+    buffer_minutes: i64,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<EventTransition>, String> {
+    let db = db.lock().await;
+    db.check_event_buffer(&event_id, buffer_minutes)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_events_by_type(
+    event_type: String,
+    start_date: String,
+    end_date: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<CalendarEvent>, String> {
+    let db = db.lock().await;
+    db.get_events_by_type(&event_type, &start_date, &end_date)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_upcoming_reminders(
+    from: String,
+    horizon_minutes: i64,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<UpcomingReminder>, String> {
+    let db = db.lock().await;
+    db.get_upcoming_reminders(&from, horizon_minutes)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
 async fn create_event(
+    app: AppHandle,
     request: CreateEventRequest,
     db: State<'_, DatabaseState>,
 ) -> Result<CalendarEvent, String> {
     let db = db.lock().await;
-    db.create_event(request).await.map_err(|e| e.to_string())
+    let event = db.create_event(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "event", "created", Some(&event.id));
+    Ok(event)
 }
 
 #[tauri::command]
+#[tracing::instrument(skip_all)]
 async fn update_event(
+    app: AppHandle,
     request: UpdateEventRequest,
     db: State<'_, DatabaseState>,
 ) -> Result<CalendarEvent, String> {
     let db = db.lock().await;
-    db.update_event(request).await.map_err(|e| e.to_string())
+    let event = db.update_event(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "event", "updated", Some(&event.id));
+    Ok(event)
 }
 
 #[tauri::command]
-async fn delete_event(
+#[tracing::instrument(skip_all)]
+async fn toggle_event_favorite(
+    app: AppHandle,
     id: String,
     db: State<'_, DatabaseState>,
-) -> Result<(), String> {
+) -> Result<CalendarEvent, String> {
     let db = db.lock().await;
-    db.delete_event(&id).await.map_err(|e| e.to_string())
+    let event = db.toggle_event_favorite(&id).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "event", "updated", Some(&event.id));
+    Ok(event)
 }
 
-// 习惯相关命令
 #[tauri::command]
-async fn get_all_habits(
+#[tracing::instrument(skip_all)]
+async fn get_favorite_events(
     db: State<'_, DatabaseState>,
-) -> Result<Vec<Habit>, String> {
+) -> Result<Vec<CalendarEvent>, String> {
     let db = db.lock().await;
-    db.get_all_habits().await.map_err(|e| e.to_string())
+    db.get_favorite_events().await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn create_habit(
-    request: CreateHabitRequest,
+#[tracing::instrument(skip_all)]
+async fn export_events_google_csv(
+    start_date: String,
+    end_date: String,
+    path: String,
     db: State<'_, DatabaseState>,
-) -> Result<Habit, String> {
+) -> Result<usize, String> {
     let db = db.lock().await;
-    db.create_habit(request).await.map_err(|e| e.to_string())
+    db.export_events_google_csv(&start_date, &end_date, &path)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn update_habit(
-    request: UpdateHabitRequest,
+#[tracing::instrument(skip_all)]
+async fn delete_event(
+    app: AppHandle,
+    id: String,
     db: State<'_, DatabaseState>,
-) -> Result<Habit, String> {
+) -> Result<Option<CalendarEvent>, String> {
     let db = db.lock().await;
-    db.update_habit(request).await.map_err(|e| e.to_string())
+    let deleted = db.delete_event(&id).await.map_err(|e| e.to_string())?;
+    if deleted.is_some() {
+        emit_data_changed(&app, "event", "deleted", Some(&id));
+    }
+    Ok(deleted)
 }
 
 #[tauri::command]
-async fn delete_habit(
-    id: String,
+#[tracing::instrument(skip_all)]
+async fn copy_events(
+    app: AppHandle,
+    from_date: String,
+    to_date: String,
     db: State<'_, DatabaseState>,
-) -> Result<(), String> {
+) -> Result<Vec<CalendarEvent>, String> {
     let db = db.lock().await;
-    db.delete_habit(&id).await.map_err(|e| e.to_string())
+    let events = db.copy_events(&from_date, &to_date).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "event", "created", None);
+    Ok(events)
 }
 
-// 习惯打卡记录相关命令
 #[tauri::command]
-async fn get_habit_records_by_date_range(
-    habit_id: String,
-    start_date: String,
-    end_date: String,
+#[tracing::instrument(skip_all)]
+async fn get_week_agenda(
+    date: String,
     db: State<'_, DatabaseState>,
-) -> Result<Vec<HabitRecord>, String> {
+) -> Result<Vec<CalendarEvent>, String> {
     let db = db.lock().await;
-    db.get_habit_records_by_date_range(&habit_id, &start_date, &end_date)
-        .await
-        .map_err(|e| e.to_string())
+    db.get_week_agenda(&date).await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn create_habit_record(
-    request: CreateHabitRecordRequest,
+#[tracing::instrument(skip_all)]
+async fn get_month_overview(
+    year: i32,
+    month: u32,
     db: State<'_, DatabaseState>,
-) -> Result<HabitRecord, String> {
+) -> Result<Vec<DayOverview>, String> {
     let db = db.lock().await;
-    db.create_habit_record(request).await.map_err(|e| e.to_string())
+    db.get_month_overview(year, month).await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn get_habit_record_by_date(
-    habit_id: String,
-    date: String,
+#[tracing::instrument(skip_all)]
+async fn search_events(
+    query: String,
     db: State<'_, DatabaseState>,
-) -> Result<Option<HabitRecord>, String> {
+) -> Result<Vec<CalendarEvent>, String> {
     let db = db.lock().await;
-    db.get_habit_record_by_date(&habit_id, &date)
-        .await
-        .map_err(|e| e.to_string())
+    db.search_events(&query).await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn get_or_create_habit_record(
-    habit_id: String,
+#[tracing::instrument(skip_all)]
+async fn cancel_event_occurrence(
+    app: AppHandle,
+    event_id: String,
     date: String,
     db: State<'_, DatabaseState>,
-) -> Result<HabitRecord, String> {
+) -> Result<EventException, String> {
     let db = db.lock().await;
-    db.get_or_create_habit_record(&habit_id, &date)
+    let exception = db.cancel_event_occurrence(&event_id, &date)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "event", "updated", Some(&exception.event_id));
+    Ok(exception)
 }
 
 #[tauri::command]
-async fn update_habit_record(
-    id: String,
-    completed: bool,
-    value: Option<i32>,
-    note: Option<String>,
+#[tracing::instrument(skip_all)]
+async fn modify_event_occurrence(
+    app: AppHandle,
+    request: ModifyEventOccurrenceRequest,
     db: State<'_, DatabaseState>,
-) -> Result<HabitRecord, String> {
+) -> Result<EventException, String> {
     let db = db.lock().await;
-    db.update_habit_record(&id, completed, value, note)
-        .await
-        .map_err(|e| e.to_string())
+    let exception = db.modify_event_occurrence(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "event", "updated", Some(&exception.event_id));
+    Ok(exception)
 }
 
 #[tauri::command]
-async fn get_habit_records_by_habit(
-    habit_id: String,
+#[tracing::instrument(skip_all)]
+async fn get_event_types(
     db: State<'_, DatabaseState>,
-) -> Result<Vec<HabitRecord>, String> {
+) -> Result<Vec<String>, String> {
     let db = db.lock().await;
-    db.get_habit_records_by_habit(&habit_id)
-        .await
-        .map_err(|e| e.to_string())
+    db.get_event_types().await.map_err(|e| e.to_string())
 }
 
-// 待办事项相关命令
 #[tauri::command]
-async fn get_all_todos(
+#[tracing::instrument(skip_all)]
+async fn update_event_types(
+    app: AppHandle,
+    event_types: Vec<String>,
     db: State<'_, DatabaseState>,
-) -> Result<Vec<Todo>, String> {
+) -> Result<Vec<String>, String> {
     let db = db.lock().await;
-    db.get_all_todos().await.map_err(|e| e.to_string())
+    let event_types = db.update_event_types(event_types).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "event_type", "updated", None);
+    Ok(event_types)
 }
 
+// 习惯相关命令
 #[tauri::command]
-async fn create_todo(
-    request: CreateTodoRequest,
+#[tracing::instrument(skip_all)]
+async fn get_all_habits(
     db: State<'_, DatabaseState>,
-) -> Result<Todo, String> {
+) -> Result<Vec<Habit>, String> {
     let db = db.lock().await;
-    db.create_todo(request).await.map_err(|e| e.to_string())
+    db.get_all_habits().await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn update_todo(
-    request: UpdateTodoRequest,
+#[tracing::instrument(skip_all)]
+async fn get_active_habits(
     db: State<'_, DatabaseState>,
-) -> Result<Todo, String> {
+) -> Result<Vec<Habit>, String> {
     let db = db.lock().await;
-    db.update_todo(request).await.map_err(|e| e.to_string())
+    db.get_active_habits().await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn delete_todo(
+#[tracing::instrument(skip_all)]
+async fn get_habits_by_category(
+    category: String,
+    include_inactive: Option<bool>,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<Habit>, String> {
+    let db = db.lock().await;
+    db.get_habits_by_category(&category, include_inactive.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_habit_categories(
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<HabitCategory>, String> {
+    let db = db.lock().await;
+    db.get_habit_categories().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn archive_habit(
+    app: AppHandle,
     id: String,
     db: State<'_, DatabaseState>,
-) -> Result<(), String> {
+) -> Result<Habit, String> {
     let db = db.lock().await;
-    db.delete_todo(&id).await.map_err(|e| e.to_string())
+    let habit = db.archive_habit(&id).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "habit", "updated", Some(&habit.id));
+    Ok(habit)
 }
 
 #[tauri::command]
-async fn toggle_todo_completion(
+#[tracing::instrument(skip_all)]
+async fn unarchive_habit(
+    app: AppHandle,
     id: String,
     db: State<'_, DatabaseState>,
-) -> Result<Todo, String> {
+) -> Result<Habit, String> {
     let db = db.lock().await;
-    db.toggle_todo_completion(&id).await.map_err(|e| e.to_string())
+    let habit = db.unarchive_habit(&id).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "habit", "updated", Some(&habit.id));
+    Ok(habit)
 }
 
-// 子任务相关命令
 #[tauri::command]
-async fn get_subtasks_by_todo(
-    todo_id: String,
+#[tracing::instrument(skip_all)]
+async fn get_today_habits(
+    date: String,
     db: State<'_, DatabaseState>,
-) -> Result<Vec<Subtask>, String> {
+) -> Result<Vec<HabitWithRecord>, String> {
     let db = db.lock().await;
-    db.get_subtasks_by_todo(&todo_id).await.map_err(|e| e.to_string())
+    db.get_today_habits(&date).await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn create_subtask(
-    request: CreateSubtaskRequest,
+#[tracing::instrument(skip_all)]
+async fn create_habit(
+    app: AppHandle,
+    request: CreateHabitRequest,
     db: State<'_, DatabaseState>,
-) -> Result<Subtask, String> {
+) -> Result<Habit, String> {
     let db = db.lock().await;
-    db.create_subtask(request).await.map_err(|e| e.to_string())
+    let habit = db.create_habit(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "habit", "created", Some(&habit.id));
+    Ok(habit)
 }
 
 #[tauri::command]
-async fn toggle_subtask_completion(
-    id: String,
+#[tracing::instrument(skip_all)]
+async fn update_habit(
+    app: AppHandle,
+    request: UpdateHabitRequest,
     db: State<'_, DatabaseState>,
-) -> Result<Subtask, String> {
+) -> Result<Habit, String> {
     let db = db.lock().await;
-    db.toggle_subtask_completion(&id).await.map_err(|e| e.to_string())
+    let habit = db.update_habit(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "habit", "updated", Some(&habit.id));
+    Ok(habit)
 }
 
 #[tauri::command]
-async fn delete_subtask(
+#[tracing::instrument(skip_all)]
+async fn delete_habit(
+    app: AppHandle,
     id: String,
     db: State<'_, DatabaseState>,
 ) -> Result<(), String> {
     let db = db.lock().await;
-    db.delete_subtask(&id).await.map_err(|e| e.to_string())
+    db.delete_habit(&id).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "habit", "deleted", Some(&id));
+    Ok(())
 }
 
-// 番茄钟会话相关命令
 #[tauri::command]
-async fn create_pomodoro_session(
-    request: CreatePomodoroSessionRequest,
+#[tracing::instrument(skip_all)]
+async fn add_habit_skip(
+    app: AppHandle,
+    habit_id: String,
+    date: String,
     db: State<'_, DatabaseState>,
-) -> Result<PomodoroSession, String> {
+) -> Result<HabitSkip, String> {
     let db = db.lock().await;
-    db.create_pomodoro_session(request).await.map_err(|e| e.to_string())
+    let skip = db.add_habit_skip(&habit_id, &date)
+        .await
+        .map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "habit", "updated", Some(&skip.habit_id));
+    Ok(skip)
 }
 
 #[tauri::command]
-async fn update_pomodoro_session(
-    request: UpdatePomodoroSessionRequest,
+#[tracing::instrument(skip_all)]
+async fn remove_habit_skip(
+    app: AppHandle,
+    habit_id: String,
+    date: String,
     db: State<'_, DatabaseState>,
-) -> Result<PomodoroSession, String> {
+) -> Result<(), String> {
     let db = db.lock().await;
-    db.update_pomodoro_session(request).await.map_err(|e| e.to_string())
+    db.remove_habit_skip(&habit_id, &date)
+        .await
+        .map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "habit", "updated", Some(&habit_id));
+    Ok(())
 }
 
 #[tauri::command]
-async fn get_pomodoro_sessions_by_date(
+#[tracing::instrument(skip_all)]
+async fn add_habit_pause(
+    app: AppHandle,
+    habit_id: String,
+    start_date: String,
+    end_date: String,
+    db: State<'_, DatabaseState>,
+) -> Result<HabitPause, String> {
+    let db = db.lock().await;
+    let pause = db.add_habit_pause(&habit_id, &start_date, &end_date)
+        .await
+        .map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "habit", "updated", Some(&pause.habit_id));
+    Ok(pause)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn remove_habit_pause(
+    app: AppHandle,
+    id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<(), String> {
+    let db = db.lock().await;
+    let pause = db.remove_habit_pause(&id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(pause) = pause {
+        emit_data_changed(&app, "habit", "updated", Some(&pause.habit_id));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_habit_pauses(habit_id: String, db: State<'_, DatabaseState>) -> Result<Vec<HabitPause>, String> {
+    let db = db.lock().await;
+    db.get_habit_pauses(&habit_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_habit_streak(
+    habit_id: String,
     date: String,
     db: State<'_, DatabaseState>,
-) -> Result<Vec<PomodoroSession>, String> {
+) -> Result<HabitStreak, String> {
     let db = db.lock().await;
-    db.get_pomodoro_sessions_by_date(&date).await.map_err(|e| e.to_string())
+    db.get_habit_streak(&habit_id, &date)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn get_pomodoro_sessions_by_date_range(
-    start_date: String,
-    end_date: String,
+#[tracing::instrument(skip_all)]
+async fn recompute_habit_streak(
+    app: AppHandle,
+    habit_id: String,
     db: State<'_, DatabaseState>,
-) -> Result<Vec<PomodoroSession>, String> {
+) -> Result<HabitStreak, String> {
     let db = db.lock().await;
-    db.get_pomodoro_sessions_by_date_range(&start_date, &end_date)
+    let streak = db.recompute_habit_streak(&habit_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "habit", "updated", Some(&habit_id));
+    Ok(streak)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_habit_week_progress(
+    habit_id: String,
+    week_start: String,
+    db: State<'_, DatabaseState>,
+) -> Result<HabitWeekProgress, String> {
+    let db = db.lock().await;
+    db.get_habit_week_progress(&habit_id, &week_start)
         .await
         .map_err(|e| e.to_string())
 }
 
-// 番茄钟设置相关命令
 #[tauri::command]
-async fn get_pomodoro_settings(
+#[tracing::instrument(skip_all)]
+async fn get_habit_completion_rate(
+    habit_id: String,
+    start_date: String,
+    end_date: String,
     db: State<'_, DatabaseState>,
-) -> Result<PomodoroSettings, String> {
+) -> Result<f64, String> {
     let db = db.lock().await;
-    db.get_pomodoro_settings().await.map_err(|e| e.to_string())
+    db.get_habit_completion_rate(&habit_id, &start_date, &end_date)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn update_pomodoro_settings(
-    request: UpdatePomodoroSettingsRequest,
+#[tracing::instrument(skip_all)]
+async fn get_habit_year_summary(
+    habit_id: String,
+    year: i32,
     db: State<'_, DatabaseState>,
-) -> Result<PomodoroSettings, String> {
+) -> Result<HabitYearSummary, String> {
     let db = db.lock().await;
-    db.update_pomodoro_settings(request).await.map_err(|e| e.to_string())
+    db.get_habit_year_summary(&habit_id, year)
+        .await
+        .map_err(|e| e.to_string())
 }
 
-// 便笺相关命令
 #[tauri::command]
-async fn get_all_notes(
+#[tracing::instrument(skip_all)]
+async fn get_habit_value_total(
+    habit_id: String,
+    start_date: String,
+    end_date: String,
     db: State<'_, DatabaseState>,
-) -> Result<Vec<Note>, String> {
+) -> Result<HabitValueTotal, String> {
     let db = db.lock().await;
-    db.get_all_notes().await.map_err(|e| e.to_string())
+    db.get_habit_value_total(&habit_id, &start_date, &end_date)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn create_note(
-    request: CreateNoteRequest,
+#[tracing::instrument(skip_all)]
+async fn get_daily_habit_completions(
+    start_date: String,
+    end_date: String,
     db: State<'_, DatabaseState>,
-) -> Result<Note, String> {
+) -> Result<Vec<DailyHabitCompletion>, String> {
     let db = db.lock().await;
-    db.create_note(request).await.map_err(|e| e.to_string())
+    db.get_daily_habit_completions(&start_date, &end_date)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn update_note(
-    request: UpdateNoteRequest,
+#[tracing::instrument(skip_all)]
+async fn get_habit_checkin_hours(
+    habit_id: String,
     db: State<'_, DatabaseState>,
-) -> Result<Note, String> {
+) -> Result<Vec<HabitCheckinHour>, String> {
     let db = db.lock().await;
-    db.update_note(request).await.map_err(|e| e.to_string())
+    db.get_habit_checkin_hours(&habit_id).await.map_err(|e| e.to_string())
 }
 
+// 习惯打卡记录相关命令
 #[tauri::command]
-async fn delete_note(
-    id: String,
+#[tracing::instrument(skip_all)]
+async fn get_habit_records_by_date_range(
+    habit_id: String,
+    start_date: String,
+    end_date: String,
     db: State<'_, DatabaseState>,
-) -> Result<(), String> {
+) -> Result<Vec<HabitRecord>, String> {
     let db = db.lock().await;
-    db.delete_note(&id).await.map_err(|e| e.to_string())
+    db.get_habit_records_by_date_range(&habit_id, &start_date, &end_date)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn toggle_note_pin(
-    id: String,
+#[tracing::instrument(skip_all)]
+async fn get_habit_records_for_habits(
+    habit_ids: Vec<String>,
+    start_date: String,
+    end_date: String,
     db: State<'_, DatabaseState>,
-) -> Result<Note, String> {
+) -> Result<Vec<HabitRecord>, String> {
     let db = db.lock().await;
-    db.toggle_note_pin(&id).await.map_err(|e| e.to_string())
+    db.get_habit_records_for_habits(&habit_ids, &start_date, &end_date)
+        .await
+        .map_err(|e| e.to_string())
 }
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn create_habit_record(
+    app: AppHandle,
+    request: CreateHabitRecordRequest,
+    db: State<'_, DatabaseState>,
+) -> Result<HabitRecord, String> {
+    let db = db.lock().await;
+    let record = db.create_habit_record(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "habit_record", "created", Some(&record.id));
+    Ok(record)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn export_habit_records_csv(
+    habit_id: String,
+    path: String,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    db: State<'_, DatabaseState>,
+) -> Result<usize, String> {
+    let db = db.lock().await;
+    db.export_habit_records_csv(&habit_id, &path, start_date.as_deref(), end_date.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_habit_record_by_date(
+    habit_id: String,
+    date: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Option<HabitRecord>, String> {
+    let db = db.lock().await;
+    db.get_habit_record_by_date(&habit_id, &date)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_or_create_habit_record(
+    habit_id: String,
+    date: String,
+    db: State<'_, DatabaseState>,
+) -> Result<HabitRecord, String> {
+    let db = db.lock().await;
+    db.get_or_create_habit_record(&habit_id, &date)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn update_habit_record(
+    app: AppHandle,
+    id: String,
+    completed: bool,
+    value: Option<i32>,
+    note: Option<String>,
+    completed_override: Option<bool>,
+    db: State<'_, DatabaseState>,
+) -> Result<HabitRecord, String> {
+    let db = db.lock().await;
+    let record = db.update_habit_record(&id, completed, value, note, completed_override.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "habit_record", "updated", Some(&record.id));
+    Ok(record)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn upsert_habit_records_bulk(
+    app: AppHandle,
+    records: Vec<CreateHabitRecordRequest>,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<HabitRecord>, String> {
+    let db = db.lock().await;
+    let saved = db.upsert_habit_records_bulk(records).await.map_err(|e| e.to_string())?;
+    if !saved.is_empty() {
+        emit_data_changed(&app, "habit_record", "updated", None);
+    }
+    Ok(saved)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_habit_records_by_habit(
+    habit_id: String,
+    limit: Option<i64>,
+    before_date: Option<String>,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<HabitRecord>, String> {
+    let db = db.lock().await;
+    db.get_habit_records_by_habit(&habit_id, limit, before_date.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 待办事项相关命令
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_all_todos(
+    sort_by: Option<String>,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<Todo>, String> {
+    let db = db.lock().await;
+    db.get_all_todos(sort_by.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn query_todos(
+    request: QueryTodosRequest,
+    db: State<'_, DatabaseState>,
+) -> Result<PaginatedResult<Todo>, String> {
+    let db = db.lock().await;
+    db.query_todos(request).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn search_todos(
+    query: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<Todo>, String> {
+    let db = db.lock().await;
+    db.search_todos(&query).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn filter_todos(
+    query: String,
+    db: State<'_, DatabaseState>,
+) -> Result<TodoSearchResult, String> {
+    let db = db.lock().await;
+    db.filter_todos(&query).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_todos_due_within(
+    today: String,
+    days: i64,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<Todo>, String> {
+    let db = db.lock().await;
+    db.get_todos_due_within(&today, days)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_todos_with_subtask_counts(
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<TodoWithSubtaskCounts>, String> {
+    let db = db.lock().await;
+    db.get_todos_with_subtask_counts().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn create_todo(
+    app: AppHandle,
+    request: CreateTodoRequest,
+    db: State<'_, DatabaseState>,
+) -> Result<Todo, String> {
+    let db = db.lock().await;
+    let todo = db.create_todo(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "todo", "created", Some(&todo.id));
+    Ok(todo)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn create_todo_from_text(
+    app: AppHandle,
+    input: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Todo, String> {
+    let db = db.lock().await;
+    let todo = db.create_todo_from_text(&input).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "todo", "created", Some(&todo.id));
+    Ok(todo)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn update_todo(
+    app: AppHandle,
+    request: UpdateTodoRequest,
+    db: State<'_, DatabaseState>,
+) -> Result<Todo, String> {
+    let db = db.lock().await;
+    let todo = db.update_todo(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "todo", "updated", Some(&todo.id));
+    Ok(todo)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn delete_todo(
+    app: AppHandle,
+    id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Option<Todo>, String> {
+    let db = db.lock().await;
+    let deleted = db.delete_todo(&id).await.map_err(|e| e.to_string())?;
+    if deleted.is_some() {
+        emit_data_changed(&app, "todo", "deleted", Some(&id));
+    }
+    Ok(deleted)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn merge_todos(
+    app: AppHandle,
+    primary_id: String,
+    secondary_id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Todo, String> {
+    let db = db.lock().await;
+    let merged = db.merge_todos(&primary_id, &secondary_id).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "todo", "deleted", Some(&secondary_id));
+    emit_data_changed(&app, "todo", "updated", Some(&merged.id));
+    Ok(merged)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn add_tag_to_todos(
+    app: AppHandle,
+    ids: Vec<String>,
+    tag: String,
+    db: State<'_, DatabaseState>,
+) -> Result<u64, String> {
+    let db = db.lock().await;
+    let modified = db.add_tag_to_todos(&ids, &tag).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "todo", "updated", None);
+    Ok(modified)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn remove_tag_from_todos(
+    app: AppHandle,
+    ids: Vec<String>,
+    tag: String,
+    db: State<'_, DatabaseState>,
+) -> Result<u64, String> {
+    let db = db.lock().await;
+    let modified = db.remove_tag_from_todos(&ids, &tag).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "todo", "updated", None);
+    Ok(modified)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn toggle_todo_completion(
+    app: AppHandle,
+    id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Todo, String> {
+    let db = db.lock().await;
+    let todo = db.toggle_todo_completion(&id).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "todo", "updated", Some(&todo.id));
+    Ok(todo)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn archive_todo(
+    app: AppHandle,
+    id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Todo, String> {
+    let db = db.lock().await;
+    let todo = db.archive_todo(&id).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "todo", "updated", Some(&todo.id));
+    Ok(todo)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn unarchive_todo(
+    app: AppHandle,
+    id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Todo, String> {
+    let db = db.lock().await;
+    let todo = db.unarchive_todo(&id).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "todo", "updated", Some(&todo.id));
+    Ok(todo)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_archived_todos(
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<Todo>, String> {
+    let db = db.lock().await;
+    db.get_archived_todos().await.map_err(|e| e.to_string())
+}
+
+// 排队的完成状态切换，前端乐观更新后在空闲时调用 flush_pending
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn enqueue_toggle(
+    entity_type: String,
+    id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<usize, String> {
+    let db = db.lock().await;
+    db.enqueue_toggle(&entity_type, &id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn flush_pending(
+    app: AppHandle,
+    db: State<'_, DatabaseState>,
+) -> Result<FlushResult, String> {
+    let db = db.lock().await;
+    let result = db.flush_pending().await.map_err(|e| e.to_string())?;
+    emit_flushed(&app, &result);
+    Ok(result)
+}
+
+fn emit_flushed(app: &AppHandle, result: &FlushResult) {
+    for todo in &result.todos {
+        emit_data_changed(app, "todo", "updated", Some(&todo.id));
+    }
+    for subtask in &result.subtasks {
+        emit_data_changed(app, "subtask", "updated", Some(&subtask.id));
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_todo_categories(
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<CategoryCount>, String> {
+    let db = db.lock().await;
+    db.get_todo_categories().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_todo_completion_streak(
+    today: String,
+    db: State<'_, DatabaseState>,
+) -> Result<TodoCompletionStreak, String> {
+    let db = db.lock().await;
+    db.get_todo_completion_streak(&today).await.map_err(|e| e.to_string())
+}
+
+// 子任务相关命令
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_subtasks_by_todo(
+    todo_id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<Subtask>, String> {
+    let db = db.lock().await;
+    db.get_subtasks_by_todo(&todo_id).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_all_subtasks(
+    completed: Option<bool>,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<SubtaskWithParent>, String> {
+    let db = db.lock().await;
+    db.get_all_subtasks(completed).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn create_subtask(
+    app: AppHandle,
+    request: CreateSubtaskRequest,
+    db: State<'_, DatabaseState>,
+) -> Result<Subtask, String> {
+    let db = db.lock().await;
+    let subtask = db.create_subtask(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "subtask", "created", Some(&subtask.id));
+    Ok(subtask)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn toggle_subtask_completion(
+    app: AppHandle,
+    id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<SubtaskToggleResult, String> {
+    let db = db.lock().await;
+    let result = db.toggle_subtask_completion(&id).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "subtask", "updated", Some(&result.subtask.id));
+    if let Some(todo) = &result.todo {
+        emit_data_changed(&app, "todo", "updated", Some(&todo.id));
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn move_subtask(
+    app: AppHandle,
+    subtask_id: String,
+    new_todo_id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Subtask, String> {
+    let db = db.lock().await;
+    let subtask = db.move_subtask(&subtask_id, &new_todo_id).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "subtask", "updated", Some(&subtask.id));
+    Ok(subtask)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn delete_subtask(
+    app: AppHandle,
+    id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<(), String> {
+    let db = db.lock().await;
+    db.delete_subtask(&id).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "subtask", "deleted", Some(&id));
+    Ok(())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn delete_completed_subtasks(
+    app: AppHandle,
+    todo_id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<u64, String> {
+    let db = db.lock().await;
+    let deleted = db.delete_completed_subtasks(&todo_id).await.map_err(|e| e.to_string())?;
+    if deleted > 0 {
+        emit_data_changed(&app, "subtask", "deleted", None);
+    }
+    Ok(deleted)
+}
+
+// 番茄钟会话相关命令
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn create_pomodoro_session(
+    app: AppHandle,
+    request: CreatePomodoroSessionRequest,
+    db: State<'_, DatabaseState>,
+) -> Result<PomodoroSession, String> {
+    let db = db.lock().await;
+    let session = db.create_pomodoro_session(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "pomodoro_session", "created", Some(&session.id));
+    Ok(session)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn update_pomodoro_session(
+    app: AppHandle,
+    request: UpdatePomodoroSessionRequest,
+    db: State<'_, DatabaseState>,
+) -> Result<PomodoroSession, String> {
+    let db = db.lock().await;
+    let was_completed = db.get_pomodoro_session(&request.id).await.map_err(|e| e.to_string())?.completed;
+    let session = db.update_pomodoro_session(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "pomodoro_session", "updated", Some(&session.id));
+    if session.completed && !was_completed {
+        let settings = db.get_pomodoro_settings().await.map_err(|e| e.to_string())?;
+        if settings.notification_enabled {
+            reminders::notify_pomodoro_completed(&app, &session);
+        }
+    }
+    Ok(session)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn delete_pomodoro_session(
+    app: AppHandle,
+    id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<bool, String> {
+    let db = db.lock().await;
+    let removed = db.delete_pomodoro_session(&id).await.map_err(|e| e.to_string())?;
+    if removed {
+        emit_data_changed(&app, "pomodoro_session", "deleted", Some(&id));
+    }
+    Ok(removed)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn purge_pomodoro_sessions(
+    app: AppHandle,
+    older_than: String,
+    db: State<'_, DatabaseState>,
+) -> Result<u64, String> {
+    let db = db.lock().await;
+    let removed = db.purge_pomodoro_sessions(&older_than).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "pomodoro_session", "deleted", None);
+    Ok(removed)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_pomodoro_sessions_by_date(
+    date: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<PomodoroSession>, String> {
+    let db = db.lock().await;
+    db.get_pomodoro_sessions_by_date(&date).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_pomodoro_sessions_by_date_range(
+    start_date: String,
+    end_date: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<PomodoroSession>, String> {
+    let db = db.lock().await;
+    db.get_pomodoro_sessions_by_date_range(&start_date, &end_date)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_pomodoro_stats(
+    start_date: String,
+    end_date: String,
+    db: State<'_, DatabaseState>,
+) -> Result<PomodoroStats, String> {
+    let db = db.lock().await;
+    db.get_pomodoro_stats(&start_date, &end_date)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_focus_trend(
+    start_date: String,
+    end_date: String,
+    window_days: u32,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<FocusTrendPoint>, String> {
+    let db = db.lock().await;
+    db.get_focus_trend(&start_date, &end_date, window_days)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_daily_pomodoro_progress(
+    date: String,
+    db: State<'_, DatabaseState>,
+) -> Result<DailyPomodoroProgress, String> {
+    let db = db.lock().await;
+    db.get_daily_pomodoro_progress(&date)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_next_pomodoro_session(
+    date: String,
+    db: State<'_, DatabaseState>,
+) -> Result<NextPomodoroSession, String> {
+    let db = db.lock().await;
+    db.get_next_pomodoro_session(&date).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn should_take_long_break(
+    date: String,
+    db: State<'_, DatabaseState>,
+) -> Result<bool, String> {
+    let db = db.lock().await;
+    db.should_take_long_break(&date).await.map_err(|e| e.to_string())
+}
+
+// 番茄钟设置相关命令
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_pomodoro_settings(
+    db: State<'_, DatabaseState>,
+) -> Result<PomodoroSettings, String> {
+    let db = db.lock().await;
+    db.get_pomodoro_settings().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn update_pomodoro_settings(
+    app: AppHandle,
+    request: UpdatePomodoroSettingsRequest,
+    db: State<'_, DatabaseState>,
+) -> Result<PomodoroSettings, String> {
+    let db = db.lock().await;
+    let settings = db.update_pomodoro_settings(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "pomodoro_settings", "updated", Some(&settings.id));
+    Ok(settings)
+}
+
+// 便笺相关命令
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_all_notes(
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<Note>, String> {
+    let db = db.lock().await;
+    db.get_all_notes().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn create_note(
+    app: AppHandle,
+    request: CreateNoteRequest,
+    db: State<'_, DatabaseState>,
+) -> Result<Note, String> {
+    let db = db.lock().await;
+    let note = db.create_note(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "note", "created", Some(&note.id));
+    Ok(note)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_notes_by_tag(
+    tag: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<Note>, String> {
+    let db = db.lock().await;
+    db.get_notes_by_tag(&tag).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_notes_created_histogram(
+    start_date: String,
+    end_date: String,
+    bucket: Bucket,
+    include_archived: Option<bool>,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<HistogramPoint>, String> {
+    let db = db.lock().await;
+    db.get_notes_created_histogram(&start_date, &end_date, bucket, include_archived.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn find_notes_by_title(
+    title: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<Note>, String> {
+    let db = db.lock().await;
+    db.find_notes_by_title(&title).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn search_notes(
+    query: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<Note>, String> {
+    let db = db.lock().await;
+    db.search_notes(&query).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn update_note(
+    app: AppHandle,
+    request: UpdateNoteRequest,
+    db: State<'_, DatabaseState>,
+) -> Result<Note, String> {
+    let db = db.lock().await;
+    let note = db.update_note(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "note", "updated", Some(&note.id));
+    Ok(note)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn patch_note(
+    app: AppHandle,
+    request: PatchNoteRequest,
+    db: State<'_, DatabaseState>,
+) -> Result<Note, String> {
+    let db = db.lock().await;
+    let note = db.patch_note(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "note", "updated", Some(&note.id));
+    Ok(note)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn delete_note(
+    app: AppHandle,
+    id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Option<Note>, String> {
+    let db = db.lock().await;
+    let deleted = db.delete_note(&id).await.map_err(|e| e.to_string())?;
+    if deleted.is_some() {
+        emit_data_changed(&app, "note", "deleted", Some(&id));
+    }
+    Ok(deleted)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn export_notes_markdown(
+    dir: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<String>, String> {
+    let db = db.lock().await;
+    db.export_notes_markdown(&dir).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn import_notes_markdown(
+    app: AppHandle,
+    dir: String,
+    db: State<'_, DatabaseState>,
+) -> Result<NoteImportResult, String> {
+    let db = db.lock().await;
+    let result = db.import_notes_markdown(&dir).await.map_err(|e| e.to_string())?;
+    if result.imported > 0 {
+        emit_data_changed(&app, "note", "created", None);
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn reorder_pinned_notes(
+    app: AppHandle,
+    ordered_ids: Vec<String>,
+    db: State<'_, DatabaseState>,
+) -> Result<(), String> {
+    let db = db.lock().await;
+    db.reorder_pinned_notes(&ordered_ids).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "note", "updated", None);
+    Ok(())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_default_note_colors(
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<NoteColor>, String> {
+    let db = db.lock().await;
+    Ok(db.get_default_note_colors())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn toggle_note_pin(
+    app: AppHandle,
+    id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Note, String> {
+    let db = db.lock().await;
+    let note = db.toggle_note_pin(&id).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "note", "updated", Some(&note.id));
+    Ok(note)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_note_categories(
+    include_archived: Option<bool>,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<CategoryCount>, String> {
+    let db = db.lock().await;
+    db.get_note_categories(include_archived.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_note_templates(
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<NoteTemplate>, String> {
+    let db = db.lock().await;
+    db.get_note_templates().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn create_note_template(
+    app: AppHandle,
+    request: CreateNoteTemplateRequest,
+    db: State<'_, DatabaseState>,
+) -> Result<NoteTemplate, String> {
+    let db = db.lock().await;
+    let template = db.create_note_template(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "note_template", "created", Some(&template.id));
+    Ok(template)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn update_note_template(
+    app: AppHandle,
+    request: UpdateNoteTemplateRequest,
+    db: State<'_, DatabaseState>,
+) -> Result<NoteTemplate, String> {
+    let db = db.lock().await;
+    let template = db.update_note_template(request).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "note_template", "updated", Some(&template.id));
+    Ok(template)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn delete_note_template(
+    app: AppHandle,
+    id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Option<NoteTemplate>, String> {
+    let db = db.lock().await;
+    let deleted = db.delete_note_template(&id).await.map_err(|e| e.to_string())?;
+    if deleted.is_some() {
+        emit_data_changed(&app, "note_template", "deleted", Some(&id));
+    }
+    Ok(deleted)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn create_note_from_template(
+    app: AppHandle,
+    template_id: String,
+    overrides: Option<NoteTemplateOverrides>,
+    db: State<'_, DatabaseState>,
+) -> Result<Note, String> {
+    let db = db.lock().await;
+    let note = db
+        .create_note_from_template(&template_id, overrides.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "note", "created", Some(&note.id));
+    Ok(note)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_or_create_daily_note(
+    date: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Note, String> {
+    let db = db.lock().await;
+    db.get_or_create_daily_note(&date)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 应用设置相关命令
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_week_start(
+    db: State<'_, DatabaseState>,
+) -> Result<u8, String> {
+    let db = db.lock().await;
+    db.get_week_start().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn set_week_start(
+    app: AppHandle,
+    week_start: u8,
+    db: State<'_, DatabaseState>,
+) -> Result<u8, String> {
+    let db = db.lock().await;
+    let week_start = db.set_week_start(week_start).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "settings", "updated", None);
+    Ok(week_start)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_pomodoro_retention_days(
+    db: State<'_, DatabaseState>,
+) -> Result<u32, String> {
+    let db = db.lock().await;
+    db.get_pomodoro_retention_days().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn set_pomodoro_retention_days(
+    app: AppHandle,
+    days: u32,
+    db: State<'_, DatabaseState>,
+) -> Result<u32, String> {
+    let db = db.lock().await;
+    let days = db.set_pomodoro_retention_days(days).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "settings", "updated", None);
+    Ok(days)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_retention_policy(
+    db: State<'_, DatabaseState>,
+) -> Result<RetentionPolicy, String> {
+    let db = db.lock().await;
+    db.get_retention_policy().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn set_retention_policy(
+    app: AppHandle,
+    policy_json: String,
+    db: State<'_, DatabaseState>,
+) -> Result<RetentionPolicy, String> {
+    let db = db.lock().await;
+    let policy = db.set_retention_policy(&policy_json).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "settings", "updated", None);
+    Ok(policy)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn apply_retention_now(
+    app: AppHandle,
+    db: State<'_, DatabaseState>,
+) -> Result<RetentionReport, String> {
+    let db = db.lock().await;
+    let report = db.apply_retention_now().await.map_err(|e| e.to_string())?;
+    for result in report.results.iter().filter(|result| result.affected > 0) {
+        let entity = match result.entity {
+            RetentionEntity::Todo => "todo",
+            RetentionEntity::Note => "note",
+            RetentionEntity::PomodoroSession => "pomodoro_session",
+        };
+        let action = match result.action {
+            RetentionAction::Archive => "updated",
+            RetentionAction::Purge => "deleted",
+        };
+        emit_data_changed(&app, entity, action, None);
+    }
+    Ok(report)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_view_state(
+    view: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Option<String>, String> {
+    let db = db.lock().await;
+    db.get_view_state(&view).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn save_view_state(
+    app: AppHandle,
+    view: String,
+    state_json: String,
+    db: State<'_, DatabaseState>,
+) -> Result<(), String> {
+    let db = db.lock().await;
+    db.save_view_state(&view, &state_json).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "settings", "updated", Some(&view));
+    Ok(())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_default_category(
+    entity: String,
+    db: State<'_, DatabaseState>,
+) -> Result<String, String> {
+    let db = db.lock().await;
+    db.get_default_category(&entity).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn set_default_category(
+    app: AppHandle,
+    entity: String,
+    category: String,
+    db: State<'_, DatabaseState>,
+) -> Result<String, String> {
+    let db = db.lock().await;
+    let category = db.set_default_category(&entity, &category).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "settings", "updated", None);
+    Ok(category)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_habit_reminder_time(
+    db: State<'_, DatabaseState>,
+) -> Result<Option<String>, String> {
+    let db = db.lock().await;
+    db.get_habit_reminder_time().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn set_habit_reminder_time(
+    app: AppHandle,
+    time: Option<String>,
+    db: State<'_, DatabaseState>,
+) -> Result<Option<String>, String> {
+    let db = db.lock().await;
+    let time = db.set_habit_reminder_time(time).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "settings", "updated", None);
+    Ok(time)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_utc_offset_minutes(
+    db: State<'_, DatabaseState>,
+) -> Result<Option<i32>, String> {
+    let db = db.lock().await;
+    db.get_utc_offset_minutes().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn set_utc_offset_minutes(
+    app: AppHandle,
+    minutes: Option<i32>,
+    db: State<'_, DatabaseState>,
+) -> Result<Option<i32>, String> {
+    let db = db.lock().await;
+    let minutes = db.set_utc_offset_minutes(minutes).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "settings", "updated", None);
+    Ok(minutes)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_journal_template_id(
+    db: State<'_, DatabaseState>,
+) -> Result<Option<String>, String> {
+    let db = db.lock().await;
+    db.get_journal_template_id().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn set_journal_template_id(
+    app: AppHandle,
+    template_id: Option<String>,
+    db: State<'_, DatabaseState>,
+) -> Result<Option<String>, String> {
+    let db = db.lock().await;
+    let template_id = db.set_journal_template_id(template_id).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "settings", "updated", None);
+    Ok(template_id)
+}
+
+// 日志相关命令
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn set_log_level(
+    level: String,
+    log_handle: State<'_, LogLevelHandle>,
+) -> Result<(), String> {
+    logging::set_level(&log_handle, &level).map_err(|e| e.to_string())?;
+    tracing::info!(level = %level, "日志级别已更新");
+    Ok(())
+}
+
+// 撤销相关命令
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn undo_last(
+    app: AppHandle,
+    db: State<'_, DatabaseState>,
+) -> Result<Option<UndoResult>, String> {
+    let db = db.lock().await;
+    let result = db.undo_last().await.map_err(|e| e.to_string())?;
+    if let Some(result) = &result {
+        // 撤销删除相当于重新创建
+        let action = if result.action == "deleted" { "created" } else { "updated" };
+        emit_data_changed(&app, &result.entity, action, Some(&result.id));
+    }
+    Ok(result)
+}
+
+// 回顾报告相关命令
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_productivity_report(
+    start_date: String,
+    end_date: String,
+    include_archived: Option<bool>,
+    db: State<'_, DatabaseState>,
+) -> Result<ProductivityReport, String> {
+    let db = db.lock().await;
+    db.get_productivity_report(&start_date, &end_date, include_archived.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn export_report_markdown(
+    path: String,
+    start_date: String,
+    end_date: String,
+    db: State<'_, DatabaseState>,
+) -> Result<ProductivityReport, String> {
+    let db = db.lock().await;
+    db.export_report_markdown(&path, &start_date, &end_date)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 备份相关命令
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn export_backup(
+    path: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<TableRowCount>, String> {
+    let db = db.lock().await;
+    db.export_backup(&path).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn validate_backup(
+    path: String,
+    db: State<'_, DatabaseState>,
+) -> Result<BackupValidation, String> {
+    let db = db.lock().await;
+    db.validate_backup(&path).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn import_backup(
+    app: AppHandle,
+    path: String,
+    mode: ImportMode,
+    db: State<'_, DatabaseState>,
+) -> Result<BackupValidation, String> {
+    let db = db.lock().await;
+    let validation = db.import_backup(&path, mode).await.map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "all", "updated", None);
+    Ok(validation)
+}
+
+// 实体关联相关命令
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn link_entities(
+    app: AppHandle,
+    source_type: String,
+    source_id: String,
+    target_type: String,
+    target_id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<EntityLink, String> {
+    let db = db.lock().await;
+    let link = db.link_entities(&source_type, &source_id, &target_type, &target_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "link", "created", Some(&link.id));
+    Ok(link)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn unlink_entities(
+    app: AppHandle,
+    source_type: String,
+    source_id: String,
+    target_type: String,
+    target_id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<(), String> {
+    let db = db.lock().await;
+    db.unlink_entities(&source_type, &source_id, &target_type, &target_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    emit_data_changed(&app, "link", "deleted", None);
+    Ok(())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_links(
+    entity_type: String,
+    id: String,
+    db: State<'_, DatabaseState>,
+) -> Result<LinkedEntities, String> {
+    let db = db.lock().await;
+    db.get_links(&entity_type, &id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 数据库诊断相关命令
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_startup_status(
+    app: AppHandle,
+) -> Result<StartupStatus, String> {
+    match app.try_state::<DatabaseState>() {
+        Some(db) => Ok(db.lock().await.startup_status()),
+        None => Ok(StartupStatus {
+            open_failed: true,
+            error: app.try_state::<DatabaseStartupError>().map(|error| error.0.clone()),
+            ..StartupStatus::default()
+        }),
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn healthcheck(
+    app: AppHandle,
+) -> Result<HealthCheck, String> {
+    let Some(db) = app.try_state::<DatabaseState>() else {
+        return Ok(HealthCheck {
+            db_ok: false,
+            schema_version: 0,
+            pending_migrations: 0,
+        });
+    };
+    let db = db.lock().await;
+    db.healthcheck().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn run_readonly_query(
+    sql: String,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let db = db.lock().await;
+    db.run_readonly_query(&sql).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn get_db_stats(
+    db: State<'_, DatabaseState>,
+) -> Result<DbStats, String> {
+    let db = db.lock().await;
+    db.get_db_stats().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn compact_database(
+    db: State<'_, DatabaseState>,
+) -> Result<CompactResult, String> {
+    let db = db.lock().await;
+    db.compact_database().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn find_orphans(
+    db: State<'_, DatabaseState>,
+) -> Result<OrphanReport, String> {
+    let db = db.lock().await;
+    db.find_orphans().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+async fn repair_orphans(
+    app: AppHandle,
+    db: State<'_, DatabaseState>,
+) -> Result<OrphanReport, String> {
+    let db = db.lock().await;
+    let removed = db.repair_orphans().await.map_err(|e| e.to_string())?;
+    if removed.orphaned_subtasks > 0 || removed.orphaned_habit_records > 0 {
+        emit_data_changed(&app, "all", "updated", None);
+    }
+    Ok(removed)
+}
+
+// 启动时检查一次孤立行，只记录日志，由用户决定是否修复
+// 前端没有及时调用 flush_pending 时，超过合并窗口的排队操作由这里自动写入
+async fn flush_pending_writes(app: AppHandle) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_millis(database::PENDING_FLUSH_WINDOW_MS));
+    loop {
+        interval.tick().await;
+        let db = app.state::<DatabaseState>();
+        let db = db.lock().await;
+        if !db.has_stale_pending() {
+            continue;
+        }
+        match db.flush_pending().await.map_err(|e| e.to_string()) {
+            Ok(result) => emit_flushed(&app, &result),
+            Err(e) => tracing::warn!(error = %e, "自动写入排队操作失败"),
+        }
+    }
+}
+
+async fn log_orphans(app: AppHandle) {
+    let db = app.state::<DatabaseState>();
+    let db = db.lock().await;
+    match db.find_orphans().await.map_err(|e| e.to_string()) {
+        Ok(report) if report.orphaned_subtasks > 0 || report.orphaned_habit_records > 0 => tracing::warn!(
+            orphaned_subtasks = report.orphaned_subtasks,
+            orphaned_habit_records = report.orphaned_habit_records,
+            "发现孤立数据，可通过 repair_orphans 清理"
+        ),
+        Ok(_) => {}
+        Err(e) => tracing::warn!(error = %e, "孤立数据检查失败"),
+    }
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::async_runtime::block_on(async {
-        let database_service = DatabaseService::new()
-            .await
-            .expect("Failed to initialize database");
-        
-        tauri::Builder::default()
-            .plugin(tauri_plugin_opener::init())
-            .manage(Arc::new(Mutex::new(database_service)))
-            .invoke_handler(tauri::generate_handler![
-                // 日程事件
-                get_all_events,
-                get_events_by_date_range,
-                create_event,
-                update_event,
-                delete_event,
-                // 习惯
-                get_all_habits,
-                create_habit,
-                update_habit,
-                delete_habit,
-                get_habit_records_by_date_range,
-                create_habit_record,
-                get_habit_record_by_date,
-                get_or_create_habit_record,
-                update_habit_record,
-                get_habit_records_by_habit,
-                // 待办事项
-                get_all_todos,
-                create_todo,
-                update_todo,
-                delete_todo,
-                toggle_todo_completion,
-                // 子任务
-                get_subtasks_by_todo,
-                create_subtask,
-                toggle_subtask_completion,
-                delete_subtask,
-                // 番茄钟会话
-                create_pomodoro_session,
-                update_pomodoro_session,
-                get_pomodoro_sessions_by_date,
-                get_pomodoro_sessions_by_date_range,
-                // 番茄钟设置
-                get_pomodoro_settings,
-                update_pomodoro_settings,
-                // 便笺
-                get_all_notes,
-                create_note,
-                update_note,
-                delete_note,
-                toggle_note_pin
-            ])
-            .run(tauri::generate_context!())
-            .expect("error while running tauri application");
-    });
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
+        .setup(|app| {
+            // 先初始化日志，打开数据库时的警告（例如损坏恢复）才能写入日志文件
+            let log_handle = logging::init(&app.path().app_data_dir()?.join("logs"))?;
+            app.manage(log_handle);
+
+            match tauri::async_runtime::block_on(DatabaseService::new()) {
+                Ok(database_service) => {
+                    app.manage::<DatabaseState>(Arc::new(Mutex::new(database_service)));
+                    tauri::async_runtime::spawn(reminders::run_reminders(app.handle().clone()));
+                    tauri::async_runtime::spawn(log_orphans(app.handle().clone()));
+                    tauri::async_runtime::spawn(flush_pending_writes(app.handle().clone()));
+                }
+                Err(e) => {
+                    // 其他命令会因缺少数据库状态而返回错误，由 get_startup_status 报告原因
+                    tracing::error!(error = %e, "数据库初始化失败");
+                    app.manage(DatabaseStartupError(e.to_string()));
+                }
+            }
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            // 日程事件
+            get_all_events,
+            get_events_by_date_range,
+            get_events_by_type,
+            check_event_buffer,
+            get_upcoming_reminders,
+            create_event,
+            update_event,
+            delete_event,
+            toggle_event_favorite,
+            get_favorite_events,
+            export_events_google_csv,
+            copy_events,
+            get_week_agenda,
+            get_month_overview,
+            search_events,
+            cancel_event_occurrence,
+            modify_event_occurrence,
+            get_event_types,
+            update_event_types,
+            // 习惯
+            get_all_habits,
+            get_active_habits,
+            get_habits_by_category,
+            get_habit_categories,
+            get_today_habits,
+            create_habit,
+            update_habit,
+            delete_habit,
+            archive_habit,
+            unarchive_habit,
+            add_habit_skip,
+            remove_habit_skip,
+            add_habit_pause,
+            remove_habit_pause,
+            get_habit_pauses,
+            get_habit_streak,
+            recompute_habit_streak,
+            get_habit_week_progress,
+            get_habit_completion_rate,
+            get_habit_year_summary,
+            get_habit_value_total,
+            get_daily_habit_completions,
+            get_habit_checkin_hours,
+            get_habit_records_by_date_range,
+            get_habit_records_for_habits,
+            create_habit_record,
+            get_habit_record_by_date,
+            get_or_create_habit_record,
+            update_habit_record,
+            upsert_habit_records_bulk,
+            get_habit_records_by_habit,
+            export_habit_records_csv,
+            // 待办事项
+            get_all_todos,
+            query_todos,
+            get_todos_with_subtask_counts,
+            get_todos_due_within,
+            search_todos,
+            filter_todos,
+            create_todo,
+            create_todo_from_text,
+            update_todo,
+            delete_todo,
+            merge_todos,
+            toggle_todo_completion,
+            archive_todo,
+            unarchive_todo,
+            get_archived_todos,
+            enqueue_toggle,
+            flush_pending,
+            add_tag_to_todos,
+            remove_tag_from_todos,
+            get_todo_categories,
+            get_todo_completion_streak,
+            // 子任务
+            get_subtasks_by_todo,
+            get_all_subtasks,
+            create_subtask,
+            toggle_subtask_completion,
+            move_subtask,
+            delete_subtask,
+            delete_completed_subtasks,
+            // 番茄钟会话
+            create_pomodoro_session,
+            update_pomodoro_session,
+            get_pomodoro_sessions_by_date,
+            delete_pomodoro_session,
+            purge_pomodoro_sessions,
+            get_pomodoro_sessions_by_date_range,
+            get_pomodoro_stats,
+            get_focus_trend,
+            get_daily_pomodoro_progress,
+            get_next_pomodoro_session,
+            should_take_long_break,
+            // 番茄钟设置
+            get_pomodoro_settings,
+            update_pomodoro_settings,
+            // 便笺
+            get_all_notes,
+            create_note,
+            get_notes_by_tag,
+            get_notes_created_histogram,
+            find_notes_by_title,
+            search_notes,
+            update_note,
+            patch_note,
+            delete_note,
+            toggle_note_pin,
+            reorder_pinned_notes,
+            get_default_note_colors,
+            export_notes_markdown,
+            import_notes_markdown,
+            get_note_categories,
+            get_note_templates,
+            create_note_template,
+            update_note_template,
+            delete_note_template,
+            create_note_from_template,
+            get_or_create_daily_note,
+            // 应用设置
+            get_week_start,
+            set_week_start,
+            get_pomodoro_retention_days,
+            set_pomodoro_retention_days,
+            get_retention_policy,
+            set_retention_policy,
+            apply_retention_now,
+            get_view_state,
+            save_view_state,
+            get_default_category,
+            set_default_category,
+            get_habit_reminder_time,
+            set_habit_reminder_time,
+            get_utc_offset_minutes,
+            set_utc_offset_minutes,
+            get_journal_template_id,
+            set_journal_template_id,
+            // 日志
+            set_log_level,
+            // 撤销
+            undo_last,
+            // 回顾报告
+            get_productivity_report,
+            export_report_markdown,
+            // 备份
+            export_backup,
+            validate_backup,
+            import_backup,
+            // 实体关联
+            link_entities,
+            unlink_entities,
+            get_links,
+            // 数据库诊断
+            get_startup_status,
+            healthcheck,
+            run_readonly_query,
+            get_db_stats,
+            compact_database,
+            find_orphans,
+            repair_orphans
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
 }
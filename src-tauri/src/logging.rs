@@ -0,0 +1,37 @@
+use std::path::Path;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry};
+use crate::error::validation_error;
+
+// 运行时调整日志级别用的句柄，由 Tauri 托管
+pub type LogLevelHandle = reload::Handle<EnvFilter, Registry>;
+
+const DEFAULT_LOG_LEVEL: &str = "info";
+const MAX_LOG_FILES: usize = 7;
+
+// 日志按天滚动写入 log_dir，最多保留 MAX_LOG_FILES 个文件；RUST_LOG 环境变量可覆盖默认级别
+pub fn init(log_dir: &Path) -> Result<LogLevelHandle, Box<dyn std::error::Error>> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_LEVEL));
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("productimate")
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(log_dir)?;
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(appender).with_ansi(false))
+        .try_init()?;
+
+    Ok(handle)
+}
+
+// level 使用 EnvFilter 语法，例如 "debug" 或 "tauri_appy_lib=trace"
+pub fn set_level(handle: &LogLevelHandle, level: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let filter = EnvFilter::try_new(level).map_err(|_| validation_error(format!("无效的日志级别: {}", level)))?;
+    handle.reload(filter)?;
+    Ok(())
+}
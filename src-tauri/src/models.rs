@@ -2,13 +2,42 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use chrono::{DateTime, Utc};
 
+// 多窗口同步：数据变更时广播的 data-changed 事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataChangedEvent {
+    pub entity: String, // 'event', 'habit', 'todo', 'note' ...
+    pub action: String, // 'created', 'updated', 'deleted'
+    pub id: Option<String>,
+}
+
+// 撤销结果，action 为被撤销的操作（'updated' 或 'deleted'）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UndoResult {
+    pub entity: String,
+    pub action: String,
+    pub id: String,
+    pub description: String,
+}
+
+// 分页查询结果，计数与当前页无关
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PaginatedResult<T> {
+    pub items: Vec<T>,
+    pub page: i64,
+    pub page_size: i64,
+    pub total_count: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_count: Option<i64>, // 仅适用于有完成状态的数据
+}
+
 // 日程事件相关
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct CalendarEvent {
     pub id: String,
     pub title: String,
     pub description: Option<String>,
     pub date: String,
+    pub end_date: Option<String>, // 多日日程的最后一天，单日日程为空
     pub start_time: Option<String>,
     pub end_time: Option<String>,
     pub event_type: String,
@@ -16,10 +45,41 @@ pub struct CalendarEvent {
     pub is_all_day: bool,
     pub reminder: Option<i32>,
     pub repeat_type: Option<String>,
+    #[serde(default)]
+    pub repeat_until: Option<String>, // 重复的最后日期（含），为空时不限
+    #[serde(default)]
+    pub repeat_count: Option<i32>, // 最多重复的次数（含首次），与 repeat_until 先到者生效
     pub location: Option<String>,
-    pub attendees: Option<String>, // JSON string of array
+    pub attendees: Option<String>, // JSON string of Attendee array
+    #[serde(default)]
+    pub is_favorite: bool, // 只能通过 toggle_event_favorite 修改，更新日程时保持不变
+    #[serde(default = "default_true")]
+    pub notification_enabled: bool, // 设置了提醒时是否同时发送系统通知
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub duration_minutes: Option<i64>, // 由起止时间计算，结束早于开始视为跨越午夜；全天日程为空
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>, // 只在创建/更新的返回值中出现，不阻止保存
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attendee {
+    pub name: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default = "default_attendee_status")]
+    pub status: String, // 'unknown', 'accepted', 'declined', 'tentative'
+}
+
+fn default_attendee_status() -> String {
+    "unknown".to_string()
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -27,6 +87,8 @@ pub struct CreateEventRequest {
     pub title: String,
     pub description: Option<String>,
     pub date: String,
+    #[serde(default)]
+    pub end_date: Option<String>,
     pub start_time: Option<String>,
     pub end_time: Option<String>,
     pub event_type: String,
@@ -34,8 +96,14 @@ pub struct CreateEventRequest {
     pub is_all_day: bool,
     pub reminder: Option<i32>,
     pub repeat_type: Option<String>,
+    #[serde(default)]
+    pub repeat_until: Option<String>,
+    #[serde(default)]
+    pub repeat_count: Option<i32>,
     pub location: Option<String>,
-    pub attendees: Option<Vec<String>>,
+    pub attendees: Option<Vec<Attendee>>,
+    #[serde(default = "default_true")]
+    pub notification_enabled: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -44,6 +112,10 @@ pub struct UpdateEventRequest {
     pub title: String,
     pub description: Option<String>,
     pub date: String,
+    #[serde(default)]
+    pub end_date: Option<String>, // 为空时保持原值
+    #[serde(default)]
+    pub clear_end_date: bool, // 为 true 时改回单日日程，优先于 end_date
     pub start_time: Option<String>,
     pub end_time: Option<String>,
     pub event_type: String,
@@ -51,12 +123,62 @@ pub struct UpdateEventRequest {
     pub is_all_day: bool,
     pub reminder: Option<i32>,
     pub repeat_type: Option<String>,
+    #[serde(default)]
+    pub repeat_until: Option<String>, // 为空时保持原值
+    #[serde(default)]
+    pub repeat_count: Option<i32>, // 为空时保持原值
+    #[serde(default)]
+    pub clear_repeat_limit: bool, // 为 true 时清除重复的结束日期和次数，优先于 repeat_until/repeat_count
+    pub location: Option<String>,
+    pub attendees: Option<Vec<Attendee>>,
+    #[serde(default)]
+    pub notification_enabled: Option<bool>, // 为空时保持原值
+    #[serde(default)]
+    pub expected_updated_at: Option<DateTime<Utc>>, // 为空时不做冲突检查
+}
+
+// 月视图中某一天的汇总
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DayOverview {
+    pub date: String,
+    pub event_count: i64,
+    pub events: Vec<CalendarEvent>, // 已展开重复日程
+    pub todos_due: i64, // 当天截止的未完成待办
+    pub completed_pomodoros: i64, // 当天完成的工作番茄
+}
+
+// 重复日程的单次例外（取消或修改某一次）
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct EventException {
+    pub id: String,
+    pub event_id: String,
+    pub occurrence_date: String,
+    pub is_cancelled: bool,
+    pub override_data: Option<String>, // JSON string of EventOccurrenceOverride
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EventOccurrenceOverride {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    pub priority: Option<String>,
+    pub is_all_day: Option<bool>,
     pub location: Option<String>,
-    pub attendees: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModifyEventOccurrenceRequest {
+    pub event_id: String,
+    pub occurrence_date: String,
+    #[serde(flatten)]
+    pub changes: EventOccurrenceOverride,
 }
 
 // 习惯相关
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Habit {
     pub id: String,
     pub name: String,
@@ -65,13 +187,21 @@ pub struct Habit {
     pub color: String,
     pub target: i32,
     pub unit: String,
-    pub frequency: String,
+    pub frequency: String, // "daily"、"weekly" 或 {"type":"custom","days":[1,3,5]}（1 = 周一）
     pub is_active: bool,
+    pub skip_weekends: bool,
+    pub weekly_target: Option<i32>, // frequency 为 weekly 时每周需要完成的次数
+    #[serde(default)]
+    pub value_based: bool, // 开启后打卡的 value 达到 target 即视为完成，低于 target 视为未完成
+    #[serde(default)]
+    pub current_streak: i32, // 缓存值，在打卡记录、跳过、暂停或习惯设置变化时重新计算，跨天后读取时刷新
+    #[serde(default)]
+    pub longest_streak: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct HabitRecord {
     pub id: String,
     pub habit_id: String,
@@ -87,11 +217,18 @@ pub struct CreateHabitRequest {
     pub name: String,
     pub description: Option<String>,
     pub category: String,
-    pub color: String,
+    #[serde(default)]
+    pub color: String, // 为空时由后端从调色板中分配
     pub target: i32,
     pub unit: String,
     pub frequency: String,
     pub is_active: bool,
+    #[serde(default)]
+    pub skip_weekends: bool,
+    #[serde(default)]
+    pub weekly_target: Option<i32>,
+    #[serde(default)]
+    pub value_based: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -105,19 +242,154 @@ pub struct UpdateHabitRequest {
     pub unit: String,
     pub frequency: String,
     pub is_active: bool,
+    #[serde(default)]
+    pub skip_weekends: Option<bool>, // 为空时保持原值
+    #[serde(default)]
+    pub weekly_target: Option<i32>, // 为空时保持原值
+    #[serde(default)]
+    pub clear_weekly_target: bool, // 为 true 时清除每周目标，优先于 weekly_target
+    #[serde(default)]
+    pub value_based: Option<bool>, // 为空时保持原值
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct HabitWithRecord {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub habit: Habit,
+    pub record_id: Option<String>, // 当天还没有记录时为空
+    pub completed: bool,
+    pub value: Option<i32>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct HabitSkip {
+    pub id: String,
+    pub habit_id: String,
+    pub date: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct HabitPause {
+    pub id: String,
+    pub habit_id: String,
+    pub start_date: String, // 包含在暂停区间内
+    pub end_date: String,   // 包含在暂停区间内
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+pub struct HabitYearSummary {
+    pub habit_id: String,
+    pub year: i32,
+    pub total_completions: i64,
+    pub completion_rate: f64, // 0.0 - 1.0，跳过和暂停的日期不计入
+    pub longest_streak: i32, // 每周习惯以周为单位
+    pub best_month: Option<u32>, // 1 - 12，全年没有打卡时为空
+    pub total_value: i64,
+    pub unit: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HabitValueTotal {
+    pub habit_id: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub total: i64, // 范围内所有记录 value 之和，空值按 0 计
+    pub unit: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HabitStreak {
+    pub habit_id: String,
+    pub current_streak: i32,
+    pub longest_streak: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HabitWeekProgress {
+    pub habit_id: String,
+    pub week_start: String,
+    pub week_end: String,
+    pub completed: i32,
+    pub target: i32,
+    pub achieved: bool,
+}
+
+// 提醒中心的一条日程提醒，remind_at 为本地时间 YYYY-MM-DDTHH:MM:SS
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpcomingReminder {
+    #[serde(flatten)]
+    pub event: CalendarEvent,
+    pub remind_at: String,
+}
+
+// 同一天内两个相邻日程之间的紧凑衔接：地点不同且间隔不足缓冲时间，gap_minutes 为负表示时间重叠
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EventTransition {
+    pub from: CalendarEvent,
+    pub to: CalendarEvent,
+    pub gap_minutes: i64,
+}
+
+// habit-reminder 事件的内容，前端按连续天数调整提醒样式
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HabitReminder {
+    pub habit_id: String,
+    pub name: String,
+    pub color: String,
+    pub current_streak: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HabitCheckinHour {
+    pub hour: u32, // 0 - 23，按配置的时区
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DailyHabitCompletion {
+    pub date: String,
+    pub completed_count: i64, // 当天完成的不同习惯数
+    pub total_active: i64,
+}
+
+// 至少完成一个待办的连续天数，待办没有单独的完成时间，以最后更新时间的日期近似
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TodoCompletionStreak {
+    pub current_streak: i32,
+    pub longest_streak: i32,
+}
+
+// 待办或便笺中已使用的分类及其条目数，供分类选择器自动补全
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct CategoryCount {
+    pub category: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct HabitCategory {
+    pub category: String,
+    pub habit_count: i64,
+    pub active_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateHabitRecordRequest {
     pub habit_id: String,
     pub date: String,
     pub completed: bool,
     pub value: Option<i32>,
     pub note: Option<String>,
+    #[serde(default)]
+    pub completed_override: bool, // 为 true 时按 completed 保存，不由 value 推导
 }
 
 // 待办事项相关
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Todo {
     pub id: String,
     pub title: String,
@@ -127,11 +399,32 @@ pub struct Todo {
     pub tags: Option<String>, // JSON string of array
     pub due_date: Option<String>,
     pub category: String,
+    #[serde(default)]
+    pub auto_complete_on_subtasks: bool, // 开启后子任务全部完成时自动完成待办，取消完成子任务时重新打开
+    #[serde(default)]
+    pub is_archived: bool, // 已归档的待办不出现在待办列表和搜索中
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>, // 只在创建/更新的返回值中出现，不阻止保存
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+// flush_pending 的结果：本次写入涉及的待办和子任务的最终状态
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FlushResult {
+    pub todos: Vec<Todo>,
+    pub subtasks: Vec<Subtask>,
+}
+
+// 切换子任务的结果，todo 只在父待办因自动完成而改变状态时出现
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubtaskToggleResult {
+    pub subtask: Subtask,
+    pub todo: Option<Todo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Subtask {
     pub id: String,
     pub todo_id: String,
@@ -140,6 +433,24 @@ pub struct Subtask {
     pub created_at: DateTime<Utc>,
 }
 
+// 跨待办的子任务清单中的一项，附带所属待办的标题
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct SubtaskWithParent {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub subtask: Subtask,
+    pub todo_title: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct TodoWithSubtaskCounts {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub todo: Todo,
+    pub subtask_total: i64,
+    pub subtask_completed: i64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateTodoRequest {
     pub title: String,
@@ -148,6 +459,8 @@ pub struct CreateTodoRequest {
     pub tags: Option<Vec<String>>,
     pub due_date: Option<String>,
     pub category: String,
+    #[serde(default)]
+    pub auto_complete_on_subtasks: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -160,9 +473,28 @@ pub struct UpdateTodoRequest {
     pub tags: Option<Vec<String>>,
     pub due_date: Option<String>,
     pub category: String,
+    #[serde(default)]
+    pub auto_complete_on_subtasks: Option<bool>, // 为空时保持原值
+    #[serde(default)]
+    pub expected_updated_at: Option<DateTime<Utc>>, // 为空时不做冲突检查
+}
+
+// filter_todos 的结果，warnings 列出被忽略的搜索条件
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TodoSearchResult {
+    pub items: Vec<Todo>,
+    pub warnings: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+pub struct QueryTodosRequest {
+    pub page: Option<i64>, // 从 1 开始
+    pub page_size: Option<i64>,
+    pub sort_by: Option<String>,
+    pub category: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateSubtaskRequest {
     pub todo_id: String,
     pub title: String,
@@ -180,13 +512,15 @@ pub struct PomodoroSession {
     pub date: String,
     pub started_at: Option<DateTime<Utc>>,
     pub ended_at: Option<DateTime<Utc>>,
+    pub interrupted: bool, // 既未完成也未中断的会话视为进行中
+    pub interruption_reason: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreatePomodoroSessionRequest {
     pub session_type: String,
-    pub duration: i32,
+    pub duration: i32, // 秒，必须大于 0
     pub task_title: Option<String>,
     pub notes: Option<String>,
     pub date: String,
@@ -199,35 +533,142 @@ pub struct UpdatePomodoroSessionRequest {
     pub task_title: Option<String>,
     pub notes: Option<String>,
     pub ended_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub interrupted: bool,
+    #[serde(default)]
+    pub interruption_reason: Option<String>,
+    // 以下字段为空时保留原值，用于修正记错的会话
+    #[serde(default)]
+    pub session_type: Option<String>,
+    #[serde(default)]
+    pub duration: Option<i32>,
+}
+
+// 数据保留策略：每条规则对超过 older_than_days 天未更新的数据执行归档或清除
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionEntity {
+    Todo,            // archive 归档已完成的待办，purge 清除已完成的待办
+    Note,            // archive 归档未置顶的便笺，purge 清除已归档的便笺
+    PomodoroSession, // 只支持 purge，按会话日期计算
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionAction {
+    Archive,
+    Purge,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionRule {
+    pub entity: RetentionEntity,
+    pub action: RetentionAction,
+    pub older_than_days: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionPolicy {
+    #[serde(default)]
+    pub rules: Vec<RetentionRule>,
+    #[serde(default)]
+    pub auto_apply: bool, // 为 true 时每次启动自动执行；默认只在调用 apply_retention_now 时执行
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RetentionActionResult {
+    pub entity: RetentionEntity,
+    pub action: RetentionAction,
+    pub older_than_days: u32,
+    pub affected: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RetentionReport {
+    pub results: Vec<RetentionActionResult>, // 与策略中的规则一一对应
+}
+
+// 统计直方图的分桶粒度，周的起始日跟随 week_start 设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Bucket {
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistogramPoint {
+    pub bucket_start: String, // 分桶的第一天，第一个桶可能早于查询的开始日期
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FocusTrendPoint {
+    pub date: String,
+    pub focus_minutes: i64, // 当天已完成工作番茄的总分钟数
+    pub moving_average: f64, // 截至当天的 window_days 天滑动平均
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PomodoroStats {
+    pub start_date: String,
+    pub end_date: String,
+    pub total_sessions: i64,
+    pub completed_sessions: i64,
+    pub interrupted_sessions: i64,
+    pub in_progress_sessions: i64,
+    pub focus_seconds: i64, // 已完成工作番茄的总时长
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct PomodoroSettings {
     pub id: String,
-    pub work_time: i32, // 分钟
-    pub short_break: i32, // 分钟
-    pub long_break: i32, // 分钟
-    pub long_break_interval: i32, // 多少个工作周期后长休息
+    pub work_time: i32, // 秒
+    pub short_break: i32, // 秒
+    pub long_break: i32, // 秒
+    pub long_break_interval: i32, // 多少个工作周期后长休息，至少为 1
     pub auto_start_breaks: bool,
     pub auto_start_work: bool,
     pub notification_enabled: bool,
+    pub daily_session_goal: i32, // 每日目标完成的工作番茄数
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdatePomodoroSettingsRequest {
-    pub work_time: i32,
-    pub short_break: i32,
-    pub long_break: i32,
-    pub long_break_interval: i32,
+    pub work_time: i32, // 秒，必须大于 0
+    pub short_break: i32, // 秒，必须大于 0
+    pub long_break: i32, // 秒，必须大于 0
+    pub long_break_interval: i32, // 至少为 1
     pub auto_start_breaks: bool,
     pub auto_start_work: bool,
     pub notification_enabled: bool,
+    #[serde(default)]
+    pub daily_session_goal: Option<i32>, // 为空时保持原值
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DailyPomodoroProgress {
+    pub date: String,
+    pub completed_sessions: i64,
+    pub goal: i32,
+    pub achieved: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NextPomodoroSession {
+    pub session_type: String, // 'work', 'short_break', 'long_break'
+    pub duration: i32, // 秒数
+    pub auto_start: bool, // 按设置是否应自动开始
+    pub completed_work_sessions: i64, // 当天已完成的工作番茄数
 }
 
 // 便笺相关
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Note {
     pub id: String,
     pub title: String,
@@ -237,8 +678,19 @@ pub struct Note {
     pub color: String,
     pub is_pinned: bool,
     pub is_archived: bool,
+    pub pin_order: Option<i32>, // 仅置顶时有值，越小越靠前
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>, // 只在创建的返回值中出现，不阻止保存
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NoteImportResult {
+    pub imported: usize,
+    pub skipped: usize,
+    pub errors: Vec<String>, // 每个解析或保存失败的文件一条
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -250,6 +702,53 @@ pub struct CreateNoteRequest {
     pub color: String,
 }
 
+// 便笺模板，content 和 name 中可以使用 {{date}}、{{title}} 占位符
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct NoteTemplate {
+    pub id: String,
+    pub name: String,
+    pub content: String,
+    pub tags: Option<String>, // JSON string of array
+    pub category: String,
+    pub color: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateNoteTemplateRequest {
+    pub name: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+    pub category: String,
+    pub color: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateNoteTemplateRequest {
+    pub id: String,
+    pub name: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+    pub category: String,
+    pub color: String,
+}
+
+// 从模板创建便笺时的可选替换值：标题默认取模板名称，日期默认为今天
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NoteTemplateOverrides {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub date: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NoteColor {
+    pub label: String,
+    pub value: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateNoteRequest {
     pub id: String,
@@ -260,4 +759,137 @@ pub struct UpdateNoteRequest {
     pub color: String,
     pub is_pinned: bool,
     pub is_archived: bool,
-}
\ No newline at end of file
+    #[serde(default)]
+    pub expected_updated_at: Option<DateTime<Utc>>, // 为空时不做冲突检查
+}
+
+// 只更新提供了的字段
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PatchNoteRequest {
+    pub id: String,
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub category: Option<String>,
+    pub color: Option<String>,
+    pub is_pinned: Option<bool>,
+    pub is_archived: Option<bool>,
+    #[serde(default)]
+    pub expected_updated_at: Option<DateTime<Utc>>, // 为空时不做冲突检查
+}
+// 回顾报告，各项统计都限定在 [start_date, end_date] 内
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProductivityReport {
+    pub start_date: String,
+    pub end_date: String,
+    pub todos_completed: i64,
+    pub habit_completion_rate: f64, // 活跃习惯完成率的平均值，0.0 - 1.0
+    pub focus_minutes: i64, // 已完成工作番茄的总时长
+    pub events_attended: i64, // 范围内的日程次数（已展开重复日程）
+    pub notes_created: i64,
+}
+
+// 备份相关
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Backup {
+    pub schema_version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub calendar_events: Vec<CalendarEvent>,
+    pub event_exceptions: Vec<EventException>,
+    pub habits: Vec<Habit>,
+    pub habit_records: Vec<HabitRecord>,
+    pub habit_skips: Vec<HabitSkip>,
+    #[serde(default)]
+    pub habit_pauses: Vec<HabitPause>, // schema_version 2 新增
+    pub todos: Vec<Todo>,
+    pub subtasks: Vec<Subtask>,
+    pub pomodoro_sessions: Vec<PomodoroSession>,
+    pub notes: Vec<Note>,
+    #[serde(default)]
+    pub note_templates: Vec<NoteTemplate>, // schema_version 2 新增
+    #[serde(default)]
+    pub entity_links: Vec<EntityLink>, // schema_version 2 新增
+}
+
+// merge 按 id 覆盖已有记录；replace 先清空所有数据表
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    Merge,
+    Replace,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupValidation {
+    pub valid: bool,
+    pub schema_version: Option<u64>,
+    pub tables: Vec<TableRowCount>,
+    pub errors: Vec<String>,
+}
+
+// 数据库诊断相关
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableRowCount {
+    pub table: String,
+    pub rows: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DbStats {
+    pub file_size: i64, // 字节
+    pub page_count: i64,
+    pub page_size: i64,
+    pub journal_mode: String,
+    pub wal_enabled: bool,
+    pub tables: Vec<TableRowCount>,
+}
+
+// 实体关联，entity 类型为 "note"、"todo" 或 "event"，便笺总是存放在 source 一侧
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct EntityLink {
+    pub id: String,
+    pub source_type: String,
+    pub source_id: String,
+    pub target_type: String,
+    pub target_id: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LinkedEntities {
+    pub notes: Vec<Note>,
+    pub todos: Vec<Todo>,
+    pub events: Vec<CalendarEvent>,
+}
+
+// 数据库损坏时会备份原文件并新建数据库，corrupt_backup_path 为备份文件的路径；
+// 其他原因导致无法打开数据库时 open_failed 为 true，error 为失败原因
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StartupStatus {
+    pub recovered_from_corruption: bool,
+    pub corrupt_backup_path: Option<String>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub open_failed: bool,
+}
+
+// schema_version 来自 PRAGMA user_version，低于应用期望的版本时 pending_migrations 大于 0
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HealthCheck {
+    pub db_ok: bool,
+    pub schema_version: i64,
+    pub pending_migrations: usize,
+}
+
+// 父记录已删除但仍残留的行数
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrphanReport {
+    pub orphaned_subtasks: i64,
+    pub orphaned_habit_records: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompactResult {
+    pub size_before: i64, // 字节
+    pub size_after: i64, // 字节
+}
@@ -0,0 +1,207 @@
+// 快速添加待办：从一行文本中解析 #标签、!优先级 和相对日期，其余部分作为标题
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use crate::models::CreateTodoRequest;
+
+const DEFAULT_PRIORITY: &str = "medium";
+
+fn parse_priority(word: &str) -> Option<&'static str> {
+    match word {
+        "high" | "h" | "高" => Some("high"),
+        "medium" | "med" | "m" | "中" => Some("medium"),
+        "low" | "l" | "低" => Some("low"),
+        _ => None,
+    }
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "monday" | "mon" => Some(Weekday::Mon),
+        "tuesday" | "tue" => Some(Weekday::Tue),
+        "wednesday" | "wed" => Some(Weekday::Wed),
+        "thursday" | "thu" => Some(Weekday::Thu),
+        "friday" | "fri" => Some(Weekday::Fri),
+        "saturday" | "sat" => Some(Weekday::Sat),
+        "sunday" | "sun" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+// today 之后（不含 today）的第一个 weekday
+fn next_weekday(today: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let days = (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+    today + Duration::days(if days == 0 { 7 } else { days as i64 })
+}
+
+// 纯函数，today 由调用方传入；无法识别的记号原样保留在标题中，只取第一个日期
+pub fn parse_quick_todo(input: &str, today: NaiveDate) -> CreateTodoRequest {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    let mut title_words = Vec::new();
+    let mut tags: Vec<String> = Vec::new();
+    let mut priority = None;
+    let mut due_date = None;
+
+    let mut i = 0;
+    while i < words.len() {
+        let word = words[i];
+        let lower = word.to_lowercase();
+        i += 1;
+
+        if let Some(tag) = word.strip_prefix('#').filter(|tag| !tag.is_empty()) {
+            if !tags.iter().any(|existing| existing == tag) {
+                tags.push(tag.to_string());
+            }
+            continue;
+        }
+
+        if let Some(level) = lower.strip_prefix('!').and_then(parse_priority) {
+            priority = Some(level);
+            continue;
+        }
+
+        if due_date.is_none() {
+            let date = match lower.as_str() {
+                "today" | "今天" => Some(today),
+                "tomorrow" | "明天" => Some(today + Duration::days(1)),
+                "后天" => Some(today + Duration::days(2)),
+                "next" => match words.get(i).and_then(|next| parse_weekday(&next.to_lowercase())) {
+                    Some(weekday) => {
+                        i += 1;
+                        Some(next_weekday(today, weekday))
+                    }
+                    None => None,
+                },
+                other => NaiveDate::parse_from_str(other, "%Y-%m-%d").ok(),
+            };
+            if let Some(date) = date {
+                due_date = Some(date);
+                continue;
+            }
+        }
+
+        title_words.push(word);
+    }
+
+    CreateTodoRequest {
+        title: title_words.join(" "),
+        description: None,
+        priority: priority.unwrap_or(DEFAULT_PRIORITY).to_string(),
+        tags: if tags.is_empty() { None } else { Some(tags) },
+        due_date: due_date.map(|date| date.format("%Y-%m-%d").to_string()),
+        category: String::new(), // 留空，由 create_todo 套用默认分类
+        auto_complete_on_subtasks: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2026-01-07 是周三
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 1, 7).unwrap()
+    }
+
+    #[test]
+    fn parses_all_token_kinds() {
+        let todo = parse_quick_todo("Buy milk tomorrow #groceries !high", today());
+        assert_eq!(todo.title, "Buy milk");
+        assert_eq!(todo.tags, Some(vec!["groceries".to_string()]));
+        assert_eq!(todo.priority, "high");
+        assert_eq!(todo.due_date.as_deref(), Some("2026-01-08"));
+        assert_eq!(todo.category, "");
+    }
+
+    #[test]
+    fn plain_text_becomes_title() {
+        let todo = parse_quick_todo("Call the plumber about the sink", today());
+        assert_eq!(todo.title, "Call the plumber about the sink");
+        assert_eq!(todo.tags, None);
+        assert_eq!(todo.priority, DEFAULT_PRIORITY);
+        assert_eq!(todo.due_date, None);
+    }
+
+    #[test]
+    fn empty_and_whitespace_input() {
+        for input in ["", "   \t  "] {
+            let todo = parse_quick_todo(input, today());
+            assert_eq!(todo.title, "");
+            assert_eq!(todo.tags, None);
+            assert_eq!(todo.due_date, None);
+        }
+    }
+
+    #[test]
+    fn collapses_extra_whitespace_in_title() {
+        let todo = parse_quick_todo("  Write   report  ", today());
+        assert_eq!(todo.title, "Write report");
+    }
+
+    #[test]
+    fn next_weekday_is_strictly_after_today() {
+        let todo = parse_quick_todo("Standup next monday", today());
+        assert_eq!(todo.title, "Standup");
+        assert_eq!(todo.due_date.as_deref(), Some("2026-01-12"));
+
+        // 今天就是周三时，next wed 指下周三
+        let todo = parse_quick_todo("Review next Wed", today());
+        assert_eq!(todo.due_date.as_deref(), Some("2026-01-14"));
+    }
+
+    #[test]
+    fn next_without_weekday_stays_in_title() {
+        let todo = parse_quick_todo("Plan next week", today());
+        assert_eq!(todo.title, "Plan next week");
+        assert_eq!(todo.due_date, None);
+    }
+
+    #[test]
+    fn relative_and_absolute_dates() {
+        assert_eq!(parse_quick_todo("Pay rent today", today()).due_date.as_deref(), Some("2026-01-07"));
+        assert_eq!(parse_quick_todo("Pay rent TOMORROW", today()).due_date.as_deref(), Some("2026-01-08"));
+        assert_eq!(parse_quick_todo("交房租 后天", today()).due_date.as_deref(), Some("2026-01-09"));
+        assert_eq!(parse_quick_todo("Renew passport 2026-02-01", today()).due_date.as_deref(), Some("2026-02-01"));
+    }
+
+    #[test]
+    fn only_first_date_is_used() {
+        let todo = parse_quick_todo("Move meeting today tomorrow", today());
+        assert_eq!(todo.due_date.as_deref(), Some("2026-01-07"));
+        assert_eq!(todo.title, "Move meeting tomorrow");
+    }
+
+    #[test]
+    fn invalid_date_stays_in_title() {
+        let todo = parse_quick_todo("Fix 2026-02-30 report", today());
+        assert_eq!(todo.due_date, None);
+        assert_eq!(todo.title, "Fix 2026-02-30 report");
+    }
+
+    #[test]
+    fn priority_aliases_and_unknown_levels() {
+        assert_eq!(parse_quick_todo("Task !L", today()).priority, "low");
+        assert_eq!(parse_quick_todo("任务 !高", today()).priority, "high");
+
+        let todo = parse_quick_todo("Task !urgent", today());
+        assert_eq!(todo.priority, DEFAULT_PRIORITY);
+        assert_eq!(todo.title, "Task !urgent");
+
+        // 多个优先级以最后一个为准
+        assert_eq!(parse_quick_todo("Task !low !high", today()).priority, "high");
+    }
+
+    #[test]
+    fn tags_are_deduplicated_and_bare_hash_is_kept() {
+        let todo = parse_quick_todo("Shop #food #home #food #", today());
+        assert_eq!(todo.tags, Some(vec!["food".to_string(), "home".to_string()]));
+        assert_eq!(todo.title, "Shop #");
+    }
+
+    #[test]
+    fn chinese_input() {
+        let todo = parse_quick_todo("明天 买菜 #家务 !中", today());
+        assert_eq!(todo.title, "买菜");
+        assert_eq!(todo.tags, Some(vec!["家务".to_string()]));
+        assert_eq!(todo.priority, "medium");
+        assert_eq!(todo.due_date.as_deref(), Some("2026-01-08"));
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+use std::time::Duration;
+use chrono::{Local, NaiveDate, NaiveTime};
+use tauri::plugin::PermissionState;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+use crate::models::{HabitReminder, PomodoroSession};
+use crate::DatabaseState;
+
+const POLL_INTERVAL_SECS: u64 = 60;
+
+// 当天已经提醒过的习惯，跨天后清空
+#[derive(Default)]
+struct ReminderState {
+    date: Option<NaiveDate>,
+    notified: HashSet<String>,
+}
+
+// 已经提醒过的日程发生，以 (日程 id, 提醒时间) 区分同一重复日程的不同发生
+#[derive(Default)]
+struct EventReminderState {
+    notified: HashSet<(String, String)>,
+}
+
+// 发送系统通知，没有权限时先请求一次；用户拒绝或发送失败只记录日志，返回是否已发送
+pub fn show_notification(app: &AppHandle, title: &str, body: &str) -> bool {
+    let notification = app.notification();
+    let granted = match notification.permission_state() {
+        Ok(PermissionState::Granted) => true,
+        Ok(PermissionState::Denied) => false,
+        Ok(_) => matches!(notification.request_permission(), Ok(PermissionState::Granted)),
+        Err(e) => {
+            tracing::warn!(error = %e, "无法获取通知权限状态");
+            false
+        }
+    };
+    if !granted {
+        tracing::debug!(title, "没有通知权限，跳过系统通知");
+        return false;
+    }
+
+    match notification.builder().title(title).body(body).show() {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::warn!(title, error = %e, "系统通知发送失败");
+            false
+        }
+    }
+}
+
+// 番茄钟会话完成后的系统通知，是否发送由番茄钟设置的 notification_enabled 决定
+pub fn notify_pomodoro_completed(app: &AppHandle, session: &PomodoroSession) {
+    let (title, body) = match session.session_type.as_str() {
+        "work" => ("番茄钟完成", "专注时间结束，休息一下吧"),
+        _ => ("休息结束", "准备开始下一个番茄钟"),
+    };
+    let body = match &session.task_title {
+        Some(task) if session.session_type == "work" => format!("「{}」{}", task, body),
+        _ => body.to_string(),
+    };
+    show_notification(app, title, &body);
+}
+
+// 每分钟检查一次习惯和日程提醒，两者互不影响
+pub async fn run_reminders(app: AppHandle) {
+    let mut habit_state = ReminderState::default();
+    let mut event_state = EventReminderState::default();
+    let mut interval = tokio::time::interval(Duration::from_secs(POLL_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        if let Err(e) = poll_habit_reminders(&app, &mut habit_state).await {
+            tracing::warn!(error = %e, "习惯提醒检查失败");
+        }
+        if let Err(e) = poll_event_reminders(&app, &mut event_state).await {
+            tracing::warn!(error = %e, "日程提醒检查失败");
+        }
+    }
+}
+
+// 到达提醒时间后，对当天未打卡的每日习惯各发送一次 habit-reminder 事件和系统通知
+async fn poll_habit_reminders(app: &AppHandle, state: &mut ReminderState) -> Result<(), String> {
+    let now = Local::now();
+    let today = now.date_naive();
+    if state.date != Some(today) {
+        state.date = Some(today);
+        state.notified.clear();
+    }
+
+    let db = app.state::<DatabaseState>();
+    let db = db.lock().await;
+    let Some(reminder_time) = db.get_habit_reminder_time().await.map_err(|e| e.to_string())? else {
+        return Ok(());
+    };
+    let reminder_time = NaiveTime::parse_from_str(&reminder_time, "%H:%M").map_err(|e| e.to_string())?;
+    if now.time() < reminder_time {
+        return Ok(());
+    }
+
+    let date = today.format("%Y-%m-%d").to_string();
+    let habits = db.get_unfinished_daily_habits(&date).await.map_err(|e| e.to_string())?;
+    drop(db);
+
+    for habit in habits {
+        if state.notified.contains(&habit.id) {
+            continue;
+        }
+
+        // 直接使用习惯上缓存的连续天数，不再逐个重新计算
+        let current_streak = habit.current_streak;
+
+        let body = if current_streak > 0 {
+            format!("「{}」今天还没有打卡，已连续 {} 天", habit.name, current_streak)
+        } else {
+            format!("「{}」今天还没有打卡", habit.name)
+        };
+        let reminder = HabitReminder {
+            habit_id: habit.id.clone(),
+            name: habit.name,
+            color: habit.color,
+            current_streak,
+        };
+        match app.emit("habit-reminder", reminder) {
+            Ok(()) => {
+                show_notification(app, "习惯打卡提醒", &body);
+                state.notified.insert(habit.id);
+            }
+            Err(e) => tracing::warn!(habit_id = %habit.id, error = %e, "habit-reminder 事件发送失败"),
+        }
+    }
+
+    Ok(())
+}
+
+// 提醒时间落在上一分钟内的日程发送 event-reminder 事件，开启了通知的日程同时发送系统通知
+async fn poll_event_reminders(app: &AppHandle, state: &mut EventReminderState) -> Result<(), String> {
+    let window = POLL_INTERVAL_SECS.div_ceil(60) as i64;
+    let from = (Local::now().naive_local() - chrono::Duration::minutes(window))
+        .format("%Y-%m-%dT%H:%M:%S")
+        .to_string();
+    // 只保留仍可能落在查询范围内的记录
+    state.notified.retain(|(_, remind_at)| *remind_at >= from);
+
+    let db = app.state::<DatabaseState>();
+    let db = db.lock().await;
+    let reminders = db.get_upcoming_reminders(&from, window).await.map_err(|e| e.to_string())?;
+    drop(db);
+
+    for reminder in reminders {
+        let key = (reminder.event.id.clone(), reminder.remind_at.clone());
+        if state.notified.contains(&key) {
+            continue;
+        }
+
+        let event = &reminder.event;
+        let title = event.title.clone();
+        let mut body = match (event.is_all_day, event.start_time.as_deref()) {
+            (false, Some(start_time)) => format!("{} {} 开始", event.date, start_time),
+            _ => format!("{} 全天", event.date),
+        };
+        if let Some(location) = event.location.as_deref().filter(|location| !location.trim().is_empty()) {
+            body.push_str(&format!(" · {}", location));
+        }
+        let notification_enabled = event.notification_enabled;
+
+        match app.emit("event-reminder", &reminder) {
+            Ok(()) => {
+                if notification_enabled {
+                    show_notification(app, &title, &body);
+                }
+                state.notified.insert(key);
+            }
+            Err(e) => tracing::warn!(event_id = %reminder.event.id, error = %e, "event-reminder 事件发送失败"),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,286 @@
+// 模糊搜索相关的工具函数
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+// 粗筛最多使用的二元组数量，避免生成过长的 SQL
+const MAX_PREFILTER_GRAMS: usize = 32;
+
+// 转小写并去掉变音符号（"Café" -> "cafe"），搜索词和被搜索的文本都先经过这里
+pub fn normalize(text: &str) -> String {
+    text.nfd().filter(|c| !is_combining_mark(*c)).collect::<String>().to_lowercase()
+}
+
+pub fn tokenize(query: &str) -> Vec<String> {
+    query.split_whitespace().map(normalize).collect()
+}
+
+// 用于 SQL 粗筛的字符二元组，单个字符的词直接使用本身；
+// 错一个字符的词仍然会保留至少一个正确的二元组
+pub fn bigrams(tokens: &[String]) -> Vec<String> {
+    let mut grams = Vec::new();
+    for token in tokens {
+        let chars: Vec<char> = token.chars().collect();
+        if chars.len() < 2 {
+            grams.push(token.clone());
+            continue;
+        }
+        for pair in chars.windows(2) {
+            let gram: String = pair.iter().collect();
+            if !grams.contains(&gram) {
+                grams.push(gram);
+            }
+        }
+    }
+    grams.truncate(MAX_PREFILTER_GRAMS);
+    grams
+}
+
+// 生成 LIKE '%...%' 模式，配合 ESCAPE '\' 使用
+pub fn like_contains(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+// 单个词与文本的匹配度（0~1）：包含即为 1，否则取与文本中各单词的编辑距离相似度
+pub fn token_score(token: &str, text: &str) -> f64 {
+    let text = normalize(text);
+    if text.contains(token) {
+        return 1.0;
+    }
+
+    let token_len = token.chars().count();
+    let max_distance = match token_len {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    };
+
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .filter_map(|word| {
+            let distance = levenshtein(token, word);
+            let longest = token_len.max(word.chars().count());
+            if distance <= max_distance {
+                Some(1.0 - distance as f64 / longest as f64)
+            } else {
+                None
+            }
+        })
+        .fold(0.0, f64::max)
+}
+
+// 搜索框里的过滤条件，由 parse_todo_query 生成；各条件之间是“且”的关系
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TodoFilter {
+    pub priority: Option<String>,
+    pub category: Option<String>,
+    pub tags: Vec<String>, // 每个标签都必须包含
+    pub completed: Option<bool>,
+    pub due_from: Option<NaiveDate>,
+    pub due_to: Option<NaiveDate>,
+    pub overdue: bool,
+    pub text: Vec<String>, // 已经过 normalize 的自由文本词
+}
+
+// due:today / tomorrow / this-week / next-week / overdue / YYYY-MM-DD
+fn apply_due(filter: &mut TodoFilter, value: &str, today: NaiveDate, week_start: Weekday) -> bool {
+    let offset = (7 + today.weekday().num_days_from_monday() - week_start.num_days_from_monday()) % 7;
+    let this_week = today - Duration::days(offset as i64);
+    let (from, to) = match value {
+        "today" => (today, today),
+        "tomorrow" => (today + Duration::days(1), today + Duration::days(1)),
+        "this-week" => (this_week, this_week + Duration::days(6)),
+        "next-week" => (this_week + Duration::days(7), this_week + Duration::days(13)),
+        "overdue" => {
+            filter.overdue = true;
+            return true;
+        }
+        _ => match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            Ok(day) => (day, day),
+            Err(_) => return false,
+        },
+    };
+    filter.due_from = Some(from);
+    filter.due_to = Some(to);
+    true
+}
+
+// 语法：空格分隔的 field:value 和自由文本，如 "priority:high tag:work due:this-week 周报"。
+// 支持 priority、category、tag、status(open/done)、due；无法识别的字段或取值会被忽略并返回提示
+pub fn parse_todo_query(query: &str, today: NaiveDate, week_start: Weekday) -> (TodoFilter, Vec<String>) {
+    let mut filter = TodoFilter::default();
+    let mut warnings = Vec::new();
+
+    for word in query.split_whitespace() {
+        let Some((field, value)) = word.split_once(':').filter(|(field, _)| !field.is_empty() && field.chars().all(|c| c.is_ascii_alphabetic())) else {
+            filter.text.push(normalize(word));
+            continue;
+        };
+        let value_lower = value.to_lowercase();
+
+        let recognized = match field.to_lowercase().as_str() {
+            _ if value.is_empty() => false,
+            "priority" | "p" => match value_lower.as_str() {
+                "high" | "medium" | "low" => {
+                    filter.priority = Some(value_lower.clone());
+                    true
+                }
+                _ => false,
+            },
+            "category" | "cat" => {
+                filter.category = Some(value.to_string());
+                true
+            }
+            "tag" | "t" => {
+                if !filter.tags.iter().any(|tag| tag == value) {
+                    filter.tags.push(value.to_string());
+                }
+                true
+            }
+            "status" | "is" => match value_lower.as_str() {
+                "open" | "todo" => {
+                    filter.completed = Some(false);
+                    true
+                }
+                "done" | "completed" => {
+                    filter.completed = Some(true);
+                    true
+                }
+                _ => false,
+            },
+            "due" => apply_due(&mut filter, &value_lower, today, week_start),
+            _ => false,
+        };
+
+        if !recognized {
+            warnings.push(format!("已忽略无法识别的条件: {}", word));
+        }
+    }
+
+    (filter, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_strips_accents_and_case() {
+        assert_eq!(normalize("Café"), "cafe");
+        assert_eq!(normalize("CRÈME Brûlée"), "creme brulee");
+        assert_eq!(normalize("Ångström"), "angstrom");
+        assert_eq!(normalize("会议 Notes"), "会议 notes");
+    }
+
+    #[test]
+    fn normalize_handles_precomposed_and_decomposed_forms() {
+        // "é" 的预组合形式 U+00E9 和分解形式 e + U+0301
+        assert_eq!(normalize("caf\u{e9}"), normalize("cafe\u{301}"));
+    }
+
+    #[test]
+    fn levenshtein_counts_single_edits() {
+        assert_eq!(levenshtein("groceries", "groceries"), 0);
+        assert_eq!(levenshtein("grocries", "groceries"), 1);
+        assert_eq!(levenshtein("gruceries", "groceries"), 1);
+        assert_eq!(levenshtein("", "abc"), 3);
+    }
+
+    #[test]
+    fn token_score_matches_one_character_typo() {
+        assert_eq!(token_score("groceries", "Buy groceries"), 1.0);
+        assert!(token_score("grocries", "Buy groceries") > 0.0);
+        assert_eq!(token_score("xyz", "Buy groceries"), 0.0);
+    }
+
+    // 2026-01-07 是周三
+    fn wednesday() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 1, 7).unwrap()
+    }
+
+    fn day(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, month, day).unwrap()
+    }
+
+    #[test]
+    fn parse_todo_query_combines_fields_and_text() {
+        let (filter, warnings) = parse_todo_query("priority:HIGH cat:Work tag:a t:b tag:a is:done 周报 Café", wednesday(), Weekday::Mon);
+        assert!(warnings.is_empty());
+        assert_eq!(
+            filter,
+            TodoFilter {
+                priority: Some("high".to_string()),
+                category: Some("Work".to_string()),
+                tags: vec!["a".to_string(), "b".to_string()],
+                completed: Some(true),
+                text: vec!["周报".to_string(), "cafe".to_string()],
+                ..TodoFilter::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_todo_query_due_ranges_follow_week_start() {
+        let (filter, _) = parse_todo_query("due:this-week", wednesday(), Weekday::Mon);
+        assert_eq!((filter.due_from, filter.due_to), (Some(day(1, 5)), Some(day(1, 11))));
+
+        let (filter, _) = parse_todo_query("due:this-week", wednesday(), Weekday::Sun);
+        assert_eq!((filter.due_from, filter.due_to), (Some(day(1, 4)), Some(day(1, 10))));
+
+        let (filter, _) = parse_todo_query("due:next-week", wednesday(), Weekday::Mon);
+        assert_eq!((filter.due_from, filter.due_to), (Some(day(1, 12)), Some(day(1, 18))));
+
+        let (filter, _) = parse_todo_query("due:tomorrow", wednesday(), Weekday::Mon);
+        assert_eq!((filter.due_from, filter.due_to), (Some(day(1, 8)), Some(day(1, 8))));
+
+        let (filter, _) = parse_todo_query("due:2026-02-01", wednesday(), Weekday::Mon);
+        assert_eq!((filter.due_from, filter.due_to), (Some(day(2, 1)), Some(day(2, 1))));
+
+        let (filter, _) = parse_todo_query("due:overdue", wednesday(), Weekday::Mon);
+        assert!(filter.overdue);
+        assert_eq!(filter.due_from, None);
+    }
+
+    #[test]
+    fn parse_todo_query_warns_about_unrecognized_conditions() {
+        let (filter, warnings) = parse_todo_query("priority:urgent due:someday status:maybe color:red tag:", wednesday(), Weekday::Mon);
+        assert_eq!(filter, TodoFilter::default());
+        assert_eq!(warnings.len(), 5);
+        assert!(warnings[0].contains("priority:urgent"));
+    }
+
+    #[test]
+    fn parse_todo_query_treats_non_field_colons_as_text() {
+        // 字段名只能是字母，时间之类的词按自由文本处理
+        let (filter, warnings) = parse_todo_query("10:30 :tag", wednesday(), Weekday::Mon);
+        assert!(warnings.is_empty());
+        assert_eq!(filter.text, vec!["10:30", ":tag"]);
+    }
+
+    #[test]
+    fn bigrams_survive_a_typo() {
+        let typo = bigrams(&tokenize("grocries"));
+        let original = bigrams(&tokenize("groceries"));
+        assert!(typo.iter().any(|gram| original.contains(gram)));
+    }
+}
@@ -0,0 +1,147 @@
+// 软校验：不会阻止保存，只返回提示给前端；真正无效的输入仍在 database 中报错
+use chrono::NaiveDate;
+use crate::database::parse_due_date;
+use crate::models::{CalendarEvent, Todo};
+
+fn parse(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+pub fn event_warnings(event: &CalendarEvent, today: NaiveDate) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    let last_day = event.end_date.as_deref().unwrap_or(&event.date);
+    let is_repeating = event.repeat_type.as_deref().is_some_and(|repeat| repeat != "none");
+    if !is_repeating && parse(last_day).is_some_and(|day| day < today) {
+        warnings.push("日程日期已经过去".to_string());
+    }
+
+    if !event.is_all_day {
+        if let (Some(start), Some(end)) = (&event.start_time, &event.end_time) {
+            if end < start && event.end_date.is_none() {
+                warnings.push("结束时间早于开始时间，将按跨越午夜到次日结束计算".to_string());
+            }
+        }
+    }
+
+    warnings
+}
+
+pub fn todo_warnings(todo: &Todo, today: NaiveDate) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    if !todo.completed && todo.due_date.as_deref().and_then(parse_due_date).is_some_and(|due| due < today) {
+        warnings.push("截止日期早于今天".to_string());
+    }
+    if todo.priority == "high" && todo.due_date.is_none() {
+        warnings.push("高优先级待办没有设置截止日期".to_string());
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 1, 7).unwrap()
+    }
+
+    fn event(date: &str) -> CalendarEvent {
+        CalendarEvent {
+            id: "event".to_string(),
+            title: "会议".to_string(),
+            description: None,
+            date: date.to_string(),
+            end_date: None,
+            start_time: Some("09:00".to_string()),
+            end_time: Some("10:00".to_string()),
+            event_type: "work".to_string(),
+            priority: "medium".to_string(),
+            is_all_day: false,
+            reminder: None,
+            repeat_type: None,
+            repeat_until: None,
+            repeat_count: None,
+            location: None,
+            attendees: None,
+            is_favorite: false,
+            notification_enabled: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            duration_minutes: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn todo(priority: &str, due_date: Option<&str>) -> Todo {
+        Todo {
+            id: "todo".to_string(),
+            title: "待办".to_string(),
+            description: None,
+            completed: false,
+            priority: priority.to_string(),
+            tags: None,
+            due_date: due_date.map(str::to_string),
+            category: "general".to_string(),
+            auto_complete_on_subtasks: false,
+            is_archived: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn event_in_the_past_warns() {
+        assert_eq!(event_warnings(&event("2026-01-06"), today()), vec!["日程日期已经过去"]);
+        assert!(event_warnings(&event("2026-01-07"), today()).is_empty());
+    }
+
+    #[test]
+    fn past_event_still_running_or_repeating_does_not_warn() {
+        let multi_day = CalendarEvent { end_date: Some("2026-01-08".to_string()), ..event("2026-01-05") };
+        assert!(event_warnings(&multi_day, today()).is_empty());
+
+        let repeating = CalendarEvent { repeat_type: Some("weekly".to_string()), ..event("2025-12-01") };
+        assert!(event_warnings(&repeating, today()).is_empty());
+
+        let not_repeating = CalendarEvent { repeat_type: Some("none".to_string()), ..event("2025-12-01") };
+        assert_eq!(event_warnings(&not_repeating, today()).len(), 1);
+    }
+
+    #[test]
+    fn end_before_start_warns_about_midnight() {
+        let overnight = CalendarEvent {
+            start_time: Some("22:00".to_string()),
+            end_time: Some("01:00".to_string()),
+            ..event("2026-01-08")
+        };
+        assert_eq!(event_warnings(&overnight, today()).len(), 1);
+
+        let all_day = CalendarEvent { is_all_day: true, ..overnight.clone() };
+        assert!(event_warnings(&all_day, today()).is_empty());
+    }
+
+    #[test]
+    fn overdue_todo_warns_unless_completed() {
+        assert_eq!(todo_warnings(&todo("medium", Some("2026-01-06")), today()), vec!["截止日期早于今天"]);
+        assert!(todo_warnings(&todo("medium", Some("2026-01-07")), today()).is_empty());
+
+        // 前端保存的截止日期带时间
+        assert_eq!(todo_warnings(&todo("medium", Some("2026-01-06 18:00:00")), today()), vec!["截止日期早于今天"]);
+        assert!(todo_warnings(&todo("medium", Some("2026-01-07 08:00:00")), today()).is_empty());
+
+        let done = Todo { completed: true, ..todo("medium", Some("2026-01-01")) };
+        assert!(todo_warnings(&done, today()).is_empty());
+    }
+
+    #[test]
+    fn high_priority_without_due_date_warns() {
+        assert_eq!(todo_warnings(&todo("high", None), today()), vec!["高优先级待办没有设置截止日期"]);
+        assert!(todo_warnings(&todo("high", Some("2026-01-09")), today()).is_empty());
+        assert!(todo_warnings(&todo("low", None), today()).is_empty());
+    }
+}
@@ -57,17 +57,35 @@ interface CalendarEvent {
   date: string;
   start_time?: string;
   end_time?: string;
+  duration_minutes?: number; // 后端根据起止时间计算，跨越午夜时已加 24 小时
   event_type: 'work' | 'personal' | 'health' | 'study' | 'meeting' | 'other';
   priority: 'high' | 'medium' | 'low';
   is_all_day: boolean;
   reminder?: number; // 提前多少分钟提醒
   repeat_type?: 'none' | 'daily' | 'weekly' | 'monthly' | 'yearly';
+  repeat_until?: string | null; // 重复的最后日期（含）
+  repeat_count?: number | null; // 最多重复次数，与 repeat_until 先到者生效
   location?: string;
-  attendees?: string; // JSON string of array
+  attendees?: string; // JSON string of Attendee array
+  is_favorite?: boolean;
+  notification_enabled?: boolean; // 设置了提醒时是否同时发送系统通知
   created_at: string;
   updated_at: string;
+  warnings?: string[]; // 仅创建/更新时返回的非阻塞提示
 }
 
+interface Attendee {
+  name: string;
+  email?: string | null;
+  status: 'unknown' | 'accepted' | 'declined' | 'tentative';
+}
+
+// 兼容已解析的数组和后端返回的 JSON 字符串
+const parseAttendees = (attendees: unknown): Attendee[] => {
+  if (!attendees) return [];
+  return (typeof attendees === 'string' ? JSON.parse(attendees) : attendees) as Attendee[];
+};
+
 interface CreateEventRequest {
   title: string;
   description?: string;
@@ -79,8 +97,11 @@ interface CreateEventRequest {
   is_all_day: boolean;
   reminder?: number;
   repeat_type?: string;
+  repeat_until?: string;
+  repeat_count?: number;
   location?: string;
-  attendees?: string[];
+  attendees?: Attendee[];
+  notification_enabled?: boolean;
 }
 
 interface UpdateEventRequest {
@@ -88,6 +109,8 @@ interface UpdateEventRequest {
   title: string;
   description?: string;
   date: string;
+  end_date?: string; // 不传时后端保持原值
+  clear_end_date?: boolean;
   start_time?: string;
   end_time?: string;
   event_type: string;
@@ -95,8 +118,12 @@ interface UpdateEventRequest {
   is_all_day: boolean;
   reminder?: number;
   repeat_type?: string;
+  repeat_until?: string; // 不传时后端保持原值
+  repeat_count?: number; // 不传时后端保持原值
+  clear_repeat_limit?: boolean;
   location?: string;
-  attendees?: string[];
+  attendees?: Attendee[];
+  notification_enabled?: boolean;
 }
 
 export const Calendar: React.FC<CalendarProps> = ({ darkMode = false }) => {
@@ -213,16 +240,20 @@ export const Calendar: React.FC<CalendarProps> = ({ darkMode = false }) => {
         reminder: values.reminder,
         repeat_type: values.repeat_type,
         location: values.location,
-        attendees: values.attendees?.split(',').map((a: string) => a.trim()).filter(Boolean),
+        // 保留已有参与者的邮箱和回复状态
+        attendees: values.attendees?.split(',').map((a: string) => a.trim()).filter(Boolean)
+          .map((name: string) => parseAttendees(editingEvent?.attendees).find(a => a.name === name) ?? { name, status: 'unknown' }),
       };
 
+      let saved: CalendarEvent;
       if (editingEvent) {
-        await invoke('update_event', { request: eventData });
+        saved = await invoke('update_event', { request: eventData });
         message.success('事件已更新');
       } else {
-        await invoke('create_event', { request: eventData });
+        saved = await invoke('create_event', { request: eventData });
         message.success('事件已创建');
       }
+      saved.warnings?.forEach(warning => message.warning(warning));
 
       await loadEvents();
       setShowEventModal(false);
@@ -253,8 +284,7 @@ export const Calendar: React.FC<CalendarProps> = ({ darkMode = false }) => {
   // 编辑事件
   const handleEditEvent = (event: CalendarEvent) => {
     setEditingEvent(event);
-    const attendeesList = event.attendees ?
-      (typeof event.attendees === 'string' ? JSON.parse(event.attendees) : event.attendees) : [];
+    const attendeesList = parseAttendees(event.attendees);
     form.setFieldsValue({
       title: event.title,
       description: event.description,
@@ -269,7 +299,7 @@ export const Calendar: React.FC<CalendarProps> = ({ darkMode = false }) => {
       reminder: event.reminder,
       repeat_type: event.repeat_type,
       location: event.location,
-      attendees: attendeesList.join(', '),
+      attendees: attendeesList.map(a => a.name).join(', '),
     });
     setShowEventModal(true);
   };
@@ -539,17 +569,18 @@ export const Calendar: React.FC<CalendarProps> = ({ darkMode = false }) => {
                         )}
                       </div>
                       {(() => {
-                        const attendeesList = event.attendees ?
-                          (typeof event.attendees === 'string' ? JSON.parse(event.attendees) : event.attendees) : [];
+                        const attendeesList = parseAttendees(event.attendees);
                         return attendeesList.length > 0 && (
                           <div className="mt-3 flex items-center space-x-2">
                             <TeamOutlined className="text-blue-600" />
                             <Text className="text-sm">参与者: </Text>
                             <Avatar.Group size="small" maxCount={3}>
-                              {attendeesList.map((attendee: string, index: number) => (
-                                <Avatar key={index} className="bg-blue-500">
-                                  {attendee.charAt(0)}
-                                </Avatar>
+                              {attendeesList.map((attendee, index) => (
+                                <Tooltip key={index} title={attendee.email ? `${attendee.name} <${attendee.email}>` : attendee.name}>
+                                  <Avatar className="bg-blue-500">
+                                    {attendee.name.charAt(0)}
+                                  </Avatar>
+                                </Tooltip>
                               ))}
                             </Avatar.Group>
                           </div>
@@ -50,12 +50,32 @@ interface Habit {
   color: string;
   target: number; // 目标次数（每天/每周）
   unit: string; // 单位（次、分钟、页等）
-  frequency: 'daily' | 'weekly';
+  frequency: string; // 'daily'、'weekly' 或自定义星期的 JSON
   is_active: boolean;
+  value_based?: boolean; // 打卡数值达到 target 即视为完成
+  current_streak?: number; // 后端缓存的连续天数
+  longest_streak?: number;
   created_at: string;
   updated_at: string;
 }
 
+const WEEKDAY_LABELS = ['一', '二', '三', '四', '五', '六', '日'];
+
+// 自定义频率形如 {"type":"custom","days":[1,3,5]}，1 = 周一
+const formatFrequency = (frequency: string): string => {
+  if (frequency === 'daily') return '每日';
+  if (frequency === 'weekly') return '每周';
+  try {
+    const spec = JSON.parse(frequency);
+    if (spec?.type === 'custom' && Array.isArray(spec.days)) {
+      return '每周' + spec.days.map((day: number) => WEEKDAY_LABELS[day - 1]).join('、');
+    }
+  } catch {
+    // 无法解析时原样显示
+  }
+  return frequency;
+};
+
 interface HabitRecord {
   id: string;
   habit_id: string;
@@ -87,6 +107,9 @@ interface UpdateHabitRequest {
   unit: string;
   frequency: string;
   is_active: boolean;
+  skip_weekends?: boolean; // 不传时后端保持原值
+  weekly_target?: number; // 不传时后端保持原值
+  clear_weekly_target?: boolean;
 }
 
 export const HabitTracker: React.FC<HabitTrackerProps> = ({ darkMode = false }) => {
@@ -355,7 +378,7 @@ export const HabitTracker: React.FC<HabitTrackerProps> = ({ darkMode = false })
           <div>
             <Text className="font-semibold">{habit.name}</Text>
             <div className="text-xs text-gray-500 mt-1">
-              {habit.target} {habit.unit} / {habit.frequency === 'daily' ? '每日' : '每周'}
+              {habit.target} {habit.unit} / {formatFrequency(habit.frequency)}
             </div>
           </div>
           
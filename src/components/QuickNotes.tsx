@@ -44,6 +44,7 @@ interface Note {
   is_archived: boolean;
   created_at: string;
   updated_at: string;
+  warnings?: string[]; // 仅创建时返回的非阻塞提示
 }
 
 interface CreateNoteRequest {
@@ -146,7 +147,7 @@ export const QuickNotes: React.FC<QuickNotesProps> = ({ darkMode = false }) => {
         title: values.title,
         content: values.content,
         tags: values.tags?.split(',').map((tag: string) => tag.trim()).filter(Boolean),
-        category: values.category || 'general',
+        category: values.category || '', // 留空时由后端套用默认分类
         color: values.color || '#fef3c7',
       };
 
@@ -160,8 +161,9 @@ export const QuickNotes: React.FC<QuickNotesProps> = ({ darkMode = false }) => {
         await invoke('update_note', { request: updateRequest });
         message.success('便笺已更新');
       } else {
-        await invoke('create_note', { request: requestData });
+        const saved = await invoke<Note>('create_note', { request: requestData });
         message.success('便笺已创建');
+        saved.warnings?.forEach(warning => message.warning(warning));
       }
 
       loadNotes();
@@ -78,6 +78,7 @@ interface Todo {
   category: string;
   created_at: string;
   updated_at: string;
+  warnings?: string[]; // 仅创建/更新时返回的非阻塞提示
 }
 
 interface CreateTodoRequest {
@@ -254,7 +255,7 @@ export const TodoList: React.FC<TodoListProps> = ({ darkMode = false }) => {
         due_date: values.due_date && values.due_time
           ? dayjs(values.due_date).hour(values.due_time.hour()).minute(values.due_time.minute()).format('YYYY-MM-DD HH:mm:ss')
           : values.due_date?.format('YYYY-MM-DD'),
-        category: values.category || 'general',
+        category: values.category || '', // 留空时由后端套用默认分类
       };
 
       if (editingTodo) {
@@ -263,11 +264,13 @@ export const TodoList: React.FC<TodoListProps> = ({ darkMode = false }) => {
           id: editingTodo.id,
           completed: editingTodo.completed,
         };
-        await invoke('update_todo', { request: updateRequest });
+        const saved: Todo = await invoke('update_todo', { request: updateRequest });
         message.success('待办事项已更新');
+        saved.warnings?.forEach(warning => message.warning(warning));
       } else {
-        await invoke('create_todo', { request: requestData });
+        const saved: Todo = await invoke('create_todo', { request: requestData });
         message.success('待办事项已创建');
+        saved.warnings?.forEach(warning => message.warning(warning));
       }
 
       loadTodos();